@@ -0,0 +1,58 @@
+// Downloads the prebuilt PDFium library the app bundles for in-process PDF
+// rendering (the `pdfium` cargo feature) into src-tauri/resources/pdfium.
+// Uses the Tauri target when building (TAURI_ENV_TARGET_TRIPLE), the host
+// platform otherwise. Pass --force to replace an existing copy.
+import { execFileSync } from 'node:child_process';
+import { copyFileSync, existsSync, mkdirSync, mkdtempSync, rmSync, writeFileSync } from 'node:fs';
+import { tmpdir } from 'node:os';
+import { dirname, join } from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+const RELEASES = 'https://github.com/bblanchon/pdfium-binaries/releases/latest/download';
+const destDir = join(dirname(fileURLToPath(import.meta.url)), '..', 'src-tauri', 'resources', 'pdfium');
+
+function target() {
+  const triple = process.env.TAURI_ENV_TARGET_TRIPLE;
+  if (triple) {
+    const arch = triple.startsWith('aarch64') ? 'arm64' : 'x64';
+    if (triple.includes('windows')) return { os: 'win', arch };
+    if (triple.includes('apple')) return { os: 'mac', arch: triple.startsWith('universal') ? 'univ' : arch };
+    return { os: 'linux', arch };
+  }
+  const os = { win32: 'win', darwin: 'mac' }[process.platform] ?? 'linux';
+  return { os, arch: process.arch === 'arm64' ? 'arm64' : 'x64' };
+}
+
+const { os, arch } = target();
+// Where each archive keeps the library, and the name the app looks for
+const library = {
+  linux: 'lib/libpdfium.so',
+  mac: 'lib/libpdfium.dylib',
+  win: 'bin/pdfium.dll',
+}[os];
+const dest = join(destDir, library.split('/')[1]);
+
+if (existsSync(dest) && !process.argv.includes('--force')) {
+  console.log(`PDFium already present at ${dest}`);
+  process.exit(0);
+}
+
+const archive = `pdfium-${os}-${arch}.tgz`;
+console.log(`Downloading ${archive}`);
+const response = await fetch(`${RELEASES}/${archive}`);
+if (!response.ok) {
+  console.error(`Failed to download ${archive}: HTTP ${response.status}`);
+  process.exit(1);
+}
+
+const workDir = mkdtempSync(join(tmpdir(), 'fileflip-pdfium-'));
+try {
+  const tgz = join(workDir, archive);
+  writeFileSync(tgz, Buffer.from(await response.arrayBuffer()));
+  execFileSync('tar', ['-xzf', tgz, '-C', workDir, library]);
+  mkdirSync(destDir, { recursive: true });
+  copyFileSync(join(workDir, library), dest);
+  console.log(`PDFium copied to ${dest}`);
+} finally {
+  rmSync(workDir, { recursive: true, force: true });
+}
@@ -0,0 +1,211 @@
+use crate::ConversionError;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Delay, Frame, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// ============================================================================
+// Animation Options
+// ============================================================================
+
+/// Options for animated images (GIF, WebP, APNG) and video clips converted
+/// to GIF or WebP.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationOptions {
+    /// Besides the converted file, write every frame of an animated source
+    /// to its own file named `<output>_frame<N>`.
+    pub extract_frames: bool,
+    /// Frame rate of GIF/WebP made from video. Defaults to 10.
+    pub fps: Option<f32>,
+    /// Where the video clip starts, in seconds.
+    pub start: Option<f64>,
+    /// Length of the video clip in seconds. Defaults to the rest of the video.
+    pub duration: Option<f64>,
+}
+
+/// Frame rate of animations made from video when none is given.
+pub(crate) const DEFAULT_VIDEO_FPS: f32 = 10.0;
+
+// ============================================================================
+// Decoding
+// ============================================================================
+
+/// One fully composited frame of an animation.
+pub(crate) struct AnimationFrame {
+    pub image: RgbaImage,
+    pub delay_ms: u32,
+}
+
+/// Formats that can hold an animation.
+pub(crate) fn can_animate(extension: &str) -> bool {
+    matches!(extension, "gif" | "webp" | "png" | "apng")
+}
+
+fn decode_error(e: impl std::fmt::Display) -> ConversionError {
+    ConversionError::DecodeError(e.to_string())
+}
+
+/// Decodes every frame of an animated GIF, WebP or PNG. The decoders
+/// composite each frame onto the full canvas, applying the previous frame's
+/// disposal and blending, so the frames can be re-encoded independently.
+/// Returns `None` for single-frame files, which take the still-image path.
+pub(crate) fn load_frames(
+    path: &Path,
+    extension: &str,
+) -> Result<Option<Vec<AnimationFrame>>, ConversionError> {
+    let reader =
+        BufReader::new(File::open(path).map_err(|e| ConversionError::ReadError(e.to_string()))?);
+
+    let frames = match extension {
+        "gif" => GifDecoder::new(reader).map_err(decode_error)?.into_frames(),
+        "webp" => {
+            let decoder = WebPDecoder::new(reader).map_err(decode_error)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(reader).map_err(decode_error)?;
+            if !decoder.is_apng().map_err(decode_error)? {
+                return Ok(None);
+            }
+            decoder.apng().map_err(decode_error)?.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    let frames = frames.collect_frames().map_err(decode_error)?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        frames
+            .into_iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                let delay_ms = numer / denom.max(1);
+                AnimationFrame {
+                    // Browsers play delays under 20 ms at 100 ms, and so do we
+                    delay_ms: if delay_ms < 20 { 100 } else { delay_ms },
+                    image: frame.into_buffer(),
+                }
+            })
+            .collect(),
+    ))
+}
+
+// ============================================================================
+// Encoding
+// ============================================================================
+
+/// Writes the frames as an endlessly looping animation in `format` (GIF,
+/// WebP or PNG, which becomes APNG). All frames must have the same size.
+pub(crate) fn save_animation(
+    frames: &[AnimationFrame],
+    output_path: &Path,
+    format: ImageFormat,
+    quality: u8,
+    lossless: bool,
+) -> Result<(), ConversionError> {
+    match format {
+        ImageFormat::Gif => save_gif(frames, output_path, quality),
+        ImageFormat::WebP => save_webp(frames, output_path, quality, lossless),
+        ImageFormat::Png => save_apng(frames, output_path),
+        other => Err(ConversionError::UnsupportedFormat(format!(
+            "{:?} cannot hold an animation",
+            other
+        ))),
+    }
+}
+
+fn encode_error(e: impl std::fmt::Display) -> ConversionError {
+    ConversionError::EncodeError(e.to_string())
+}
+
+fn create(output_path: &Path) -> Result<BufWriter<File>, ConversionError> {
+    File::create(output_path)
+        .map(BufWriter::new)
+        .map_err(|e| ConversionError::WriteError(e.to_string()))
+}
+
+fn save_gif(
+    frames: &[AnimationFrame],
+    output_path: &Path,
+    quality: u8,
+) -> Result<(), ConversionError> {
+    // Palette quantisation speed: 1 samples every pixel, 30 is fastest
+    let speed = ((100 - quality.min(100) as i32) / 10 + 1).clamp(1, 30);
+    let mut encoder = GifEncoder::new_with_speed(create(output_path)?, speed);
+    encoder.set_repeat(Repeat::Infinite).map_err(encode_error)?;
+    encoder
+        .encode_frames(frames.iter().map(|frame| {
+            Frame::from_parts(
+                frame.image.clone(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(frame.delay_ms, 1),
+            )
+        }))
+        .map_err(encode_error)
+}
+
+fn save_webp(
+    frames: &[AnimationFrame],
+    output_path: &Path,
+    quality: u8,
+    lossless: bool,
+) -> Result<(), ConversionError> {
+    let (width, height) = frames[0].image.dimensions();
+    let mut config = webp::WebPConfig::new()
+        .map_err(|_| encode_error("WebP: failed to initialise the encoder"))?;
+    config.lossless = lossless as i32;
+    config.quality = quality as f32;
+
+    // Frames carry their start time. libwebp gives the last frame the average
+    // duration of the others, as the container stores no end time.
+    let mut encoder = webp::AnimEncoder::new(width, height, &config);
+    encoder.set_loop_count(0);
+    let mut timestamp = 0;
+    for frame in frames {
+        encoder.add_frame(webp::AnimFrame::from_rgba(
+            frame.image.as_raw(),
+            width,
+            height,
+            timestamp,
+        ));
+        timestamp += frame.delay_ms as i32;
+    }
+
+    let data = encoder
+        .try_encode()
+        .map_err(|e| encode_error(format!("WebP: {:?}", e)))?;
+    fs::write(output_path, &*data).map_err(|e| ConversionError::WriteError(e.to_string()))
+}
+
+fn save_apng(frames: &[AnimationFrame], output_path: &Path) -> Result<(), ConversionError> {
+    let (width, height) = frames[0].image.dimensions();
+    let mut encoder = png::Encoder::new(create(output_path)?, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(encode_error)?;
+
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    for frame in frames {
+        writer
+            .set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)
+            .map_err(encode_error)?;
+        writer
+            .write_image_data(frame.image.as_raw())
+            .map_err(encode_error)?;
+    }
+    writer.finish().map_err(encode_error)
+}
@@ -0,0 +1,234 @@
+use crate::jobs::{self, JobHandle};
+use crate::{
+    convert_file_with_options, get_media_category, ConversionError, ConversionOptions,
+    ConversionResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Default number of concurrent external processes (FFmpeg, LibreOffice, ...).
+/// These tools are multithreaded themselves, so running many at once mostly
+/// adds memory pressure.
+const DEFAULT_PROCESS_WORKERS: usize = 2;
+
+// ============================================================================
+// Batch Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchOptions {
+    /// Options applied to every file in the batch.
+    #[serde(flatten)]
+    pub conversion: ConversionOptions,
+    /// Worker limit for in-process (CPU-bound) conversions. Defaults to the
+    /// number of available cores.
+    pub max_cpu_workers: Option<usize>,
+    /// Worker limit for conversions that spawn an external tool.
+    pub max_process_workers: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub original_size: u64,
+    pub new_size: u64,
+    /// Bytes saved across successful conversions (negative if outputs grew).
+    pub bytes_saved: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResult {
+    /// One result per input, in input order.
+    pub results: Vec<ConversionResult>,
+    pub summary: BatchSummary,
+}
+
+impl BatchSummary {
+    fn from_results(results: &[ConversionResult]) -> Self {
+        let mut summary = BatchSummary {
+            total: results.len(),
+            ..Default::default()
+        };
+
+        for result in results {
+            if result.success {
+                let original = result.original_size.unwrap_or(0);
+                let new = result.new_size.unwrap_or(0);
+                summary.succeeded += 1;
+                summary.original_size += original;
+                summary.new_size += new;
+                summary.bytes_saved += original as i64 - new as i64;
+            } else {
+                summary.failed += 1;
+            }
+        }
+
+        summary
+    }
+}
+
+// ============================================================================
+// Scheduling
+// ============================================================================
+
+/// Whether converting `input_ext` to `output_format` runs an external tool
+/// rather than in-process code.
+fn uses_external_tool(input_ext: &str, output_format: &str) -> bool {
+    let to = output_format.to_lowercase();
+    match (get_media_category(input_ext), get_media_category(&to)) {
+        ("audio", _) | ("video", _) => true,
+        ("document", "image") => true,
+        ("document", _) | (_, "document") => {
+            matches!(input_ext, "docx" | "doc" | "odt" | "epub")
+                || matches!(to.as_str(), "docx" | "doc" | "odt" | "epub")
+        }
+        _ => false,
+    }
+}
+
+/// Groups inputs that would resolve to the same output file name so they are
+/// converted one after another. Output names are picked by checking which
+/// files already exist, so converting `a.png` and `a.jpg` to WebP in parallel
+/// could otherwise make both pick `a.webp`.
+fn group_by_output_name(inputs: &[String], options: &BatchOptions) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut index_by_key: HashMap<(String, String), usize> = HashMap::new();
+
+    for (i, input) in inputs.iter().enumerate() {
+        let path = Path::new(input);
+        let dir = match options.conversion.output_dir.as_deref() {
+            Some(dir) if !dir.is_empty() => dir.to_string(),
+            _ => path
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match index_by_key.get(&(dir.clone(), stem.clone())) {
+            Some(&group) => groups[group].push(i),
+            None => {
+                index_by_key.insert((dir, stem), groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    groups
+}
+
+/// Runs `groups` on `workers` threads, sending each `(index, result)` pair to `tx`.
+fn run_pool<'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    groups: Vec<Vec<usize>>,
+    workers: usize,
+    inputs: &'scope [String],
+    options: &'scope BatchOptions,
+    job: Option<Arc<JobHandle>>,
+    tx: mpsc::Sender<(usize, ConversionResult)>,
+) {
+    if groups.is_empty() {
+        return;
+    }
+
+    let groups = Arc::new(groups);
+    let next = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..workers.clamp(1, groups.len()) {
+        let groups = groups.clone();
+        let next = next.clone();
+        let tx = tx.clone();
+        let job = job.clone();
+
+        scope.spawn(move || {
+            let work = || loop {
+                let g = next.fetch_add(1, Ordering::SeqCst);
+                let Some(group) = groups.get(g) else {
+                    break;
+                };
+
+                for &i in group {
+                    let result = match jobs::check_cancelled() {
+                        Ok(()) => convert_file_with_options(&inputs[i], &options.conversion, None),
+                        Err(e) => ConversionResult::error(e.to_string()),
+                    };
+                    if tx.send((i, result)).is_err() {
+                        return;
+                    }
+                }
+            };
+
+            // Workers inherit the caller's job so cancelling it stops the whole batch
+            match &job {
+                Some(job) => job.run(work),
+                None => work(),
+            }
+        });
+    }
+}
+
+// ============================================================================
+// Batch Conversion
+// ============================================================================
+
+/// Converts every input with the same options on a bounded worker pool.
+///
+/// In-process conversions (images, text documents) and conversions that spawn
+/// an external tool (FFmpeg, LibreOffice, Pandoc, ImageMagick) are scheduled on
+/// separate pools with separate limits. Results are returned in input order.
+///
+/// When called inside `JobHandle::run`, cancelling the job stops the batch;
+/// files that had not started yet are reported as cancelled.
+pub fn convert_batch(inputs: &[String], options: &BatchOptions) -> BatchResult {
+    let cpu_workers = options.max_cpu_workers.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    let process_workers = options
+        .max_process_workers
+        .unwrap_or(DEFAULT_PROCESS_WORKERS);
+
+    let (cpu_groups, process_groups): (Vec<_>, Vec<_>) = group_by_output_name(inputs, options)
+        .into_iter()
+        .partition(|group| {
+            !group.iter().any(|&i| {
+                let ext = Path::new(&inputs[i])
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or("")
+                    .to_lowercase();
+                uses_external_tool(&ext, &options.conversion.output_format)
+            })
+        });
+
+    let mut slots: Vec<Option<ConversionResult>> = (0..inputs.len()).map(|_| None).collect();
+    let (tx, rx) = mpsc::channel();
+    let job = jobs::current();
+
+    thread::scope(|scope| {
+        run_pool(scope, cpu_groups, cpu_workers, inputs, options, job.clone(), tx.clone());
+        run_pool(scope, process_groups, process_workers, inputs, options, job, tx);
+
+        for (i, result) in rx {
+            slots[i] = Some(result);
+        }
+    });
+
+    let results: Vec<ConversionResult> = slots
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| ConversionResult::error(ConversionError::Cancelled.to_string())))
+        .collect();
+    let summary = BatchSummary::from_results(&results);
+
+    BatchResult { results, summary }
+}
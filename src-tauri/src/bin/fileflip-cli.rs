@@ -0,0 +1,732 @@
+//! Headless command-line front end for the FileFlip conversion engine.
+//!
+//! Exposes the same `convert_file` pipeline the desktop app uses, so conversions
+//! can run from shell scripts and build jobs without opening a window.
+
+use clap::{Args, Parser, Subcommand};
+use fileflip_lib::{
+    check_tools_available, convert_file_with_options, decrypt_pdf, get_supported_formats,
+    images_to_pdf, is_conversion_supported, AnimationOptions, ConversionOptions,
+    ConversionProgress, ConversionResult, CropRect, HeifOptions, IconOptions, ImageTransform,
+    LayerOptions, PageFit, PageOrientation, PageSize, PdfEncryptionOptions, PdfExportOptions,
+    PdfOptimizeOptions, PdfPageOptions, PdfToolOptions, ProgressReporter, RawMode, RawOptions,
+    ResizeMode, SvgOptions,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+// Exit codes
+const EXIT_OK: u8 = 0;
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "fileflip-cli", version, about = "Offline file converter")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert one or more files to another format
+    Convert {
+        /// Input files or glob patterns (e.g. "photos/*.png")
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Target format (e.g. jpg, webp, pdf, mp3)
+        #[arg(short, long = "to")]
+        to: String,
+
+        /// Output quality from 1 to 100
+        #[arg(short, long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: u8,
+
+        /// Directory to write converted files to (defaults to the input's directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Keep metadata from the source file
+        #[arg(long)]
+        preserve_metadata: bool,
+
+        /// With --preserve-metadata, still remove GPS location and camera serial numbers
+        #[arg(long, requires = "preserve_metadata")]
+        strip_private: bool,
+
+        /// Overwrite existing output files instead of adding a numeric suffix
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Use lossless encoding where the format supports it (WebP)
+        #[arg(long)]
+        lossless: bool,
+
+        /// Audio bitrate for audio/video conversions (e.g. 192k)
+        #[arg(short, long)]
+        bitrate: Option<String>,
+
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Show encoding progress for audio/video conversions on stderr
+        #[arg(long)]
+        progress: bool,
+
+        #[command(flatten)]
+        transform: Box<TransformArgs>,
+
+        /// Use the camera's embedded JPEG preview for RAW files instead of
+        /// developing the sensor data (much faster)
+        #[arg(long, help_heading = "Image options")]
+        raw_preview: bool,
+
+        /// Exposure compensation in stops when developing RAW files
+        #[arg(
+            long,
+            default_value_t = 0.0,
+            allow_negative_numbers = true,
+            help_heading = "Image options"
+        )]
+        raw_exposure: f32,
+
+        /// Also write each layer of PSD/XCF files to its own file
+        #[arg(long, help_heading = "Image options")]
+        export_layers: bool,
+
+        /// With --export-layers, include hidden layers too
+        #[arg(long, requires = "export_layers", help_heading = "Image options")]
+        include_hidden_layers: bool,
+
+        /// Sizes to pack into ICO/ICNS output (e.g. 16,32,256)
+        #[arg(long, value_delimiter = ',', help_heading = "Image options")]
+        icon_sizes: Vec<u32>,
+
+        /// Also write each frame of animated GIF/WebP/PNG files to its own file
+        #[arg(long, help_heading = "Animation options")]
+        extract_frames: bool,
+
+        /// Frame rate of GIF/WebP made from video [default: 10]
+        #[arg(long, help_heading = "Animation options")]
+        fps: Option<f32>,
+
+        /// Start of the video clip, in seconds
+        #[arg(long, help_heading = "Animation options")]
+        clip_start: Option<f64>,
+
+        /// Length of the video clip, in seconds
+        #[arg(long, help_heading = "Animation options")]
+        clip_duration: Option<f64>,
+
+        #[command(flatten)]
+        svg: Box<SvgArgs>,
+
+        #[command(flatten)]
+        pdf: Box<PdfArgs>,
+    },
+    /// Remove the password protection from PDFs, writing <name>_decrypted.pdf
+    Decrypt {
+        /// Protected PDF files or glob patterns
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// The user or owner password (leave empty for PDFs that open without one)
+        #[arg(short, long, default_value = "")]
+        password: String,
+
+        /// Directory to write decrypted files to (defaults to the input's directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Overwrite existing output files instead of adding a numeric suffix
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the formats a file type can be converted to
+    Formats {
+        /// Source format or extension (e.g. png)
+        from: String,
+
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check whether a conversion is supported (exit code 0 if it is)
+    Supports {
+        /// Source format or extension
+        from: String,
+
+        /// Target format or extension
+        to: String,
+    },
+    /// Report which external tools (FFmpeg, LibreOffice, Pandoc) are available
+    Tools {
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Image edits applied before encoding (see `ImageTransform`).
+#[derive(Args)]
+#[command(next_help_heading = "Image options")]
+struct TransformArgs {
+    /// Output width in pixels (height follows the aspect ratio unless given)
+    #[arg(long)]
+    width: Option<u32>,
+
+    /// Output height in pixels (width follows the aspect ratio unless given)
+    #[arg(long)]
+    height: Option<u32>,
+
+    /// How --width/--height are applied: fit, fill, pad or stretch
+    #[arg(long, default_value = "fit", value_parser = parse_resize_mode)]
+    fit: ResizeMode,
+
+    /// Downscale to at most this width
+    #[arg(long)]
+    max_width: Option<u32>,
+
+    /// Downscale to at most this height
+    #[arg(long)]
+    max_height: Option<u32>,
+
+    /// Crop region as WIDTHxHEIGHT+X+Y (e.g. 800x600+100+50)
+    #[arg(long, value_parser = parse_crop)]
+    crop: Option<CropRect>,
+
+    /// Rotate clockwise by 90, 180 or 270 degrees
+    #[arg(long, default_value_t = 0, value_parser = parse_rotation)]
+    rotate: u16,
+
+    /// Mirror the image left to right
+    #[arg(long)]
+    flip_horizontal: bool,
+
+    /// Mirror the image top to bottom
+    #[arg(long)]
+    flip_vertical: bool,
+
+    /// Ignore the EXIF orientation tag instead of rotating the pixels upright
+    #[arg(long)]
+    no_auto_orient: bool,
+}
+
+impl TransformArgs {
+    fn into_transform(self) -> ImageTransform {
+        ImageTransform {
+            auto_orient: !self.no_auto_orient,
+            crop: self.crop,
+            rotate: self.rotate,
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+            width: self.width,
+            height: self.height,
+            resize_mode: self.fit,
+            max_width: self.max_width,
+            max_height: self.max_height,
+            ..ImageTransform::default()
+        }
+    }
+}
+
+/// How SVG sources are rasterised (see `SvgOptions`).
+#[derive(Args)]
+#[command(next_help_heading = "SVG options")]
+struct SvgArgs {
+    /// Width to render SVG files at, in pixels
+    #[arg(long)]
+    svg_width: Option<u32>,
+
+    /// Height to render SVG files at, in pixels
+    #[arg(long)]
+    svg_height: Option<u32>,
+
+    /// Resolution to render SVG files at when no size is given [default: 96]
+    #[arg(long)]
+    dpi: Option<f32>,
+
+    /// Background colour for SVG files (e.g. #ffffff)
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Extra directory to load SVG fonts from (repeatable)
+    #[arg(long)]
+    font_dir: Vec<String>,
+
+    /// Directory for relative image links in SVG files [default: the SVG's directory]
+    #[arg(long)]
+    resources_dir: Option<String>,
+}
+
+impl SvgArgs {
+    fn into_options(self) -> SvgOptions {
+        SvgOptions {
+            width: self.svg_width,
+            height: self.svg_height,
+            dpi: self.dpi,
+            background: self.background,
+            font_dirs: self.font_dir,
+            resources_dir: self.resources_dir,
+        }
+    }
+}
+
+/// Page layout for images written to PDF (see `PdfPageOptions`).
+#[derive(Args)]
+#[command(next_help_heading = "PDF options")]
+struct PdfArgs {
+    /// Combine all input images into one PDF, one page each, in the order given
+    #[arg(long)]
+    combine: bool,
+
+    /// Page size: fit (the image's own size), a4 or letter
+    #[arg(long, default_value = "fit", value_parser = parse_page_size)]
+    page_size: PageSize,
+
+    /// Orientation of A4/Letter pages: auto, portrait or landscape
+    #[arg(long, default_value = "auto", value_parser = parse_orientation)]
+    orientation: PageOrientation,
+
+    /// Margin around each image, in millimetres
+    #[arg(long, default_value_t = 0.0)]
+    margin: f32,
+
+    /// How images are placed on A4/Letter pages: fit, fill, stretch or original
+    #[arg(long, default_value = "fit", value_parser = parse_page_fit)]
+    page_fit: PageFit,
+
+    /// PDF pages to export as images, one file each: all, or e.g. 1-3,7 or 5-
+    #[arg(long)]
+    pages: Option<String>,
+
+    /// Pack exported PDF pages into one ZIP (all pages unless --pages is given)
+    #[arg(long)]
+    zip: bool,
+
+    /// When optimising a PDF (--to pdf), downsample images above this DPI (0 keeps them)
+    #[arg(long, default_value_t = 150)]
+    image_dpi: u32,
+
+    /// Password of protected PDF inputs (user or owner password)
+    #[arg(long)]
+    password: Option<String>,
+
+    /// Encrypt PDF output with AES-256, asking for this password to open it
+    #[arg(long)]
+    user_password: Option<String>,
+
+    /// Password that lifts the --deny restrictions (random if only --user-password is given)
+    #[arg(long)]
+    owner_password: Option<String>,
+
+    /// With a password, forbid these actions (e.g. print,copy)
+    #[arg(long, value_delimiter = ',', value_parser = ["print", "modify", "copy", "annotate", "fill-forms", "assemble"])]
+    deny: Vec<String>,
+}
+
+impl PdfArgs {
+    fn page_options(&self) -> PdfPageOptions {
+        PdfPageOptions {
+            size: self.page_size,
+            orientation: self.orientation,
+            margin_mm: self.margin,
+            fit: self.page_fit,
+        }
+    }
+
+    fn export_options(&self) -> PdfExportOptions {
+        PdfExportOptions {
+            pages: self.pages.clone(),
+            zip: self.zip,
+        }
+    }
+
+    fn encryption_options(&self) -> PdfEncryptionOptions {
+        let allowed = |action: &str| !self.deny.iter().any(|denied| denied == action);
+        PdfEncryptionOptions {
+            user_password: self.user_password.clone(),
+            owner_password: self.owner_password.clone(),
+            allow_print: allowed("print"),
+            allow_modify: allowed("modify"),
+            allow_copy: allowed("copy"),
+            allow_annotate: allowed("annotate"),
+            allow_fill_forms: allowed("fill-forms"),
+            allow_assemble: allowed("assemble"),
+        }
+    }
+}
+
+fn parse_resize_mode(value: &str) -> Result<ResizeMode, String> {
+    match value {
+        "fit" => Ok(ResizeMode::Fit),
+        "fill" => Ok(ResizeMode::Fill),
+        "pad" => Ok(ResizeMode::Pad),
+        "stretch" => Ok(ResizeMode::Stretch),
+        _ => Err("expected fit, fill, pad or stretch".to_string()),
+    }
+}
+
+fn parse_page_size(value: &str) -> Result<PageSize, String> {
+    match value.to_lowercase().as_str() {
+        "fit" => Ok(PageSize::Fit),
+        "a4" => Ok(PageSize::A4),
+        "letter" => Ok(PageSize::Letter),
+        _ => Err("expected fit, a4 or letter".to_string()),
+    }
+}
+
+fn parse_orientation(value: &str) -> Result<PageOrientation, String> {
+    match value {
+        "auto" => Ok(PageOrientation::Auto),
+        "portrait" => Ok(PageOrientation::Portrait),
+        "landscape" => Ok(PageOrientation::Landscape),
+        _ => Err("expected auto, portrait or landscape".to_string()),
+    }
+}
+
+fn parse_page_fit(value: &str) -> Result<PageFit, String> {
+    match value {
+        "fit" => Ok(PageFit::Fit),
+        "fill" => Ok(PageFit::Fill),
+        "stretch" => Ok(PageFit::Stretch),
+        "original" => Ok(PageFit::Original),
+        _ => Err("expected fit, fill, stretch or original".to_string()),
+    }
+}
+
+fn parse_rotation(value: &str) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(degrees @ (0 | 90 | 180 | 270)) => Ok(degrees),
+        _ => Err("expected 0, 90, 180 or 270".to_string()),
+    }
+}
+
+fn parse_crop(value: &str) -> Result<CropRect, String> {
+    let invalid = || "expected WIDTHxHEIGHT+X+Y".to_string();
+    let (size, offset) = value.split_once('+').ok_or_else(invalid)?;
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+
+    Ok(CropRect {
+        x: number(x)?,
+        y: number(y)?,
+        width: number(width)?,
+        height: number(height)?,
+    })
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let code = match cli.command {
+        Command::Convert {
+            inputs,
+            to,
+            quality,
+            output_dir,
+            preserve_metadata,
+            strip_private,
+            overwrite,
+            lossless,
+            bitrate,
+            json,
+            progress,
+            transform,
+            raw_preview,
+            raw_exposure,
+            export_layers,
+            include_hidden_layers,
+            icon_sizes,
+            extract_frames,
+            fps,
+            clip_start,
+            clip_duration,
+            svg,
+            pdf,
+        } => {
+            let paths = match expand_inputs(&inputs) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::from(EXIT_USAGE);
+                }
+            };
+
+            let options = ConversionOptions {
+                output_format: to,
+                quality,
+                output_dir,
+                preserve_metadata,
+                strip_private_metadata: strip_private,
+                overwrite_existing: overwrite,
+                bitrate,
+                lossless,
+                transform: transform.into_transform(),
+                heif: HeifOptions::default(),
+                raw: RawOptions {
+                    mode: if raw_preview {
+                        RawMode::Preview
+                    } else {
+                        RawMode::Develop
+                    },
+                    exposure: raw_exposure,
+                },
+                layers: LayerOptions {
+                    export_layers,
+                    include_hidden: include_hidden_layers,
+                },
+                icon: IconOptions { sizes: icon_sizes },
+                animation: AnimationOptions {
+                    extract_frames,
+                    fps,
+                    start: clip_start,
+                    duration: clip_duration,
+                },
+                svg: svg.into_options(),
+                pdf_page: pdf.page_options(),
+                pdf_export: pdf.export_options(),
+                pdf_optimize: PdfOptimizeOptions {
+                    image_dpi: pdf.image_dpi,
+                },
+                pdf_encryption: pdf.encryption_options(),
+                pdf_password: pdf.password.clone(),
+            };
+
+            if pdf.combine {
+                if !options.output_format.eq_ignore_ascii_case("pdf") {
+                    eprintln!("error: --combine requires --to pdf");
+                    return ExitCode::from(EXIT_USAGE);
+                }
+                let inputs: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                let result = images_to_pdf(&inputs, &options);
+                if json {
+                    print_json(&result);
+                } else {
+                    print_result(&paths[0], &result);
+                }
+                return ExitCode::from(if result.success { EXIT_OK } else { EXIT_FAILED });
+            }
+
+            let results: Vec<ConversionResult> = paths
+                .iter()
+                .map(|path| {
+                    let input = path.to_string_lossy().to_string();
+                    let reporter = progress
+                        .then(|| ProgressReporter::new(input.clone(), Arc::new(print_progress)));
+                    let result = convert_file_with_options(&input, &options, reporter.as_ref());
+                    if reporter.is_some() {
+                        eprintln!();
+                    }
+                    if !json {
+                        print_result(path, &result);
+                    }
+                    result
+                })
+                .collect();
+
+            if json {
+                print_json(&results);
+            }
+
+            if results.iter().all(|r| r.success) {
+                EXIT_OK
+            } else {
+                EXIT_FAILED
+            }
+        }
+        Command::Decrypt {
+            inputs,
+            password,
+            output_dir,
+            overwrite,
+            json,
+        } => {
+            let paths = match expand_inputs(&inputs) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::from(EXIT_USAGE);
+                }
+            };
+
+            let options = PdfToolOptions {
+                output_dir,
+                overwrite_existing: overwrite,
+            };
+            let results: Vec<ConversionResult> = paths
+                .iter()
+                .map(|path| {
+                    let result = decrypt_pdf(&path.to_string_lossy(), &password, &options);
+                    if !json {
+                        print_result(path, &result);
+                    }
+                    result
+                })
+                .collect();
+
+            if json {
+                print_json(&results);
+            }
+
+            if results.iter().all(|r| r.success) {
+                EXIT_OK
+            } else {
+                EXIT_FAILED
+            }
+        }
+        Command::Formats { from, json } => {
+            let formats = get_supported_formats(from.trim_start_matches('.').to_string());
+            if json {
+                print_json(&formats);
+            } else {
+                for format in &formats {
+                    println!("{}", format);
+                }
+            }
+            if formats.is_empty() {
+                EXIT_FAILED
+            } else {
+                EXIT_OK
+            }
+        }
+        Command::Supports { from, to } => {
+            let supported = is_conversion_supported(
+                from.trim_start_matches('.').to_string(),
+                to.trim_start_matches('.').to_string(),
+            );
+            println!("{}", if supported { "yes" } else { "no" });
+            if supported {
+                EXIT_OK
+            } else {
+                EXIT_FAILED
+            }
+        }
+        Command::Tools { json } => {
+            let tools = check_tools_available();
+            if json {
+                print_json(&tools);
+            } else {
+                println!("ffmpeg:      {}", availability(tools.ffmpeg));
+                println!("libreoffice: {}", availability(tools.libreoffice));
+                println!("pandoc:      {}", availability(tools.pandoc));
+            }
+            EXIT_OK
+        }
+    };
+
+    ExitCode::from(code)
+}
+
+/// Expands glob patterns in the input list. Shells on Unix expand wildcards
+/// already, but Windows shells do not, so patterns are resolved here as well.
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+
+    for input in inputs {
+        if !input.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+
+        let entries =
+            glob::glob(input).map_err(|e| format!("Invalid pattern '{}': {}", input, e))?;
+        let before = paths.len();
+        for entry in entries.flatten() {
+            if entry.is_file() {
+                paths.push(entry);
+            }
+        }
+        if paths.len() == before {
+            return Err(format!("No files match '{}'", input));
+        }
+    }
+
+    Ok(paths)
+}
+
+fn print_result(input: &Path, result: &ConversionResult) {
+    if result.success {
+        println!(
+            "{} -> {} ({} -> {})",
+            input.display(),
+            result.output_path.as_deref().unwrap_or(""),
+            format_size(result.original_size.unwrap_or(0)),
+            format_size(result.new_size.unwrap_or(0)),
+        );
+        for extra in result.output_paths.iter().skip(1) {
+            println!("  + {}", extra);
+        }
+    } else {
+        eprintln!(
+            "{}: {}",
+            input.display(),
+            result.error.as_deref().unwrap_or("Unknown error")
+        );
+    }
+}
+
+fn print_progress(progress: ConversionProgress) {
+    let mut line = format!("\r{}: {:5.1}%", progress.job_id, progress.percent);
+    if let Some(speed) = progress.speed {
+        line.push_str(&format!("  {:.2}x", speed));
+    }
+    if let Some(eta) = progress.eta_seconds {
+        line.push_str(&format!("  ETA {}", format_duration(eta)));
+    }
+    let mut stderr = std::io::stderr();
+    let _ = write!(stderr, "{}\x1b[K", line);
+    let _ = stderr.flush();
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(s) => println!("{}", s),
+        Err(e) => eprintln!("error: failed to serialize output: {}", e),
+    }
+}
+
+fn availability(found: bool) -> &'static str {
+    if found {
+        "available"
+    } else {
+        "not found"
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let secs = seconds.round() as u64;
+    let (hrs, mins, secs) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hrs > 0 {
+        format!("{}:{:02}:{:02}", hrs, mins, secs)
+    } else {
+        format!("{}:{:02}", mins, secs)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
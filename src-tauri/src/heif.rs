@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+/// Which picture to take from a HEIF/HEIC container. Phones store bursts,
+/// depth maps and thumbnails alongside the main photo.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeifOptions {
+    /// Zero-based index among the container's top-level images (e.g. burst
+    /// frames). Defaults to the primary image.
+    pub image_index: Option<usize>,
+    /// Decode the image's embedded depth map (as grayscale) instead of the
+    /// colour image.
+    pub depth_map: bool,
+    /// Use the largest embedded thumbnail instead of the full image. Much
+    /// faster for previews; falls back to the full image if there is none.
+    pub thumbnail: bool,
+}
+
+#[cfg(feature = "heif")]
+pub(crate) use decoder::{load_heif, read_heif_metadata};
+
+#[cfg(feature = "heif")]
+mod decoder {
+    use super::HeifOptions;
+    use crate::metadata::ImageMetadata;
+    use crate::ConversionError;
+    use image::metadata::Orientation;
+    use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgba};
+    use libheif_rs::{ColorSpace, HeifContext, ImageHandle, ItemId, LibHeif, RgbChroma};
+    use std::path::Path;
+
+    fn decode_error(e: impl std::fmt::Display) -> ConversionError {
+        ConversionError::DecodeError(format!("HEIF: {}", e))
+    }
+
+    fn open(path: &Path) -> Result<HeifContext<'static>, ConversionError> {
+        let path = path.to_str().ok_or(ConversionError::InvalidPath)?;
+        HeifContext::read_from_file(path).map_err(decode_error)
+    }
+
+    /// Picks the handle described by `options`: a top-level image (or the
+    /// primary one), then optionally its depth map or thumbnail.
+    fn select_handle(
+        ctx: &HeifContext,
+        options: &HeifOptions,
+    ) -> Result<ImageHandle, ConversionError> {
+        let handle = match options.image_index {
+            Some(index) => {
+                let mut handles = ctx.top_level_image_handles();
+                if index >= handles.len() {
+                    return Err(ConversionError::DecodeError(format!(
+                        "HEIF image {} requested, but the file has {}",
+                        index,
+                        handles.len()
+                    )));
+                }
+                handles.swap_remove(index)
+            }
+            None => ctx.primary_image_handle().map_err(decode_error)?,
+        };
+
+        if options.depth_map {
+            let mut ids: Vec<ItemId> = vec![0; handle.number_of_depth_images().max(0) as usize];
+            let count = handle.depth_image_ids(&mut ids);
+            let id = ids.into_iter().take(count).next().ok_or_else(|| {
+                ConversionError::DecodeError("HEIF image has no depth map".to_string())
+            })?;
+            return handle.depth_image_handle(id).map_err(decode_error);
+        }
+
+        if options.thumbnail {
+            let mut ids: Vec<ItemId> = vec![0; handle.number_of_thumbnails()];
+            let count = handle.thumbnail_ids(&mut ids);
+            let largest = ids
+                .into_iter()
+                .take(count)
+                .filter_map(|id| handle.thumbnail(id).ok())
+                .max_by_key(|t| t.width() as u64 * t.height() as u64);
+            if let Some(thumbnail) = largest {
+                return Ok(thumbnail);
+            }
+        }
+
+        Ok(handle)
+    }
+
+    /// Decodes a HEIF/HEIC file. libheif applies the container's rotation,
+    /// mirroring and cropping, so the result is already upright.
+    pub(crate) fn load_heif(
+        path: &Path,
+        options: &HeifOptions,
+    ) -> Result<DynamicImage, ConversionError> {
+        let lib = LibHeif::new();
+        let ctx = open(path)?;
+        let handle = select_handle(&ctx, options)?;
+
+        let (width, height) = (handle.width(), handle.height());
+        let bits = handle.luma_bits_per_pixel();
+        let high_bit_depth = bits > 8;
+
+        if options.depth_map {
+            let image = lib
+                .decode(&handle, ColorSpace::Monochrome, None)
+                .map_err(decode_error)?;
+            let plane = image
+                .planes()
+                .y
+                .ok_or_else(|| decode_error("depth map has no luma plane"))?;
+            let bytes_per_sample = if high_bit_depth { 2 } else { 1 };
+            let samples = rows(plane.data, plane.stride, width as usize * bytes_per_sample, height);
+
+            return if high_bit_depth {
+                let shift = 16 - bits.min(16) as u32;
+                let data = samples
+                    .chunks_exact(2)
+                    .map(|c| u16::from_ne_bytes([c[0], c[1]]) << shift)
+                    .collect();
+                ImageBuffer::<Luma<u16>, _>::from_raw(width, height, data)
+                    .map(DynamicImage::ImageLuma16)
+                    .ok_or_else(|| decode_error("depth map size mismatch"))
+            } else {
+                ImageBuffer::<Luma<u8>, _>::from_raw(width, height, samples)
+                    .map(DynamicImage::ImageLuma8)
+                    .ok_or_else(|| decode_error("depth map size mismatch"))
+            };
+        }
+
+        let has_alpha = handle.has_alpha_channel();
+        let chroma = match (has_alpha, high_bit_depth) {
+            (false, false) => RgbChroma::Rgb,
+            (true, false) => RgbChroma::Rgba,
+            (false, true) => RgbChroma::HdrRgbLe,
+            (true, true) => RgbChroma::HdrRgbaLe,
+        };
+        let image = lib
+            .decode(&handle, ColorSpace::Rgb(chroma), None)
+            .map_err(decode_error)?;
+        let plane = image
+            .planes()
+            .interleaved
+            .ok_or_else(|| decode_error("no interleaved RGB plane"))?;
+
+        let channels = if has_alpha { 4 } else { 3 };
+        let bytes_per_sample = if high_bit_depth { 2 } else { 1 };
+        let row_bytes = width as usize * channels * bytes_per_sample;
+        let samples = rows(plane.data, plane.stride, row_bytes, height);
+
+        let img = if high_bit_depth {
+            // Scale 10/12-bit samples up to the full 16-bit range
+            let shift = 16 - bits.min(16) as u32;
+            let data: Vec<u16> = samples
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]) << shift)
+                .collect();
+            if has_alpha {
+                ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, data)
+                    .map(DynamicImage::ImageRgba16)
+            } else {
+                ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, data)
+                    .map(DynamicImage::ImageRgb16)
+            }
+        } else if has_alpha {
+            ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, samples).map(DynamicImage::ImageRgba8)
+        } else {
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, samples).map(DynamicImage::ImageRgb8)
+        };
+
+        img.ok_or_else(|| decode_error("decoded image size mismatch"))
+    }
+
+    /// Copies `height` rows of `row_bytes` out of a plane with the given stride.
+    fn rows(data: &[u8], stride: usize, row_bytes: usize, height: u32) -> Vec<u8> {
+        let mut out = Vec::with_capacity(row_bytes * height as usize);
+        for row in data.chunks(stride).take(height as usize) {
+            out.extend_from_slice(&row[..row_bytes]);
+        }
+        out
+    }
+
+    /// Reads the ICC profile, EXIF and XMP of the image `load_heif` decodes
+    /// with the same `options`. Burst frames carry their own, and depth maps
+    /// and thumbnails usually have none.
+    pub(crate) fn read_heif_metadata(path: &Path, options: &HeifOptions) -> ImageMetadata {
+        let Ok(ctx) = open(path) else {
+            return ImageMetadata::default();
+        };
+        let Ok(handle) = select_handle(&ctx, options) else {
+            return ImageMetadata::default();
+        };
+
+        let mut metadata = ImageMetadata {
+            icc: handle.color_profile_raw().map(|profile| profile.data),
+            // Pixels come out of libheif already transformed
+            orientation: Some(Orientation::NoTransforms),
+            ..Default::default()
+        };
+
+        for block in handle.all_metadata() {
+            match &block.item_type.0 {
+                b"Exif" => {
+                    // The block starts with a 4-byte offset to the TIFF header
+                    let offset = block
+                        .raw_data
+                        .get(..4)
+                        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+                    if let Some(tiff) = offset.and_then(|o| block.raw_data.get(4 + o..)) {
+                        let mut exif = tiff.to_vec();
+                        // libheif has already applied the rotation
+                        let _ = Orientation::remove_from_exif_chunk(&mut exif);
+                        metadata.exif = Some(exif);
+                    }
+                }
+                b"mime" if block.content_type == "application/rdf+xml" => {
+                    metadata.xmp = Some(block.raw_data);
+                }
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+}
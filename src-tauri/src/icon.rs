@@ -0,0 +1,208 @@
+use crate::ConversionError;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::png::PngEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+// ============================================================================
+// Icon Options
+// ============================================================================
+
+/// Sizes packed into `.ico` and `.icns` output.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconOptions {
+    /// Square sizes in pixels, one image each. Non-square sources are scaled
+    /// to fit and centred on a transparent square. Empty uses the defaults:
+    /// 16 to 256 for ICO (any size from 1 to 256), and 16 to 1024 for ICNS
+    /// (16, 32, 64, 128, 256, 512 or 1024).
+    pub sizes: Vec<u32>,
+}
+
+const ICO_DEFAULT_SIZES: &[u32] = &[16, 24, 32, 48, 64, 128, 256];
+const ICNS_DEFAULT_SIZES: &[u32] = &[16, 32, 64, 128, 256, 512, 1024];
+
+/// ICNS element types holding PNG data, by pixel size. The `@2x` retina
+/// types share their pixel size with a standard type and get the same image.
+const ICNS_TYPES: &[(u32, &[u8; 4])] = &[
+    (16, b"icp4"),
+    (32, b"icp5"),
+    (32, b"ic11"),
+    (64, b"icp6"),
+    (64, b"ic12"),
+    (128, b"ic07"),
+    (256, b"ic08"),
+    (256, b"ic13"),
+    (512, b"ic09"),
+    (512, b"ic14"),
+    (1024, b"ic10"),
+];
+
+/// Extensions of the multi-size icon containers written here.
+pub(crate) fn is_icon(extension: &str) -> bool {
+    matches!(extension, "ico" | "icns")
+}
+
+/// The sizes to write for an icon extension, largest first.
+pub(crate) fn icon_sizes(
+    extension: &str,
+    options: &IconOptions,
+) -> Result<Vec<u32>, ConversionError> {
+    let is_icns = extension == "icns";
+    let mut sizes = match (options.sizes.is_empty(), is_icns) {
+        (true, true) => ICNS_DEFAULT_SIZES.to_vec(),
+        (true, false) => ICO_DEFAULT_SIZES.to_vec(),
+        (false, _) => options.sizes.clone(),
+    };
+
+    for &size in &sizes {
+        let valid = if is_icns {
+            ICNS_TYPES.iter().any(|&(s, _)| s == size)
+        } else {
+            (1..=256).contains(&size)
+        };
+        if !valid {
+            return Err(ConversionError::EncodeError(format!(
+                "{} icons cannot hold a {}x{} image",
+                extension.to_uppercase(),
+                size,
+                size
+            )));
+        }
+    }
+
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes.dedup();
+    Ok(sizes)
+}
+
+/// Scales the image to fit a `size`×`size` square, keeping its aspect ratio,
+/// and centres it on a transparent canvas.
+fn fit_square(img: &DynamicImage, size: u32) -> RgbaImage {
+    let scaled = img.resize(size, size, FilterType::Lanczos3).to_rgba8();
+    let mut canvas = RgbaImage::new(size, size);
+    let left = (size - scaled.width()) / 2;
+    let top = (size - scaled.height()) / 2;
+    imageops::overlay(&mut canvas, &scaled, left as i64, top as i64);
+    canvas
+}
+
+fn encode_png(img: &RgbaImage) -> Result<Vec<u8>, ConversionError> {
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data)
+        .write_image(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+    Ok(data)
+}
+
+// ============================================================================
+// Writing
+// ============================================================================
+
+/// Writes every size into one `.ico` or `.icns` file, picked by the output
+/// path's extension.
+pub(crate) fn save_icon(
+    img: &DynamicImage,
+    output_path: &Path,
+    sizes: &[u32],
+) -> Result<(), ConversionError> {
+    let extension = output_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("ico")
+        .to_lowercase();
+
+    if extension == "icns" {
+        write_icns(img, output_path, sizes)
+    } else {
+        write_ico(img, output_path, sizes)
+    }
+}
+
+/// PNG-compressed frames, smallest first as Windows resource tools write them.
+fn write_ico(img: &DynamicImage, output_path: &Path, sizes: &[u32]) -> Result<(), ConversionError> {
+    let mut frames = Vec::with_capacity(sizes.len());
+    for &size in sizes.iter().rev() {
+        let square = fit_square(img, size);
+        let frame = IcoFrame::as_png(square.as_raw(), size, size, ExtendedColorType::Rgba8)
+            .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        frames.push(frame);
+    }
+
+    let output_file =
+        File::create(output_path).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    IcoEncoder::new(BufWriter::new(output_file))
+        .encode_images(&frames)
+        .map_err(|e| ConversionError::EncodeError(e.to_string()))
+}
+
+/// An Apple icon family: the `icns` header followed by one PNG element per
+/// type, each prefixed with its four-character type and big-endian length.
+fn write_icns(
+    img: &DynamicImage,
+    output_path: &Path,
+    sizes: &[u32],
+) -> Result<(), ConversionError> {
+    let mut elements = Vec::new();
+    for &size in sizes.iter().rev() {
+        let png = encode_png(&fit_square(img, size))?;
+        for (_, kind) in ICNS_TYPES.iter().filter(|&&(s, _)| s == size) {
+            elements.extend_from_slice(*kind);
+            elements.extend_from_slice(&(png.len() as u32 + 8).to_be_bytes());
+            elements.extend_from_slice(&png);
+        }
+    }
+
+    let mut data = Vec::with_capacity(elements.len() + 8);
+    data.extend_from_slice(b"icns");
+    data.extend_from_slice(&(elements.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(&elements);
+    fs::write(output_path, data).map_err(|e| ConversionError::WriteError(e.to_string()))
+}
+
+// ============================================================================
+// Reading
+// ============================================================================
+
+/// Decodes the largest PNG-encoded image of an `.icns` file. Legacy
+/// RLE and JPEG 2000 elements are skipped.
+pub(crate) fn load_icns(path: &Path) -> Result<DynamicImage, ConversionError> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    let data = fs::read(path).map_err(|e| ConversionError::ReadError(e.to_string()))?;
+    let error = |message: &str| ConversionError::DecodeError(format!("ICNS: {}", message));
+    if data.len() < 8 || &data[..4] != b"icns" {
+        return Err(error("not an Apple icon file"));
+    }
+
+    let mut best: Option<DynamicImage> = None;
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos + 4..pos + 8].try_into().expect("4 bytes"));
+        let end = pos + length as usize;
+        if length < 8 || end > data.len() {
+            return Err(error("truncated element"));
+        }
+
+        let element = &data[pos + 8..end];
+        if element.starts_with(PNG_SIGNATURE) {
+            let img = image::load_from_memory(element)
+                .map_err(|e| ConversionError::DecodeError(e.to_string()))?;
+            if best.as_ref().is_none_or(|b| img.width() > b.width()) {
+                best = Some(img);
+            }
+        }
+        pos = end;
+    }
+
+    best.ok_or_else(|| error("no PNG-encoded image found"))
+}
@@ -0,0 +1,564 @@
+use crate::metadata::{ImageMetadata, MetadataMode};
+use crate::pdf_security;
+use crate::{
+    generate_output_path, get_media_category, jobs, load_transformed_image, tonemap, AtomicOutput,
+    ConversionError, ConversionOptions, ConversionResult,
+};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::codecs::jpeg::JpegEncoder;
+use image::metadata::Orientation;
+use image::{ColorType, DynamicImage};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+// ============================================================================
+// Page Options
+// ============================================================================
+
+/// Paper size of the pages images are placed on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    /// Each page is the size of its image at 96 DPI, plus the margins.
+    #[default]
+    Fit,
+    /// 210 × 297 mm.
+    A4,
+    /// 8.5 × 11 inches.
+    Letter,
+}
+
+/// Orientation of A4 and Letter pages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageOrientation {
+    /// Landscape for images wider than they are tall, portrait otherwise,
+    /// decided page by page.
+    #[default]
+    Auto,
+    Portrait,
+    Landscape,
+}
+
+/// How an image is placed inside the page margins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageFit {
+    /// Scale to fit inside the margins, keeping the aspect ratio.
+    #[default]
+    Fit,
+    /// Scale to cover the area inside the margins, keeping the aspect ratio,
+    /// and clip the overflow.
+    Fill,
+    /// Stretch to the area inside the margins.
+    Stretch,
+    /// Keep the image's size at 96 DPI, only shrinking images that would
+    /// not fit.
+    Original,
+}
+
+/// Page layout for images written to PDF.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfPageOptions {
+    pub size: PageSize,
+    pub orientation: PageOrientation,
+    /// Blank border around the image on every side, in millimetres.
+    pub margin_mm: f32,
+    /// Placement of the image on A4 and Letter pages. Fit-to-image pages
+    /// always hold the image at its own size.
+    pub fit: PageFit,
+}
+
+/// Resolution images are sized at on the page, matching CSS pixels.
+const IMAGE_DPI: f32 = 96.0;
+const POINTS_PER_INCH: f32 = 72.0;
+const MM_PER_INCH: f32 = 25.4;
+
+const A4_POINTS: (f32, f32) = (595.28, 841.89);
+const LETTER_POINTS: (f32, f32) = (612.0, 792.0);
+
+/// A rectangle in PDF points, from the bottom-left corner of the page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Where one image goes: the page size, the image's rectangle, and the
+/// area it is clipped to when it overflows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PageLayout {
+    width: f32,
+    height: f32,
+    image: Rect,
+    clip: Option<Rect>,
+}
+
+/// Lays out an image of `image_width` × `image_height` pixels.
+fn layout_page(
+    image_width: u32,
+    image_height: u32,
+    options: &PdfPageOptions,
+) -> Result<PageLayout, ConversionError> {
+    let to_points = POINTS_PER_INCH / IMAGE_DPI;
+    let (natural_width, natural_height) = (
+        image_width as f32 * to_points,
+        image_height as f32 * to_points,
+    );
+    let margin = options.margin_mm * POINTS_PER_INCH / MM_PER_INCH;
+    if !margin.is_finite() || margin < 0.0 {
+        return Err(ConversionError::PdfError(
+            "Page margins cannot be negative".to_string(),
+        ));
+    }
+
+    let (short, long) = match options.size {
+        PageSize::Fit => {
+            return Ok(PageLayout {
+                width: natural_width + 2.0 * margin,
+                height: natural_height + 2.0 * margin,
+                image: Rect {
+                    x: margin,
+                    y: margin,
+                    width: natural_width,
+                    height: natural_height,
+                },
+                clip: None,
+            });
+        }
+        PageSize::A4 => A4_POINTS,
+        PageSize::Letter => LETTER_POINTS,
+    };
+
+    let landscape = match options.orientation {
+        PageOrientation::Auto => image_width > image_height,
+        PageOrientation::Portrait => false,
+        PageOrientation::Landscape => true,
+    };
+    let (page_width, page_height) = if landscape {
+        (long, short)
+    } else {
+        (short, long)
+    };
+
+    let area = Rect {
+        x: margin,
+        y: margin,
+        width: page_width - 2.0 * margin,
+        height: page_height - 2.0 * margin,
+    };
+    if area.width <= 0.0 || area.height <= 0.0 {
+        return Err(ConversionError::PdfError(
+            "Page margins leave no room for the image".to_string(),
+        ));
+    }
+
+    let fit_scale = (area.width / natural_width).min(area.height / natural_height);
+    let (width, height) = match options.fit {
+        PageFit::Fit => (natural_width * fit_scale, natural_height * fit_scale),
+        PageFit::Fill => {
+            let scale = (area.width / natural_width).max(area.height / natural_height);
+            (natural_width * scale, natural_height * scale)
+        }
+        PageFit::Stretch => (area.width, area.height),
+        PageFit::Original => {
+            let scale = fit_scale.min(1.0);
+            (natural_width * scale, natural_height * scale)
+        }
+    };
+
+    Ok(PageLayout {
+        width: page_width,
+        height: page_height,
+        image: Rect {
+            x: area.x + (area.width - width) / 2.0,
+            y: area.y + (area.height - height) / 2.0,
+            width,
+            height,
+        },
+        clip: (options.fit == PageFit::Fill).then_some(area),
+    })
+}
+
+// ============================================================================
+// Image Embedding
+// ============================================================================
+
+/// How an image's pixels are stored in the PDF.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Embedding {
+    /// Deflate, losslessly. Used for screenshots, line art and anything
+    /// else that came from a lossless format.
+    Flate,
+    /// Re-encoded as JPEG at this quality.
+    Jpeg(u8),
+    /// The source's JPEG bytes, embedded as they are.
+    Passthrough(Vec<u8>),
+}
+
+/// Sources that were lossy to begin with, so JPEG adds little further loss.
+fn is_lossy_source(extension: &str) -> bool {
+    matches!(
+        extension,
+        "jpg"
+            | "jpeg"
+            | "jfif"
+            | "webp"
+            | "avif"
+            | "heic"
+            | "heif"
+            | "raw"
+            | "cr2"
+            | "nef"
+            | "arw"
+            | "dng"
+    )
+}
+
+/// Number of colour components of a baseline or progressive JPEG, read from
+/// its start-of-frame marker.
+pub(crate) fn jpeg_components(data: &[u8]) -> Option<u8> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return data.get(pos + 9).copied();
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+/// Picks how to embed the image decoded from `path`. An unedited, upright
+/// RGB or greyscale JPEG is copied byte for byte; other lossy sources are
+/// re-encoded as JPEG at `options.quality`, and everything else (or any
+/// source with `options.lossless`) is stored losslessly.
+fn choose_embedding(path: &Path, extension: &str, options: &ConversionOptions) -> Embedding {
+    if matches!(extension, "jpg" | "jpeg" | "jfif") && !options.transform.has_edits() {
+        let upright = !options.transform.auto_orient
+            || matches!(
+                ImageMetadata::read(path).orientation,
+                None | Some(Orientation::NoTransforms)
+            );
+        if upright {
+            if let Ok(data) = fs::read(path) {
+                // CMYK JPEGs would need their inverted channels described
+                if matches!(jpeg_components(&data), Some(1 | 3)) {
+                    return Embedding::Passthrough(data);
+                }
+            }
+        }
+    }
+
+    if options.lossless || !is_lossy_source(extension) {
+        Embedding::Flate
+    } else {
+        Embedding::Jpeg(options.quality.clamp(1, 100))
+    }
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(pdf_error)?;
+    encoder.finish().map_err(pdf_error)
+}
+
+/// The alpha channel as a soft mask, or `None` when the image is opaque.
+fn soft_mask(img: &DynamicImage) -> Option<Vec<u8>> {
+    if !img.color().has_alpha() {
+        return None;
+    }
+    let alpha: Vec<u8> = img.to_rgba8().pixels().map(|p| p[3]).collect();
+    alpha.iter().any(|&a| a < 255).then_some(alpha)
+}
+
+// ============================================================================
+// PDF Writing
+// ============================================================================
+
+fn pdf_error(e: impl std::fmt::Display) -> ConversionError {
+    ConversionError::PdfError(e.to_string())
+}
+
+/// Builds a PDF with one image per page.
+pub(crate) struct ImagePdf {
+    doc: Document,
+    pages_id: ObjectId,
+    page_ids: Vec<ObjectId>,
+}
+
+impl ImagePdf {
+    pub(crate) fn new() -> Self {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        Self {
+            doc,
+            pages_id,
+            page_ids: Vec::new(),
+        }
+    }
+
+    /// Adds `img` as an image XObject and returns its id. Greyscale images
+    /// stay greyscale, and transparency is kept as a soft mask.
+    fn add_image(
+        &mut self,
+        img: &DynamicImage,
+        embedding: Embedding,
+    ) -> Result<ObjectId, ConversionError> {
+        let img = tonemap::to_sdr(img);
+        let gray = matches!(
+            img.color(),
+            ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+        );
+
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => img.width() as i64,
+            "Height" => img.height() as i64,
+            "BitsPerComponent" => 8,
+        };
+
+        if let Some(alpha) = soft_mask(&img) {
+            let mask_id = self.doc.add_object(Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => img.width() as i64,
+                    "Height" => img.height() as i64,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                    "Filter" => "FlateDecode",
+                },
+                deflate(&alpha)?,
+            ));
+            dict.set("SMask", mask_id);
+        }
+
+        let (filter, gray, data) = match embedding {
+            Embedding::Passthrough(data) => ("DCTDecode", jpeg_components(&data) == Some(1), data),
+            Embedding::Jpeg(quality) => {
+                let mut data = Vec::new();
+                let encoder = JpegEncoder::new_with_quality(&mut data, quality);
+                if gray {
+                    img.to_luma8().write_with_encoder(encoder)
+                } else {
+                    img.to_rgb8().write_with_encoder(encoder)
+                }
+                .map_err(pdf_error)?;
+                ("DCTDecode", gray, data)
+            }
+            Embedding::Flate => {
+                let data = if gray {
+                    deflate(img.to_luma8().as_raw())?
+                } else {
+                    deflate(img.to_rgb8().as_raw())?
+                };
+                ("FlateDecode", gray, data)
+            }
+        };
+        dict.set("Filter", filter);
+        dict.set("ColorSpace", if gray { "DeviceGray" } else { "DeviceRGB" });
+
+        Ok(self.doc.add_object(Stream::new(dict, data)))
+    }
+
+    /// Adds a page holding `img`, laid out according to `options`.
+    pub(crate) fn add_page(
+        &mut self,
+        img: &DynamicImage,
+        embedding: Embedding,
+        options: &PdfPageOptions,
+    ) -> Result<(), ConversionError> {
+        let layout = layout_page(img.width(), img.height(), options)?;
+        let image_id = self.add_image(img, embedding)?;
+
+        let mut operations = vec![Operation::new("q", vec![])];
+        if let Some(clip) = layout.clip {
+            operations.push(Operation::new(
+                "re",
+                vec![
+                    clip.x.into(),
+                    clip.y.into(),
+                    clip.width.into(),
+                    clip.height.into(),
+                ],
+            ));
+            operations.push(Operation::new("W", vec![]));
+            operations.push(Operation::new("n", vec![]));
+        }
+        // Images are drawn into the unit square, so scale it to the rectangle
+        let image = layout.image;
+        operations.push(Operation::new(
+            "cm",
+            vec![
+                image.width.into(),
+                0.into(),
+                0.into(),
+                image.height.into(),
+                image.x.into(),
+                image.y.into(),
+            ],
+        ));
+        operations.push(Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]));
+        operations.push(Operation::new("Q", vec![]));
+
+        let content = Content { operations }.encode().map_err(pdf_error)?;
+        let content_id = self.doc.add_object(Stream::new(dictionary! {}, content));
+
+        let page_id = self.doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), layout.width.into(), layout.height.into()],
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+            "Contents" => content_id,
+        });
+        self.page_ids.push(page_id);
+        Ok(())
+    }
+
+    /// Writes the document to `output_path`.
+    pub(crate) fn save(mut self, output_path: &Path) -> Result<(), ConversionError> {
+        let count = self.page_ids.len() as i64;
+        let kids: Vec<Object> = self.page_ids.into_iter().map(Object::from).collect();
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+            }),
+        );
+
+        let catalog_id = self.doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        });
+        self.doc.trailer.set("Root", catalog_id);
+        self.doc.compress();
+
+        self.doc
+            .save(output_path)
+            .map(|_| ())
+            .map_err(|e| ConversionError::WriteError(e.to_string()))
+    }
+}
+
+/// Decodes and transforms one image for a PDF page, and picks how to embed it.
+fn load_page_image(
+    path: &Path,
+    options: &ConversionOptions,
+) -> Result<(DynamicImage, Embedding), ConversionError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let (img, _) = load_transformed_image(path, &extension, options, MetadataMode::Strip)?;
+    Ok((img, choose_embedding(path, &extension, options)))
+}
+
+/// Converts one image to a one-page PDF laid out by `options.pdf_page`.
+pub(crate) fn image_to_pdf(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConversionOptions,
+) -> Result<(), ConversionError> {
+    let (img, embedding) = load_page_image(input_path, options)?;
+    jobs::check_cancelled()?;
+
+    let mut pdf = ImagePdf::new();
+    pdf.add_page(&img, embedding, &options.pdf_page)?;
+    pdf.save(output_path)
+}
+
+// ============================================================================
+// Multi-Image PDFs
+// ============================================================================
+
+/// Combines images into one PDF, one page per image in the order given,
+/// laid out by `options.pdf_page`. Each image gets `options.transform`
+/// first. The PDF is named after the first image and written to
+/// `options.output_dir` (or next to the first image); the result's
+/// `original_size` is the total size of the inputs.
+pub fn images_to_pdf(input_paths: &[String], options: &ConversionOptions) -> ConversionResult {
+    let Some(first) = input_paths.first() else {
+        return ConversionResult::error("No images to combine".to_string());
+    };
+
+    let mut original_size = 0;
+    for input in input_paths {
+        let path = Path::new(input);
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if get_media_category(&extension) != "image" {
+            return ConversionResult::error(format!("Not an image: {}", input));
+        }
+        match fs::metadata(path) {
+            Ok(meta) => original_size += meta.len(),
+            Err(_) => return ConversionResult::error(format!("Input file not found: {}", input)),
+        }
+    }
+
+    let output_path = match generate_output_path(
+        Path::new(first),
+        "pdf",
+        options.output_dir.as_deref(),
+        options.overwrite_existing,
+    ) {
+        Ok(p) => p,
+        Err(e) => return ConversionResult::error(e.to_string()),
+    };
+    let output = AtomicOutput::new(&output_path);
+
+    // Images are decoded one at a time, so long scans don't pile up in memory
+    let options = ConversionOptions {
+        output_format: "pdf".to_string(),
+        ..options.clone()
+    };
+    let mut pdf = ImagePdf::new();
+    let result = input_paths
+        .iter()
+        .try_for_each(|input| {
+            let (img, embedding) = load_page_image(Path::new(input), &options)
+                .map_err(|e| ConversionError::PdfError(format!("{}: {}", input, e)))?;
+            jobs::check_cancelled()?;
+            pdf.add_page(&img, embedding, &options.pdf_page)
+        })
+        .and_then(|_| pdf.save(output.path()))
+        .and_then(|_| pdf_security::protect(output.path(), &options.pdf_encryption))
+        .and_then(|_| jobs::check_cancelled())
+        .and_then(|_| output.commit());
+
+    if let Err(e) = result {
+        return ConversionResult::error(e.to_string());
+    }
+
+    let new_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+    ConversionResult::success(
+        output_path.to_string_lossy().to_string(),
+        original_size,
+        new_size,
+    )
+}
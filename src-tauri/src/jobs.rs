@@ -0,0 +1,219 @@
+use crate::ConversionError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// How often a running child process is polled for exit (and cancellation).
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    static CURRENT_JOB: RefCell<Option<Arc<JobHandle>>> = const { RefCell::new(None) };
+}
+
+// ============================================================================
+// Jobs
+// ============================================================================
+
+/// A cancellable conversion. External processes started while the job is
+/// active on a thread (see `JobHandle::run`) are killed when it is cancelled,
+/// and in-process pipelines stop at their next cancellation check.
+pub struct JobHandle {
+    id: String,
+    cancelled: AtomicBool,
+    children: Mutex<Vec<Arc<Mutex<Child>>>>,
+}
+
+impl JobHandle {
+    fn new(id: String) -> Self {
+        Self {
+            id,
+            cancelled: AtomicBool::new(false),
+            children: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Marks the job cancelled and kills any child processes it is running.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for child in self.children.lock().unwrap().iter() {
+            let _ = child.lock().unwrap().kill();
+        }
+    }
+
+    /// Runs `f` with this job as the current job of the calling thread.
+    pub fn run<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT_JOB.with(|job| job.replace(Some(self.clone())));
+        let result = f();
+        CURRENT_JOB.with(|job| *job.borrow_mut() = previous);
+        result
+    }
+
+    fn attach(&self, child: &Arc<Mutex<Child>>) {
+        self.children.lock().unwrap().push(child.clone());
+        // Cancelled between the check in run_command and the spawn
+        if self.is_cancelled() {
+            let _ = child.lock().unwrap().kill();
+        }
+    }
+
+    fn detach(&self, child: &Arc<Mutex<Child>>) {
+        self.children
+            .lock()
+            .unwrap()
+            .retain(|c| !Arc::ptr_eq(c, child));
+    }
+}
+
+/// Tracks running jobs by id so they can be cancelled from another thread
+/// (e.g. a `cancel_conversion` command).
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, Arc<JobHandle>>>,
+    next_id: AtomicU64,
+}
+
+impl JobRegistry {
+    /// The process-wide registry used by the Tauri commands.
+    pub fn global() -> &'static JobRegistry {
+        static REGISTRY: OnceLock<JobRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(JobRegistry::default)
+    }
+
+    /// Hands out a new, unused job id.
+    pub fn next_id(&self) -> String {
+        format!("job-{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    /// Registers a job under `id`, or under a fresh id if none is given.
+    pub fn start(&self, id: Option<String>) -> Arc<JobHandle> {
+        let id = id.unwrap_or_else(|| self.next_id());
+        let job = Arc::new(JobHandle::new(id.clone()));
+        self.jobs.lock().unwrap().insert(id, job.clone());
+        job
+    }
+
+    /// Cancels the job with `id`. Returns false if no such job is running.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(job) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes a finished job from the registry.
+    pub fn finish(&self, job: &JobHandle) {
+        let mut jobs = self.jobs.lock().unwrap();
+        // The id may have been reused by a newer job
+        if jobs.get(job.id()).is_some_and(|j| std::ptr::eq(j.as_ref(), job)) {
+            jobs.remove(job.id());
+        }
+    }
+}
+
+// ============================================================================
+// Cancellation Helpers
+// ============================================================================
+
+/// The job active on the calling thread, if any.
+pub(crate) fn current() -> Option<Arc<JobHandle>> {
+    CURRENT_JOB.with(|job| job.borrow().clone())
+}
+
+/// Returns `ConversionError::Cancelled` if the current job has been cancelled.
+pub(crate) fn check_cancelled() -> Result<(), ConversionError> {
+    match current() {
+        Some(job) if job.is_cancelled() => Err(ConversionError::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+fn cancelled_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, ConversionError::Cancelled.to_string())
+}
+
+/// Runs `cmd` to completion like `Command::output`, but lets the current job
+/// kill it.
+pub(crate) fn run_command(cmd: &mut Command) -> io::Result<Output> {
+    run_command_streaming(cmd, |mut stdout| {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Like `run_command`, but hands the child's stdout to `read_stdout` on the
+/// calling thread while the process runs. The bytes it returns become the
+/// output's `stdout`.
+pub(crate) fn run_command_streaming<F>(cmd: &mut Command, read_stdout: F) -> io::Result<Output>
+where
+    F: FnOnce(ChildStdout) -> Vec<u8>,
+{
+    let job = current();
+    if job.as_ref().is_some_and(|j| j.is_cancelled()) {
+        return Err(cancelled_error());
+    }
+
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+
+    // Drain stderr on its own thread so a full pipe can't stall the child
+    let stderr_pipe = child.stderr.take();
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = stderr_pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+    let stdout_pipe = child.stdout.take();
+
+    let child = Arc::new(Mutex::new(child));
+    if let Some(job) = &job {
+        job.attach(&child);
+    }
+
+    let stdout = stdout_pipe.map(read_stdout).unwrap_or_default();
+
+    // Poll rather than block in wait() so cancel() can take the lock and kill
+    let status = loop {
+        match child.lock().unwrap().try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => {}
+            Err(e) => break Err(e),
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if let Some(job) = &job {
+        job.detach(&child);
+    }
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if job.is_some_and(|j| j.is_cancelled()) {
+        return Err(cancelled_error());
+    }
+
+    Ok(Output {
+        status: status?,
+        stdout,
+        stderr,
+    })
+}
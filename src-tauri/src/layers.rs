@@ -0,0 +1,348 @@
+use crate::metadata::ImageMetadata;
+use crate::{psd, xcf, ConversionError};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// ============================================================================
+// Layer Options
+// ============================================================================
+
+/// Options for layered sources (Photoshop PSD, GIMP XCF).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerOptions {
+    /// Besides the flattened output, write every layer to its own file named
+    /// `<output>_layer<N>_<layer name>`. Layers keep their position on a
+    /// transparent canvas the size of the document, so the files line up.
+    pub export_layers: bool,
+    /// Also export hidden layers.
+    pub include_hidden: bool,
+}
+
+// ============================================================================
+// Layered Documents
+// ============================================================================
+
+/// Blend modes shared by Photoshop and GIMP. Modes without an equivalent
+/// here (hue, saturation, colour, luminosity...) are composited as `Normal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    LinearBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Add,
+    Subtract,
+    Divide,
+    GrainExtract,
+    GrainMerge,
+}
+
+pub(crate) struct Layer {
+    pub name: String,
+    /// Straight-alpha pixels, with any layer mask already applied.
+    pub pixels: RgbaImage,
+    /// Position of the layer's top-left corner on the canvas.
+    pub left: i64,
+    pub top: i64,
+    /// Layer opacity from 0 to 1.
+    pub opacity: f32,
+    /// Whether the layer and all groups containing it are visible.
+    pub visible: bool,
+    pub blend: BlendMode,
+}
+
+pub(crate) struct LayeredImage {
+    pub width: u32,
+    pub height: u32,
+    /// Layers from bottom to top.
+    pub layers: Vec<Layer>,
+    /// Flattened image saved by the application, used when there are no layers.
+    pub composite: Option<RgbaImage>,
+    pub metadata: ImageMetadata,
+}
+
+/// Extensions of the layered formats handled here.
+pub(crate) fn is_layered(extension: &str) -> bool {
+    matches!(extension, "psd" | "xcf")
+}
+
+/// Parses a PSD or XCF file into its layers.
+pub(crate) fn load_layered(path: &Path, extension: &str) -> Result<LayeredImage, ConversionError> {
+    let data = fs::read(path).map_err(|e| ConversionError::DecodeError(e.to_string()))?;
+    match extension {
+        "psd" => psd::parse(&data),
+        "xcf" => xcf::parse(&data),
+        other => Err(ConversionError::UnsupportedFormat(other.to_string())),
+    }
+}
+
+impl LayeredImage {
+    /// Composites the visible layers bottom to top onto a transparent
+    /// canvas. Documents without layers return the application's composite.
+    pub(crate) fn flatten(&self) -> RgbaImage {
+        if self.layers.is_empty() {
+            if let Some(composite) = &self.composite {
+                return composite.clone();
+            }
+        }
+
+        let mut canvas = RgbaImage::new(self.width, self.height);
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            self.draw(&mut canvas, layer, |dst, src, alpha| {
+                composite_pixel(dst, src, alpha, layer.blend)
+            });
+        }
+        canvas
+    }
+
+    /// The layer alone on a transparent canvas of the document's size, with
+    /// its opacity applied but not its blend mode.
+    pub(crate) fn layer_on_canvas(&self, layer: &Layer) -> RgbaImage {
+        let mut canvas = RgbaImage::new(self.width, self.height);
+        self.draw(&mut canvas, layer, |dst, src, alpha| {
+            *dst = Rgba([src[0], src[1], src[2], (alpha * 255.0).round() as u8]);
+        });
+        canvas
+    }
+
+    /// Calls `paint` for every canvas pixel the layer covers, with the
+    /// layer's pixel and its effective alpha (pixel alpha × opacity).
+    fn draw(
+        &self,
+        canvas: &mut RgbaImage,
+        layer: &Layer,
+        mut paint: impl FnMut(&mut Rgba<u8>, &Rgba<u8>, f32),
+    ) {
+        for (x, y, src) in layer.pixels.enumerate_pixels() {
+            let cx = layer.left + x as i64;
+            let cy = layer.top + y as i64;
+            if cx < 0 || cy < 0 || cx >= self.width as i64 || cy >= self.height as i64 {
+                continue;
+            }
+            let alpha = src[3] as f32 / 255.0 * layer.opacity;
+            if alpha > 0.0 {
+                paint(canvas.get_pixel_mut(cx as u32, cy as u32), src, alpha);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Blending
+// ============================================================================
+
+/// Source-over compositing with a separable blend mode (W3C Compositing
+/// and Blending), on straight-alpha pixels.
+fn composite_pixel(dst: &mut Rgba<u8>, src: &Rgba<u8>, src_alpha: f32, mode: BlendMode) {
+    let dst_alpha = dst[3] as f32 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+    if out_alpha <= 0.0 {
+        return;
+    }
+
+    for c in 0..3 {
+        let cs = src[c] as f32 / 255.0;
+        let cb = dst[c] as f32 / 255.0;
+        let blended = blend(cb, cs, mode).clamp(0.0, 1.0);
+        let premultiplied = src_alpha * (1.0 - dst_alpha) * cs
+            + src_alpha * dst_alpha * blended
+            + (1.0 - src_alpha) * dst_alpha * cb;
+        dst[c] = (premultiplied / out_alpha * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// Blends source colour `cs` onto backdrop colour `cb`, both 0 to 1.
+fn blend(cb: f32, cs: f32, mode: BlendMode) -> f32 {
+    let multiply = |a: f32, b: f32| a * b;
+    let screen = |a: f32, b: f32| a + b - a * b;
+    let hard_light = |cb: f32, cs: f32| {
+        if cs <= 0.5 {
+            multiply(cb, 2.0 * cs)
+        } else {
+            screen(cb, 2.0 * cs - 1.0)
+        }
+    };
+
+    match mode {
+        BlendMode::Normal => cs,
+        BlendMode::Multiply => multiply(cb, cs),
+        BlendMode::Screen => screen(cb, cs),
+        BlendMode::Overlay => hard_light(cs, cb),
+        BlendMode::Darken => cb.min(cs),
+        BlendMode::Lighten => cb.max(cs),
+        BlendMode::ColorDodge => {
+            if cb <= 0.0 {
+                0.0
+            } else if cs >= 1.0 {
+                1.0
+            } else {
+                (cb / (1.0 - cs)).min(1.0)
+            }
+        }
+        BlendMode::ColorBurn => {
+            if cb >= 1.0 {
+                1.0
+            } else if cs <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - cb) / cs).min(1.0)
+            }
+        }
+        BlendMode::LinearBurn => cb + cs - 1.0,
+        BlendMode::HardLight => hard_light(cb, cs),
+        BlendMode::SoftLight => {
+            if cs <= 0.5 {
+                cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+            } else {
+                let d = if cb <= 0.25 {
+                    ((16.0 * cb - 12.0) * cb + 4.0) * cb
+                } else {
+                    cb.sqrt()
+                };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+        }
+        BlendMode::Difference => (cb - cs).abs(),
+        BlendMode::Exclusion => cb + cs - 2.0 * cb * cs,
+        BlendMode::Add => cb + cs,
+        BlendMode::Subtract => cb - cs,
+        BlendMode::Divide => {
+            if cs <= 0.0 {
+                1.0
+            } else {
+                cb / cs
+            }
+        }
+        BlendMode::GrainExtract => cb - cs + 0.5,
+        BlendMode::GrainMerge => cb + cs - 0.5,
+    }
+}
+
+// ============================================================================
+// Parsing Helpers
+// ============================================================================
+
+/// Big-endian cursor over a layered file, shared by the PSD and XCF parsers.
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8], format: &'static str) -> Self {
+        Self {
+            data,
+            pos: 0,
+            format,
+        }
+    }
+
+    pub(crate) fn error(&self, message: impl std::fmt::Display) -> ConversionError {
+        ConversionError::DecodeError(format!("{}: {}", self.format, message))
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// The position `len` bytes ahead, for lengths read from the file, which
+    /// may be corrupt.
+    pub(crate) fn pos_after(&self, len: usize) -> Result<usize, ConversionError> {
+        self.pos
+            .checked_add(len)
+            .ok_or_else(|| self.error("length out of range"))
+    }
+
+    /// Everything from the current position to the end of the file.
+    pub(crate) fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    pub(crate) fn seek(&mut self, pos: usize) -> Result<(), ConversionError> {
+        if pos > self.data.len() {
+            return Err(self.error("offset past the end of the file"));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], ConversionError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), ConversionError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ConversionError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, ConversionError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn i16(&mut self) -> Result<i16, ConversionError> {
+        self.u16().map(|v| v as i16)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, ConversionError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, ConversionError> {
+        self.u32().map(|v| v as i32)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, ConversionError> {
+        let b = self.bytes(8)?;
+        Ok(u64::from_be_bytes(b.try_into().expect("8 bytes")))
+    }
+}
+
+/// Multiplies a layer's alpha by a grayscale mask covering `mask_rect`
+/// (left, top, width, height, in layer coordinates). Pixels outside the mask
+/// take `default` (0 hides, 255 shows).
+pub(crate) fn apply_mask(
+    pixels: &mut RgbaImage,
+    mask: &[u8],
+    mask_rect: (i64, i64, u32, u32),
+    default: u8,
+) {
+    let (left, top, width, height) = mask_rect;
+    for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+        let mx = x as i64 - left;
+        let my = y as i64 - top;
+        let value = if mx >= 0 && my >= 0 && mx < width as i64 && my < height as i64 {
+            mask[my as usize * width as usize + mx as usize]
+        } else {
+            default
+        };
+        pixel[3] = ((pixel[3] as u32 * value as u32 + 127) / 255) as u8;
+    }
+}
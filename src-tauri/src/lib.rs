@@ -1,16 +1,72 @@
-use image::{DynamicImage, ImageFormat, ImageReader, RgbaImage};
+use image::{DynamicImage, ImageFormat, ImageReader};
 use image::codecs::jpeg::JpegEncoder;
 use image::codecs::png::{CompressionType, FilterType, PngEncoder};
 use image::codecs::webp::WebPEncoder;
-use image::imageops::FilterType as ResizeFilter;
-use image::ExtendedColorType;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::openexr::OpenExrEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::metadata::Orientation;
+use image::{ExtendedColorType, ImageEncoder};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
-use std::io::BufWriter;
+use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
 use std::process::Command as StdCommand;
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 
+#[cfg(feature = "gui")]
+use std::sync::Arc;
+#[cfg(feature = "gui")]
+use tauri::{Emitter, Manager};
+
+mod animation;
+mod batch;
+mod heif;
+mod icon;
+mod image_pdf;
+mod jobs;
+mod layers;
+mod metadata;
+mod pdf_export;
+mod pdf_optimize;
+mod pdf_render;
+mod pdf_security;
+mod pdf_text;
+mod pdf_tools;
+mod progress;
+mod psd;
+mod raw;
+mod svg;
+mod tonemap;
+mod transform;
+mod xcf;
+
+pub use animation::AnimationOptions;
+pub use batch::{convert_batch, BatchOptions, BatchResult, BatchSummary};
+pub use heif::HeifOptions;
+pub use icon::IconOptions;
+pub use image_pdf::{images_to_pdf, PageFit, PageOrientation, PageSize, PdfPageOptions};
+pub use jobs::{JobHandle, JobRegistry};
+pub use layers::LayerOptions;
+pub use pdf_export::PdfExportOptions;
+pub use pdf_optimize::PdfOptimizeOptions;
+pub use pdf_render::set_pdfium_library_dir;
+pub use pdf_security::PdfEncryptionOptions;
+pub use pdf_tools::{
+    decrypt_pdf, delete_pdf_pages, extract_pdf_pages, merge_pdfs, reorder_pdf_pages,
+    rotate_pdf_pages, split_pdf, PdfToolOptions,
+};
+pub use progress::{ConversionProgress, ProgressCallback, ProgressReporter, PROGRESS_EVENT};
+pub use raw::{RawMode, RawOptions};
+pub use svg::SvgOptions;
+pub use transform::{CropRect, ImageTransform, ResampleFilter, ResizeMode};
+
+use animation::AnimationFrame;
+use metadata::{DocumentInfo, ImageMetadata, MetadataMode};
+
 // ============================================================================
 // Error Types
 // ============================================================================
@@ -45,6 +101,10 @@ pub enum ConversionError {
     LibreOfficeNotFound,
     #[error("Pandoc not found - required for this conversion")]
     PandocNotFound,
+    #[error("Conversion cancelled")]
+    Cancelled,
+    #[error("Invalid image transform: {0}")]
+    InvalidTransform(String),
 }
 
 // ============================================================================
@@ -55,6 +115,10 @@ pub enum ConversionError {
 pub struct ConversionResult {
     pub success: bool,
     pub output_path: Option<String>,
+    /// Every file written, starting with `output_path`. Conversions that
+    /// produce several files (such as exported layers) list them all here.
+    #[serde(default)]
+    pub output_paths: Vec<String>,
     pub error: Option<String>,
     pub original_size: Option<u64>,
     pub new_size: Option<u64>,
@@ -64,6 +128,7 @@ impl ConversionResult {
     fn success(output_path: String, original_size: u64, new_size: u64) -> Self {
         Self {
             success: true,
+            output_paths: vec![output_path.clone()],
             output_path: Some(output_path),
             error: None,
             original_size: Some(original_size),
@@ -75,6 +140,7 @@ impl ConversionResult {
         Self {
             success: false,
             output_path: None,
+            output_paths: Vec::new(),
             error: Some(message),
             original_size: None,
             new_size: None,
@@ -82,6 +148,92 @@ impl ConversionResult {
     }
 }
 
+/// Options for a single conversion, shared by the Tauri commands, batches and the CLI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionOptions {
+    pub output_format: String,
+    pub quality: u8,
+    pub output_dir: Option<String>,
+    pub preserve_metadata: bool,
+    /// When preserving metadata, still drop GPS location, camera/lens serial
+    /// numbers and owner names. Has no effect when `preserve_metadata` is off,
+    /// since all of those are stripped anyway.
+    #[serde(default)]
+    pub strip_private_metadata: bool,
+    pub overwrite_existing: bool,
+    pub bitrate: Option<String>,
+    /// Use lossless encoding for formats that offer both (WebP). Otherwise
+    /// `quality` drives a lossy encoder.
+    #[serde(default)]
+    pub lossless: bool,
+    /// Resize/crop/rotate edits for image outputs (and images placed in PDFs).
+    #[serde(default)]
+    pub transform: ImageTransform,
+    /// Which picture to decode from HEIF/HEIC containers.
+    #[serde(default)]
+    pub heif: HeifOptions,
+    /// How camera RAW files are developed.
+    #[serde(default)]
+    pub raw: RawOptions,
+    /// Layer export for layered sources (PSD, XCF).
+    #[serde(default)]
+    pub layers: LayerOptions,
+    /// Sizes packed into ICO and ICNS output.
+    #[serde(default)]
+    pub icon: IconOptions,
+    /// Frame export for animated images, and clip settings for video to GIF.
+    #[serde(default)]
+    pub animation: AnimationOptions,
+    /// Render size, background and fonts for SVG sources.
+    #[serde(default)]
+    pub svg: SvgOptions,
+    /// Page size, margins and image placement for images written to PDF.
+    #[serde(default)]
+    pub pdf_page: PdfPageOptions,
+    /// Which pages of a PDF are exported when converting it to images.
+    #[serde(default)]
+    pub pdf_export: PdfExportOptions,
+    /// How much a PDF is shrunk when converted to PDF.
+    #[serde(default)]
+    pub pdf_optimize: PdfOptimizeOptions,
+    /// Password protection for PDF output.
+    #[serde(default)]
+    pub pdf_encryption: PdfEncryptionOptions,
+    /// Opens password-protected PDF inputs. Either the user or the owner
+    /// password works; `""` opens PDFs that only restrict printing or copying.
+    #[serde(default)]
+    pub pdf_password: Option<String>,
+}
+
+/// No output format, quality 90 (the app's default) and every other option at
+/// its default.
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            output_format: String::new(),
+            quality: 90,
+            output_dir: None,
+            preserve_metadata: false,
+            strip_private_metadata: false,
+            overwrite_existing: false,
+            bitrate: None,
+            lossless: false,
+            transform: ImageTransform::default(),
+            heif: HeifOptions::default(),
+            raw: RawOptions::default(),
+            layers: LayerOptions::default(),
+            icon: IconOptions::default(),
+            animation: AnimationOptions::default(),
+            svg: SvgOptions::default(),
+            pdf_page: PdfPageOptions::default(),
+            pdf_export: PdfExportOptions::default(),
+            pdf_optimize: PdfOptimizeOptions::default(),
+            pdf_encryption: PdfEncryptionOptions::default(),
+            pdf_password: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MediaInfo {
     pub name: String,
@@ -106,14 +258,20 @@ pub struct ToolAvailability {
 
 fn get_image_format(format: &str) -> Result<ImageFormat, ConversionError> {
     match format.to_lowercase().as_str() {
-        "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+        "jpg" | "jpeg" | "jfif" => Ok(ImageFormat::Jpeg),
         "png" => Ok(ImageFormat::Png),
         "gif" => Ok(ImageFormat::Gif),
         "bmp" => Ok(ImageFormat::Bmp),
         "webp" => Ok(ImageFormat::WebP),
         "tiff" | "tif" => Ok(ImageFormat::Tiff),
-        "ico" => Ok(ImageFormat::Ico),
+        // ICNS has no `ImageFormat`; `save_image` writes it in the ICO branch
+        "ico" | "icns" => Ok(ImageFormat::Ico),
         "avif" => Ok(ImageFormat::Avif),
+        "ppm" | "pgm" | "pbm" | "pam" | "pnm" => Ok(ImageFormat::Pnm),
+        "tga" => Ok(ImageFormat::Tga),
+        "qoi" => Ok(ImageFormat::Qoi),
+        "hdr" => Ok(ImageFormat::Hdr),
+        "exr" => Ok(ImageFormat::OpenExr),
         _ => Err(ConversionError::UnsupportedFormat(format.to_string())),
     }
 }
@@ -127,8 +285,19 @@ fn get_extension(format: &str) -> &str {
         "webp" => "webp",
         "tiff" | "tif" => "tiff",
         "ico" => "ico",
+        "icns" => "icns",
         "avif" => "avif",
+        "ppm" | "pnm" => "ppm",
+        "pgm" => "pgm",
+        "pbm" => "pbm",
+        "pam" => "pam",
+        "tga" => "tga",
+        "qoi" => "qoi",
+        "hdr" => "hdr",
+        "exr" => "exr",
         "pdf" => "pdf",
+        // Archives
+        "zip" => "zip",
         // Audio formats
         "mp3" => "mp3",
         "wav" => "wav",
@@ -171,7 +340,8 @@ fn get_media_category(extension: &str) -> &str {
         // Image formats
         "heic" | "heif" | "png" | "jpg" | "jpeg" | "webp" | "bmp" | "tiff" | "tif" | "gif"
         | "svg" | "ico" | "avif" | "raw" | "cr2" | "nef" | "arw" | "dng" | "psd" | "xcf"
-        | "jfif" | "ppm" | "pgm" | "pbm" => "image",
+        | "jfif" | "ppm" | "pgm" | "pbm" | "pam" | "pnm" | "tga" | "qoi" | "hdr" | "exr"
+        | "icns" | "apng" => "image",
         // Document formats
         "pdf" | "txt" | "md" | "markdown" | "html" | "htm" | "rtf" | "docx" | "doc" | "odt"
         | "epub" | "xps" | "tex" | "rst" | "asciidoc" | "adoc" => "document",
@@ -232,17 +402,103 @@ fn generate_output_path(
     Ok(output_path)
 }
 
+// ============================================================================
+// Atomic Output
+// ============================================================================
+
+/// A unique name for a hidden scratch file or directory next to `stem`.
+fn scratch_name(stem: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        ".{}.fileflip-{}-{}",
+        stem,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// A temporary sibling of an output file. Converters write to `path()` and the
+/// result only replaces the destination on `commit()`, so an error or crash
+/// never leaves a truncated file that looks like a real result, and an
+/// existing file being overwritten survives a failed conversion. The temp file
+/// is removed if the output is dropped without committing.
+struct AtomicOutput {
+    temp_path: PathBuf,
+    final_path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    fn new(final_path: &Path) -> Self {
+        let stem = final_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        // Keep the real extension: FFmpeg and ImageMagick pick the output format from it
+        let mut name = scratch_name(stem);
+        if let Some(ext) = final_path.extension().and_then(|e| e.to_str()) {
+            name.push('.');
+            name.push_str(ext);
+        }
+
+        Self {
+            temp_path: final_path.with_file_name(name),
+            final_path: final_path.to_path_buf(),
+            committed: false,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    fn commit(mut self) -> Result<(), ConversionError> {
+        if !self.temp_path.exists() {
+            return Err(ConversionError::WriteError(
+                "Converter did not produce an output file".to_string(),
+            ));
+        }
+
+        fs::rename(&self.temp_path, &self.final_path)
+            .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
 // ============================================================================
 // Image Loading & Saving
 // ============================================================================
 
+/// Input formats whose decoder depends on a native library and sits behind a
+/// cargo feature, with whether that feature is enabled in this build.
+const OPTIONAL_DECODERS: &[(&[&str], bool)] = &[
+    (&["avif"], cfg!(feature = "avif-decode")),
+    (&["heic", "heif"], cfg!(feature = "heif")),
+];
+
+/// Whether images with this extension can be decoded in this build.
+fn can_decode_image(extension: &str) -> bool {
+    OPTIONAL_DECODERS
+        .iter()
+        .all(|(extensions, enabled)| *enabled || !extensions.contains(&extension))
+}
+
 fn load_image(path: &Path) -> Result<DynamicImage, ConversionError> {
-    // Check for AVIF files - decoding not supported on all platforms
     if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        if ext.to_lowercase() == "avif" {
-            return Err(ConversionError::UnsupportedFormat(
-                "AVIF decoding is not supported. You can convert TO AVIF format, but not FROM AVIF.".to_string()
-            ));
+        if !can_decode_image(&ext.to_lowercase()) {
+            return Err(ConversionError::UnsupportedFormat(format!(
+                "{} decoding is not available in this build",
+                ext.to_uppercase()
+            )));
         }
     }
 
@@ -256,33 +512,209 @@ fn load_image(path: &Path) -> Result<DynamicImage, ConversionError> {
         .map_err(|e| ConversionError::DecodeError(e.to_string()))
 }
 
-fn load_svg(path: &Path, width: Option<u32>) -> Result<DynamicImage, ConversionError> {
-    let svg_data = fs::read(path).map_err(|e| ConversionError::ReadError(e.to_string()))?;
+/// Decodes `path` (rasterising SVGs), applies `options.transform`, and reads
+/// the metadata to carry into the output according to `mode`.
+fn load_transformed_image(
+    path: &Path,
+    input_ext: &str,
+    options: &ConversionOptions,
+    mode: MetadataMode,
+) -> Result<(DynamicImage, ImageMetadata), ConversionError> {
+    let transform = &options.transform;
+    let (img, mut metadata) = match input_ext {
+        "svg" => {
+            // Icons are rendered at their largest size instead of being scaled
+            // up, unless a render size was asked for
+            let output_ext = options.output_format.to_lowercase();
+            let mut svg_options = options.svg.clone();
+            if icon::is_icon(&output_ext)
+                && svg_options.width.is_none()
+                && svg_options.height.is_none()
+                && svg_options.dpi.is_none()
+            {
+                svg_options.width = icon::icon_sizes(&output_ext, &options.icon)?.first().copied();
+            }
+            (
+                svg::load_svg(path, &svg_options, &output_ext)?,
+                ImageMetadata::default(),
+            )
+        }
+        "icns" => (icon::load_icns(path)?, ImageMetadata::default()),
+        #[cfg(feature = "heif")]
+        "heic" | "heif" => (
+            heif::load_heif(path, &options.heif)?,
+            heif::read_heif_metadata(path, &options.heif).for_mode(mode),
+        ),
+        "raw" | "cr2" | "nef" | "arw" | "dng" => (
+            raw::load_raw(path, &options.raw)?,
+            raw::read_raw_metadata(path).for_mode(mode),
+        ),
+        ext if layers::is_layered(ext) => {
+            let document = layers::load_layered(path, ext)?;
+            (
+                DynamicImage::ImageRgba8(document.flatten()),
+                document.metadata.for_mode(mode),
+            )
+        }
+        _ => (load_image(path)?, ImageMetadata::read(path).for_mode(mode)),
+    };
 
-    let options = usvg::Options::default();
-    let tree = usvg::Tree::from_data(&svg_data, &options)
-        .map_err(|e| ConversionError::SvgError(e.to_string()))?;
+    let orientation = metadata.orientation.unwrap_or(Orientation::NoTransforms);
+    let img = transform.apply(img, orientation)?;
+    if transform.auto_orient {
+        metadata.clear_orientation();
+    }
 
-    let original_size = tree.size();
-    let scale = if let Some(w) = width {
-        w as f32 / original_size.width()
-    } else {
-        1.0
-    };
+    Ok((img, metadata))
+}
 
-    let scaled_width = (original_size.width() * scale) as u32;
-    let scaled_height = (original_size.height() * scale) as u32;
+/// Writes each layer of a PSD/XCF source to its own file next to
+/// `output_path`, named `<output>_layer<N>_<layer name>`, and returns the
+/// paths written, bottom layer first. Nothing is left behind on failure.
+fn export_layers(
+    input_path: &Path,
+    input_ext: &str,
+    output_path: &Path,
+    options: &ConversionOptions,
+) -> Result<Vec<String>, ConversionError> {
+    let document = layers::load_layered(input_path, input_ext)?;
+    let stem = output_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+
+    let images = document
+        .layers
+        .iter()
+        .enumerate()
+        .filter(|(_, layer)| layer.visible || options.layers.include_hidden)
+        .map(|(index, layer)| {
+            let name = layer_file_name(&layer.name);
+            let base = if name.is_empty() {
+                format!("{}_layer{:02}", stem, index + 1)
+            } else {
+                format!("{}_layer{:02}_{}", stem, index + 1, name)
+            };
+            (base, DynamicImage::ImageRgba8(document.layer_on_canvas(layer)))
+        });
+    save_extra_images(images, output_path, options)
+}
 
-    let mut pixmap = resvg::tiny_skia::Pixmap::new(scaled_width, scaled_height)
-        .ok_or_else(|| ConversionError::SvgError("Failed to create pixmap".to_string()))?;
+/// Writes each frame of an animated GIF/WebP/PNG source to its own file next
+/// to `output_path`, named `<output>_frame<N>`, and returns the paths
+/// written. Still images produce no frames.
+fn export_frames(
+    input_path: &Path,
+    input_ext: &str,
+    output_path: &Path,
+    options: &ConversionOptions,
+) -> Result<Vec<String>, ConversionError> {
+    let Some(frames) = animation::load_frames(input_path, input_ext)? else {
+        return Ok(Vec::new());
+    };
+    let stem = output_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+
+    let images = frames.into_iter().enumerate().map(|(index, frame)| {
+        (
+            format!("{}_frame{:03}", stem, index + 1),
+            DynamicImage::ImageRgba8(frame.image),
+        )
+    });
+    save_extra_images(images, output_path, options)
+}
+
+/// Applies the output transform to every frame and writes the animation.
+fn save_transformed_animation(
+    frames: Vec<AnimationFrame>,
+    output_path: &Path,
+    format: ImageFormat,
+    options: &ConversionOptions,
+) -> Result<(), ConversionError> {
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            jobs::check_cancelled()?;
+            let image = options
+                .transform
+                .apply(DynamicImage::ImageRgba8(frame.image), Orientation::NoTransforms)?
+                .into_rgba8();
+            Ok(AnimationFrame {
+                image,
+                delay_ms: frame.delay_ms,
+            })
+        })
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+
+    animation::save_animation(
+        &frames,
+        output_path,
+        format,
+        options.quality.clamp(1, 100),
+        options.lossless,
+    )
+}
 
-    let transform = resvg::tiny_skia::Transform::from_scale(scale, scale);
-    resvg::render(&tree, transform, &mut pixmap.as_mut());
+/// Saves the extra images a conversion produces besides its main output
+/// (layers, frames) in the output format, each named `<base>.<ext>` next to
+/// `output_path` and with the output transform applied. Returns the paths
+/// written; on failure the ones already written are removed.
+fn save_extra_images(
+    images: impl Iterator<Item = (String, DynamicImage)>,
+    output_path: &Path,
+    options: &ConversionOptions,
+) -> Result<Vec<String>, ConversionError> {
+    let format = get_image_format(&options.output_format)?;
+    let dir = output_path.parent().unwrap_or(Path::new("."));
+
+    let mut written = Vec::new();
+    for (base, img) in images {
+        let saved = jobs::check_cancelled()
+            .and_then(|_| {
+                generate_output_path(
+                    &dir.join(format!("{}.extra", base)),
+                    &options.output_format,
+                    dir.to_str(),
+                    options.overwrite_existing,
+                )
+            })
+            .and_then(|path| {
+                let img = options.transform.apply(img, Orientation::NoTransforms)?;
+                let output = AtomicOutput::new(&path);
+                save_image(
+                    &img,
+                    output.path(),
+                    format,
+                    options.quality.clamp(1, 100),
+                    options.lossless,
+                    &options.icon,
+                    &ImageMetadata::default(),
+                )?;
+                output.commit()?;
+                Ok(path)
+            });
+
+        match saved {
+            Ok(path) => written.push(path.to_string_lossy().to_string()),
+            Err(e) => {
+                remove_files(&written);
+                return Err(e);
+            }
+        }
+    }
 
-    let rgba_image = RgbaImage::from_raw(scaled_width, scaled_height, pixmap.data().to_vec())
-        .ok_or_else(|| ConversionError::SvgError("Failed to create image from SVG".to_string()))?;
+    Ok(written)
+}
 
-    Ok(DynamicImage::ImageRgba8(rgba_image))
+/// Turns a layer name into something safe to put in a file name.
+fn layer_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    cleaned.trim_matches('_').chars().take(64).collect()
 }
 
 fn save_image(
@@ -290,7 +722,17 @@ fn save_image(
     output_path: &Path,
     format: ImageFormat,
     quality: u8,
+    lossless: bool,
+    icon: &IconOptions,
+    metadata: &ImageMetadata,
 ) -> Result<(), ConversionError> {
+    // Floating-point sources are tone mapped once here for every 8-bit format
+    let img = match format {
+        ImageFormat::Hdr | ImageFormat::OpenExr => tonemap::to_linear(img),
+        _ => tonemap::to_sdr(img),
+    };
+    let img = img.as_ref();
+
     match format {
         ImageFormat::Jpeg => {
             let rgb_img = img.to_rgb8();
@@ -298,8 +740,9 @@ fn save_image(
                 .map_err(|e| ConversionError::WriteError(e.to_string()))?;
 
             let mut encoder = JpegEncoder::new_with_quality(&mut output_file, quality);
+            metadata.apply_to(&mut encoder);
             encoder
-                .encode(
+                .write_image(
                     rgb_img.as_raw(),
                     rgb_img.width(),
                     rgb_img.height(),
@@ -318,91 +761,154 @@ fn save_image(
             let output_file = File::create(output_path)
                 .map_err(|e| ConversionError::WriteError(e.to_string()))?;
 
-            let encoder = PngEncoder::new_with_quality(output_file, compression, filter);
+            let mut encoder = PngEncoder::new_with_quality(output_file, compression, filter);
+            metadata.apply_to(&mut encoder);
+            img.write_with_encoder(encoder)
+                .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        }
+        ImageFormat::WebP if lossless => {
+            let output_file = File::create(output_path)
+                .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+
+            let mut encoder = WebPEncoder::new_lossless(output_file);
+            metadata.apply_to(&mut encoder);
             img.write_with_encoder(encoder)
                 .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
         }
         ImageFormat::WebP => {
+            // libwebp's lossy encoder; alpha is kept as a separate
+            // (losslessly compressed) plane
+            let encoded = if img.color().has_alpha() {
+                let rgba = img.to_rgba8();
+                webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height())
+                    .encode(quality as f32)
+                    .to_vec()
+            } else {
+                let rgb = img.to_rgb8();
+                webp::Encoder::from_rgb(rgb.as_raw(), rgb.width(), rgb.height())
+                    .encode(quality as f32)
+                    .to_vec()
+            };
+
+            // The lossy encoder writes no metadata, so all of it is embedded here
+            let data = metadata.embed_in_webp(encoded, img);
+            return fs::write(output_path, data)
+                .map_err(|e| ConversionError::WriteError(e.to_string()));
+        }
+        ImageFormat::Tiff => {
             let output_file = File::create(output_path)
                 .map_err(|e| ConversionError::WriteError(e.to_string()))?;
 
-            let encoder = WebPEncoder::new_lossless(output_file);
+            let mut encoder = TiffEncoder::new(BufWriter::new(output_file));
+            metadata.apply_to(&mut encoder);
             img.write_with_encoder(encoder)
                 .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
         }
-        ImageFormat::Ico => {
-            // For ICO, we resize to standard icon sizes
-            let resized = img.resize(256, 256, ResizeFilter::Lanczos3);
-            resized
+        ImageFormat::Avif => {
+            let output_file = File::create(output_path)
+                .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+
+            // Speed 4 is the encoder's default trade-off between size and time
+            let mut encoder = AvifEncoder::new_with_speed_quality(output_file, 4, quality);
+            metadata.apply_to(&mut encoder);
+            img.write_with_encoder(encoder)
+                .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        }
+        ImageFormat::Pnm => {
+            // The extension picks the Netpbm flavour; PAM keeps alpha and 16-bit depth
+            let extension = output_path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("ppm")
+                .to_lowercase();
+            let (encoded, subtype) = match extension.as_str() {
+                "pgm" => (
+                    DynamicImage::ImageLuma8(img.to_luma8()),
+                    Some(PnmSubtype::Graymap(SampleEncoding::Binary)),
+                ),
+                "pbm" => {
+                    // PBM samples are 0 (black) or 1 (white), thresholded at mid-grey
+                    let mut bitmap = img.to_luma8();
+                    for pixel in bitmap.pixels_mut() {
+                        pixel[0] = (pixel[0] >= 128) as u8;
+                    }
+                    (
+                        DynamicImage::ImageLuma8(bitmap),
+                        Some(PnmSubtype::Bitmap(SampleEncoding::Binary)),
+                    )
+                }
+                "pam" => (img.clone(), None),
+                _ => (
+                    DynamicImage::ImageRgb8(img.to_rgb8()),
+                    Some(PnmSubtype::Pixmap(SampleEncoding::Binary)),
+                ),
+            };
+
+            let output_file = File::create(output_path)
+                .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+            let encoder = PnmEncoder::new(BufWriter::new(output_file));
+            let encoder = match subtype {
+                Some(subtype) => encoder.with_subtype(subtype),
+                None => encoder,
+            };
+            encoded
+                .write_with_encoder(encoder)
+                .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        }
+        ImageFormat::Qoi => {
+            // QOI only stores 8-bit RGB and RGBA
+            let encoded = if img.color().has_alpha() {
+                DynamicImage::ImageRgba8(img.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(img.to_rgb8())
+            };
+            encoded
                 .save_with_format(output_path, format)
                 .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
         }
+        ImageFormat::Hdr => {
+            // Radiance HDR has no alpha channel
+            let output_file = File::create(output_path)
+                .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+            DynamicImage::ImageRgb32F(img.to_rgb32f())
+                .write_with_encoder(HdrEncoder::new(BufWriter::new(output_file)))
+                .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        }
+        ImageFormat::OpenExr => {
+            let output_file = File::create(output_path)
+                .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+            img.write_with_encoder(OpenExrEncoder::new(BufWriter::new(output_file)))
+                .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
+        }
+        ImageFormat::Ico => {
+            // Both Windows ICO and Apple ICNS; the extension picks the container
+            let extension = output_path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("ico")
+                .to_lowercase();
+            let sizes = icon::icon_sizes(&extension, icon)?;
+            icon::save_icon(img, output_path, &sizes)?;
+        }
         _ => {
             img.save_with_format(output_path, format)
                 .map_err(|e| ConversionError::EncodeError(e.to_string()))?;
         }
     }
 
-    Ok(())
+    metadata.embed_in_file(output_path, format, img)
 }
 
 // ============================================================================
 // PDF Generation
 // ============================================================================
 
-fn image_to_pdf(img: &DynamicImage, output_path: &Path) -> Result<(), ConversionError> {
-    use printpdf::{ColorBits, ColorSpace, Image, ImageFilter, ImageTransform, ImageXObject, Mm, PdfDocument, Px};
-
-    let (width, height) = (img.width(), img.height());
-
-    // Convert to mm (assuming 96 DPI)
-    let width_mm = Mm((width as f32 / 96.0) * 25.4);
-    let height_mm = Mm((height as f32 / 96.0) * 25.4);
-
-    let (doc, page1, layer1) = PdfDocument::new("Image", width_mm, height_mm, "Layer 1");
-    let current_layer = doc.get_page(page1).get_layer(layer1);
-
-    // Convert image to JPEG bytes for PDF embedding
-    let mut jpeg_bytes = Vec::new();
-    let mut cursor = std::io::Cursor::new(&mut jpeg_bytes);
-    img.to_rgb8()
-        .write_to(&mut cursor, ImageFormat::Jpeg)
-        .map_err(|e| ConversionError::PdfError(e.to_string()))?;
-
-    let image = Image::from(
-        ImageXObject {
-            width: Px(width as usize),
-            height: Px(height as usize),
-            color_space: ColorSpace::Rgb,
-            bits_per_component: ColorBits::Bit8,
-            interpolate: true,
-            image_data: jpeg_bytes,
-            image_filter: Some(ImageFilter::DCT),
-            clipping_bbox: None,
-            smask: None,
-        }
-    );
-
-    image.add_to_layer(
-        current_layer,
-        ImageTransform {
-            translate_x: Some(Mm(0.0)),
-            translate_y: Some(Mm(0.0)),
-            scale_x: Some(width_mm.0 / width as f32),
-            scale_y: Some(height_mm.0 / height as f32),
-            ..Default::default()
-        },
-    );
-
-    doc.save(&mut BufWriter::new(
-        File::create(output_path).map_err(|e| ConversionError::PdfError(e.to_string()))?,
-    ))
-    .map_err(|e| ConversionError::PdfError(e.to_string()))?;
-
-    Ok(())
-}
-
-fn text_to_pdf(text: &str, output_path: &Path, title: &str) -> Result<(), ConversionError> {
+fn text_to_pdf(
+    text: &str,
+    output_path: &Path,
+    title: &str,
+    author: Option<&str>,
+) -> Result<(), ConversionError> {
     use printpdf::{BuiltinFont, Mm, PdfDocument};
 
     // A4 size
@@ -410,6 +916,10 @@ fn text_to_pdf(text: &str, output_path: &Path, title: &str) -> Result<(), Conver
     let height_mm = Mm(297.0);
 
     let (doc, page1, layer1) = PdfDocument::new(title, width_mm, height_mm, "Layer 1");
+    let doc = match author {
+        Some(author) => doc.with_author(author),
+        None => doc,
+    };
     let font = doc.add_builtin_font(BuiltinFont::Courier)
         .map_err(|e| ConversionError::PdfError(e.to_string()))?;
 
@@ -472,6 +982,8 @@ fn markdown_to_html(markdown: &str) -> String {
     options.extension.table = true;
     options.extension.autolink = true;
     options.extension.tasklist = true;
+    // YAML front matter is metadata, not content
+    options.extension.front_matter_delimiter = Some("---".to_string());
     comrak_md_to_html(markdown, &options)
 }
 
@@ -484,22 +996,52 @@ pub fn convert_document(
     output_path: &Path,
     input_ext: &str,
     output_format: &str,
+) -> Result<(), ConversionError> {
+    convert_document_with_metadata(input_path, output_path, input_ext, output_format, false)
+}
+
+/// Converts a document, carrying its title and author (from HTML `<title>` and
+/// author meta tags, Markdown front matter, RTF `\info` or the PDF document
+/// info) into the output when `preserve_metadata` is set. Otherwise the output
+/// is titled after the file name and has no author.
+pub fn convert_document_with_metadata(
+    input_path: &Path,
+    output_path: &Path,
+    input_ext: &str,
+    output_format: &str,
+    preserve_metadata: bool,
 ) -> Result<(), ConversionError> {
     let input_lower = input_ext.to_lowercase();
     let output_lower = output_format.to_lowercase();
 
+    let info = if preserve_metadata && input_lower == "pdf" {
+        pdf_text::document_info(input_path)
+    } else if preserve_metadata
+        && matches!(input_lower.as_str(), "html" | "htm" | "md" | "markdown" | "rtf")
+    {
+        DocumentInfo::extract(&read_text_file(input_path)?, &input_lower)
+    } else {
+        DocumentInfo::default()
+    };
+    let title = info
+        .title
+        .as_deref()
+        .or_else(|| input_path.file_stem().and_then(|s| s.to_str()))
+        .unwrap_or("Document");
+    let author = info.author.as_deref();
+
     match (input_lower.as_str(), output_lower.as_str()) {
         // TXT conversions
         ("txt", "pdf") => {
             let text = read_text_file(input_path)?;
-            let title = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document");
-            text_to_pdf(&text, output_path, title)
+            text_to_pdf(&text, output_path, title, author)
         }
         ("txt", "html") => {
             let text = read_text_file(input_path)?;
             let html = format!(
-                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n</head>\n<body>\n<pre>{}</pre>\n</body>\n</html>",
-                input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document"),
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n{}</head>\n<body>\n<pre>{}</pre>\n</body>\n</html>",
+                html_escape(title),
+                author_meta_tag(author),
                 html_escape(&text)
             );
             fs::write(output_path, html).map_err(|e| ConversionError::WriteError(e.to_string()))
@@ -514,8 +1056,9 @@ pub fn convert_document(
             let md = read_text_file(input_path)?;
             let html_body = markdown_to_html(&md);
             let full_html = format!(
-                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n<style>body{{font-family:sans-serif;max-width:800px;margin:0 auto;padding:20px;}}pre{{background:#f4f4f4;padding:10px;overflow-x:auto;}}code{{background:#f4f4f4;padding:2px 4px;}}</style>\n</head>\n<body>\n{}\n</body>\n</html>",
-                input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document"),
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n{}<style>body{{font-family:sans-serif;max-width:800px;margin:0 auto;padding:20px;}}pre{{background:#f4f4f4;padding:10px;overflow-x:auto;}}code{{background:#f4f4f4;padding:2px 4px;}}</style>\n</head>\n<body>\n{}\n</body>\n</html>",
+                html_escape(title),
+                author_meta_tag(author),
                 html_body
             );
             fs::write(output_path, full_html).map_err(|e| ConversionError::WriteError(e.to_string()))
@@ -530,8 +1073,7 @@ pub fn convert_document(
             let md = read_text_file(input_path)?;
             let html = markdown_to_html(&md);
             let text = html_to_text(&html);
-            let title = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document");
-            text_to_pdf(&text, output_path, title)
+            text_to_pdf(&text, output_path, title, author)
         }
 
         // HTML conversions
@@ -549,8 +1091,7 @@ pub fn convert_document(
         ("html" | "htm", "pdf") => {
             let html = read_text_file(input_path)?;
             let text = html_to_text(&html);
-            let title = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document");
-            text_to_pdf(&text, output_path, title)
+            text_to_pdf(&text, output_path, title, author)
         }
 
         // RTF conversions (basic - just extract text)
@@ -562,15 +1103,16 @@ pub fn convert_document(
         ("rtf", "pdf") => {
             let rtf_content = read_text_file(input_path)?;
             let text = extract_rtf_text(&rtf_content);
-            let title = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Document");
-            text_to_pdf(&text, output_path, title)
+            text_to_pdf(&text, output_path, title, author)
         }
         ("rtf", "html") | ("rtf", "md") => {
             let rtf_content = read_text_file(input_path)?;
             let text = extract_rtf_text(&rtf_content);
             if output_lower == "html" {
                 let html = format!(
-                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n</head>\n<body>\n<pre>{}</pre>\n</body>\n</html>",
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n{}</head>\n<body>\n<pre>{}</pre>\n</body>\n</html>",
+                    html_escape(title),
+                    author_meta_tag(author),
                     html_escape(&text)
                 );
                 fs::write(output_path, html).map_err(|e| ConversionError::WriteError(e.to_string()))
@@ -582,7 +1124,7 @@ pub fn convert_document(
         // TXT to RTF
         ("txt", "rtf") => {
             let text = read_text_file(input_path)?;
-            let rtf = text_to_rtf(&text);
+            let rtf = text_to_rtf(&text, &info);
             fs::write(output_path, rtf).map_err(|e| ConversionError::WriteError(e.to_string()))
         }
         ("md" | "markdown", "rtf") | ("html" | "htm", "rtf") => {
@@ -593,10 +1135,27 @@ pub fn convert_document(
             } else {
                 html_to_text(&content)
             };
-            let rtf = text_to_rtf(&text);
+            let rtf = text_to_rtf(&text, &info);
             fs::write(output_path, rtf).map_err(|e| ConversionError::WriteError(e.to_string()))
         }
 
+        // PDF text extraction, in reading order
+        ("pdf", "txt" | "md" | "html" | "rtf") => {
+            let pages = pdf_text::extract(input_path)?;
+            let content = match output_lower.as_str() {
+                "md" => pdf_text::to_markdown(&pages),
+                "html" => format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n{}<style>body{{font-family:sans-serif;max-width:800px;margin:0 auto;padding:20px;}}.page{{break-after:page;}}</style>\n</head>\n<body>\n{}\n</body>\n</html>",
+                    html_escape(title),
+                    author_meta_tag(author),
+                    pdf_text::to_html_body(&pages)
+                ),
+                "rtf" => text_to_rtf(&pdf_text::to_text(&pages).replace('\x0C', ""), &info),
+                _ => pdf_text::to_text(&pages),
+            };
+            fs::write(output_path, content).map_err(|e| ConversionError::WriteError(e.to_string()))
+        }
+
         // For DOCX, DOC, ODT - require LibreOffice
         ("docx" | "doc" | "odt", _) | (_, "docx" | "doc" | "odt") => {
             convert_with_libreoffice(input_path, output_path, output_format)
@@ -614,6 +1173,18 @@ pub fn convert_document(
     }
 }
 
+fn rtf_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '{' => "\\{".to_string(),
+            '}' => "\\}".to_string(),
+            c if c as u32 > 127 => format!("\\u{}?", c as i32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
 fn html_escape(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -680,21 +1251,29 @@ fn extract_rtf_text(rtf: &str) -> String {
     result.trim().to_string()
 }
 
-fn text_to_rtf(text: &str) -> String {
+/// `<meta name="author">` tag for an HTML head, or nothing without an author.
+fn author_meta_tag(author: Option<&str>) -> String {
+    author
+        .map(|a| format!("<meta name=\"author\" content=\"{}\">\n", html_escape(a)))
+        .unwrap_or_default()
+}
+
+fn text_to_rtf(text: &str, info: &DocumentInfo) -> String {
     let mut rtf = String::from("{\\rtf1\\ansi\\deff0\n");
 
+    if info.title.is_some() || info.author.is_some() {
+        rtf.push_str("{\\info");
+        if let Some(title) = &info.title {
+            rtf.push_str(&format!("{{\\title {}}}", rtf_escape(title)));
+        }
+        if let Some(author) = &info.author {
+            rtf.push_str(&format!("{{\\author {}}}", rtf_escape(author)));
+        }
+        rtf.push_str("}\n");
+    }
+
     for line in text.lines() {
-        // Escape special characters
-        let escaped: String = line.chars().map(|c| {
-            match c {
-                '\\' => "\\\\".to_string(),
-                '{' => "\\{".to_string(),
-                '}' => "\\}".to_string(),
-                c if c as u32 > 127 => format!("\\u{}?", c as i32),
-                c => c.to_string(),
-            }
-        }).collect();
-        rtf.push_str(&escaped);
+        rtf.push_str(&rtf_escape(line));
         rtf.push_str("\\par\n");
     }
 
@@ -769,8 +1348,22 @@ fn convert_with_libreoffice(
 ) -> Result<(), ConversionError> {
     let soffice = find_libreoffice().ok_or(ConversionError::LibreOfficeNotFound)?;
 
-    let output_dir = output_path.parent().unwrap_or(Path::new("."));
+    // LibreOffice names its output after the input, which could clobber an
+    // unrelated file, so let it write into a private scratch directory
+    let output_dir = output_path.parent().unwrap_or(Path::new(".")).join(scratch_name("soffice"));
+    fs::create_dir_all(&output_dir).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    let result = run_libreoffice(&soffice, input_path, output_path, &output_dir, output_format);
+    let _ = fs::remove_dir_all(&output_dir);
+    result
+}
 
+fn run_libreoffice(
+    soffice: &Path,
+    input_path: &Path,
+    output_path: &Path,
+    output_dir: &Path,
+    output_format: &str,
+) -> Result<(), ConversionError> {
     // LibreOffice filter name mapping
     let filter = match output_format.to_lowercase().as_str() {
         "pdf" => "pdf",
@@ -783,16 +1376,16 @@ fn convert_with_libreoffice(
         _ => return Err(ConversionError::UnsupportedFormat(output_format.to_string())),
     };
 
-    let output = StdCommand::new(&soffice)
-        .args([
-            "--headless",
-            "--convert-to",
-            filter,
-            "--outdir",
-            output_dir.to_str().unwrap_or("."),
-            input_path.to_str().unwrap_or(""),
-        ])
-        .output()
+    let mut cmd = StdCommand::new(soffice);
+    cmd.args([
+        "--headless",
+        "--convert-to",
+        filter,
+        "--outdir",
+        output_dir.to_str().unwrap_or("."),
+        input_path.to_str().unwrap_or(""),
+    ]);
+    let output = jobs::run_command(&mut cmd)
         .map_err(|e| ConversionError::DocumentError(e.to_string()))?;
 
     if !output.status.success() {
@@ -807,13 +1400,14 @@ fn convert_with_libreoffice(
         get_extension(output_format)
     ));
 
-    // Rename if needed
-    if expected_output != output_path && expected_output.exists() {
-        fs::rename(&expected_output, output_path)
-            .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    if !expected_output.exists() {
+        return Err(ConversionError::DocumentError(
+            "LibreOffice did not produce an output file".to_string(),
+        ));
     }
 
-    Ok(())
+    fs::rename(&expected_output, output_path)
+        .map_err(|e| ConversionError::WriteError(e.to_string()))
 }
 
 fn convert_with_pandoc(
@@ -839,7 +1433,7 @@ fn convert_with_pandoc(
 
     cmd.arg(input_path);
 
-    let output = cmd.output()
+    let output = jobs::run_command(&mut cmd)
         .map_err(|e| ConversionError::DocumentError(e.to_string()))?;
 
     if !output.status.success() {
@@ -915,6 +1509,8 @@ fn convert_with_ffmpeg(
     output_format: &str,
     quality: u8,
     bitrate: Option<&str>,
+    metadata: MetadataMode,
+    progress: Option<&ProgressReporter>,
 ) -> Result<(), ConversionError> {
     let ffmpeg = find_ffmpeg().ok_or(ConversionError::FFmpegNotFound)?;
 
@@ -922,8 +1518,42 @@ fn convert_with_ffmpeg(
     cmd.arg("-i").arg(input_path);
     cmd.arg("-y"); // Overwrite output
 
+    let format_lower = output_format.to_lowercase();
+    let input_is_audio = input_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| get_media_category(&e.to_lowercase()) == "audio");
+
+    match metadata {
+        MetadataMode::Strip => {
+            cmd.arg("-map_metadata").arg("-1").arg("-map_chapters").arg("-1");
+            // Cover art can carry its own EXIF, so drop it from audio outputs
+            if get_media_category(&format_lower) == "audio" {
+                cmd.arg("-vn");
+            }
+        }
+        MetadataMode::Preserve | MetadataMode::PreservePublic => {
+            cmd.arg("-map_metadata").arg("0");
+            if matches!(format_lower.as_str(), "mp4" | "mov" | "m4a") {
+                // Keep custom tags that the MP4 muxer drops by default
+                cmd.arg("-movflags").arg("use_metadata_tags");
+            }
+            if input_is_audio && matches!(format_lower.as_str(), "mp3" | "flac" | "m4a") {
+                // Carry embedded cover art across as an attached picture
+                cmd.args(["-map", "0:a", "-map", "0:v?", "-c:v", "copy"])
+                    .args(["-disposition:v:0", "attached_pic"]);
+            }
+            if metadata == MetadataMode::PreservePublic {
+                // Recording location, as written by phones and cameras
+                for key in ["location", "location-eng", "com.apple.quicktime.location.ISO6709"] {
+                    cmd.arg("-metadata").arg(format!("{}=", key));
+                }
+            }
+        }
+    }
+
     // Apply format-specific settings
-    match output_format.to_lowercase().as_str() {
+    match format_lower.as_str() {
         // Audio formats
         "mp3" => {
             let br = bitrate.unwrap_or("192k");
@@ -1031,9 +1661,35 @@ fn convert_with_ffmpeg(
         _ => {}
     }
 
-    cmd.arg(output_path);
+    run_ffmpeg(cmd, output_path, progress, || get_media_duration(input_path))
+}
 
-    let output = cmd.output().map_err(|e| ConversionError::FFmpegError(e.to_string()))?;
+/// Runs an FFmpeg command that writes `output_path`. With a reporter,
+/// progress is streamed against `duration`, the seconds of media to encode.
+fn run_ffmpeg(
+    mut cmd: StdCommand,
+    output_path: &Path,
+    progress: Option<&ProgressReporter>,
+    duration: impl FnOnce() -> Option<f64>,
+) -> Result<(), ConversionError> {
+    let output = match progress {
+        Some(reporter) => {
+            // Stream machine-readable progress to stdout while encoding
+            cmd.arg("-progress").arg("pipe:1").arg("-nostats");
+            cmd.arg(output_path);
+
+            let duration = duration();
+            jobs::run_command_streaming(&mut cmd, |stdout| {
+                progress::read_ffmpeg_progress(BufReader::new(stdout), duration, reporter);
+                Vec::new()
+            })
+        }
+        None => {
+            cmd.arg(output_path);
+            jobs::run_command(&mut cmd)
+        }
+    }
+    .map_err(|e| ConversionError::FFmpegError(e.to_string()))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -1043,17 +1699,72 @@ fn convert_with_ffmpeg(
     Ok(())
 }
 
+/// Turns a video clip into a looping GIF or WebP. GIFs get a palette built
+/// from the clip itself, which looks far better than FFmpeg's default one.
+/// Frames are scaled down to the transform's `max_width`/`max_height`.
+fn video_to_animation(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConversionOptions,
+    progress: Option<&ProgressReporter>,
+) -> Result<(), ConversionError> {
+    let ffmpeg = find_ffmpeg().ok_or(ConversionError::FFmpegNotFound)?;
+    let clip = &options.animation;
+    let fps = clip.fps.unwrap_or(animation::DEFAULT_VIDEO_FPS);
+    let start = clip.start.unwrap_or(0.0);
+
+    let mut cmd = StdCommand::new(ffmpeg);
+    // Seeking before the input is fast and frame-accurate when re-encoding
+    if start > 0.0 {
+        cmd.arg("-ss").arg(start.to_string());
+    }
+    if let Some(duration) = clip.duration {
+        cmd.arg("-t").arg(duration.to_string());
+    }
+    cmd.arg("-i").arg(input_path);
+    cmd.arg("-y");
+    cmd.args(["-an", "-map_metadata", "-1", "-loop", "0"]);
+
+    let mut filters = vec![format!("fps={}", fps)];
+    let transform = &options.transform;
+    if transform.max_width.is_some() || transform.max_height.is_some() {
+        let bound = |max: Option<u32>, input: &str| match max {
+            Some(max) => format!("'min({},{})'", input, max),
+            None => input.to_string(),
+        };
+        filters.push(format!(
+            "scale={}:{}:force_original_aspect_ratio=decrease:flags=lanczos",
+            bound(transform.max_width, "iw"),
+            bound(transform.max_height, "ih")
+        ));
+    }
+    let filters = filters.join(",");
+
+    if options.output_format.eq_ignore_ascii_case("gif") {
+        cmd.arg("-filter_complex").arg(format!(
+            "{},split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=diff_mode=rectangle",
+            filters
+        ));
+    } else {
+        cmd.arg("-vf").arg(filters);
+        cmd.args(["-c:v", "libwebp"]);
+        cmd.arg("-lossless").arg(if options.lossless { "1" } else { "0" });
+        cmd.arg("-q:v").arg(options.quality.clamp(1, 100).to_string());
+    }
+
+    run_ffmpeg(cmd, output_path, progress, || {
+        clip.duration
+            .or_else(|| get_media_duration(input_path).map(|total| (total - start).max(0.0)))
+    })
+}
+
 fn get_media_duration(path: &Path) -> Option<f64> {
     let ffmpeg = find_ffmpeg()?;
 
+    // With no output file FFmpeg only probes the input, printing its header and
+    // exiting with an error, which is all we need to read the duration
     let output = StdCommand::new(ffmpeg)
-        .args([
-            "-i",
-            path.to_str()?,
-            "-f",
-            "null",
-            "-",
-        ])
+        .args(["-i", path.to_str()?])
         .output()
         .ok()?;
 
@@ -1090,27 +1801,31 @@ fn pdf_to_image(
     quality: u8,
     page: Option<usize>,
 ) -> Result<(), ConversionError> {
-    // Try using ImageMagick/GraphicsMagick first (if available)
+    let dpi = pdf_render_dpi(quality);
+
+    // Render in-process when built with PDFium, so no external tool is needed
+    if let Some(img) = pdf_render::render_page(input_path, page.unwrap_or(0), dpi as f32)? {
+        jobs::check_cancelled()?;
+        return save_pdf_page(&img, output_path, output_format, quality);
+    }
+
+    // Then try ImageMagick/GraphicsMagick (if available)
     if let Some(convert_cmd) = find_imagemagick() {
-        let density = match quality {
-            90..=100 => "300",
-            70..=89 => "200",
-            _ => "150",
-        };
+        let density = dpi.to_string();
 
         let page_spec = page.map(|p| format!("[{}]", p)).unwrap_or_default();
         let input_with_page = format!("{}{}", input_path.to_string_lossy(), page_spec);
 
-        let output = StdCommand::new(&convert_cmd)
-            .args([
-                "-density",
-                density,
-                &input_with_page,
-                "-quality",
-                &quality.to_string(),
-                output_path.to_str().unwrap_or(""),
-            ])
-            .output()
+        let mut cmd = StdCommand::new(&convert_cmd);
+        cmd.args([
+            "-density",
+            &density,
+            &input_with_page,
+            "-quality",
+            &quality.to_string(),
+            output_path.to_str().unwrap_or(""),
+        ]);
+        let output = jobs::run_command(&mut cmd)
             .map_err(|e| ConversionError::PdfError(e.to_string()))?;
 
         if output.status.success() {
@@ -1130,23 +1845,19 @@ fn pdf_to_image(
         let mut cmd = StdCommand::new(&pdftoppm);
         cmd.arg(format_arg);
 
+        // -singlefile drops the page number pdftoppm would add to the name
         if let Some(p) = page {
             cmd.arg("-f").arg((p + 1).to_string())
                .arg("-l").arg((p + 1).to_string());
-        } else {
-            cmd.arg("-singlefile");
         }
+        cmd.arg("-singlefile");
 
-        cmd.arg("-r").arg(match quality {
-            90..=100 => "300",
-            70..=89 => "200",
-            _ => "150",
-        });
+        cmd.arg("-r").arg(dpi.to_string());
 
         cmd.arg(input_path);
         cmd.arg(&output_stem);
 
-        let result = cmd.output()
+        let result = jobs::run_command(&mut cmd)
             .map_err(|e| ConversionError::PdfError(e.to_string()))?;
 
         if result.status.success() {
@@ -1161,16 +1872,56 @@ fn pdf_to_image(
         }
     }
 
-    // Fallback: Try FFmpeg (can handle some PDFs)
-    if let Ok(()) = convert_with_ffmpeg(input_path, output_path, output_format, quality, None) {
+    jobs::check_cancelled()?;
+
+    // Fallback: Try FFmpeg (can handle some PDFs, first page only)
+    if page.unwrap_or(0) == 0
+        && convert_with_ffmpeg(
+            input_path,
+            output_path,
+            output_format,
+            quality,
+            None,
+            MetadataMode::Strip,
+            None,
+        )
+        .is_ok()
+    {
         return Ok(());
     }
 
     Err(ConversionError::PdfError(
-        "No PDF renderer available. Install ImageMagick, Poppler, or Ghostscript.".to_string()
+        "No PDF renderer available: PDFium was not found and ImageMagick, Poppler or Ghostscript are not installed.".to_string()
     ))
 }
 
+/// Rendering resolution for PDF pages, higher for higher quality settings.
+fn pdf_render_dpi(quality: u8) -> u32 {
+    match quality {
+        90..=100 => 300,
+        70..=89 => 200,
+        _ => 150,
+    }
+}
+
+/// Writes a page rendered in-process to `output_path`.
+fn save_pdf_page(
+    img: &DynamicImage,
+    output_path: &Path,
+    output_format: &str,
+    quality: u8,
+) -> Result<(), ConversionError> {
+    save_image(
+        img,
+        output_path,
+        get_image_format(output_format)?,
+        quality,
+        false,
+        &IconOptions::default(),
+        &ImageMetadata::default(),
+    )
+}
+
 fn find_imagemagick() -> Option<PathBuf> {
     let names = if cfg!(target_os = "windows") {
         vec!["magick.exe", "convert.exe", "magick", "convert"]
@@ -1215,7 +1966,30 @@ pub fn convert_file(
     overwrite_existing: bool,
     bitrate: Option<String>,
 ) -> ConversionResult {
-    let input_path = Path::new(&input_path);
+    let options = ConversionOptions {
+        output_format,
+        quality,
+        output_dir,
+        preserve_metadata,
+        overwrite_existing,
+        bitrate,
+        ..ConversionOptions::default()
+    };
+    convert_file_with_options(&input_path, &options, None)
+}
+
+/// Converts a single file, optionally reporting progress for long-running
+/// (FFmpeg) conversions.
+pub fn convert_file_with_options(
+    input_path: &str,
+    options: &ConversionOptions,
+    progress: Option<&ProgressReporter>,
+) -> ConversionResult {
+    let output_format = options.output_format.as_str();
+    let quality = options.quality;
+    let metadata_mode = MetadataMode::from_options(options);
+
+    let input_path = Path::new(input_path);
 
     if !input_path.exists() {
         return ConversionResult::error(format!("Input file not found: {:?}", input_path));
@@ -1233,93 +2007,190 @@ pub fn convert_file(
         .to_lowercase();
 
     let category = get_media_category(&input_ext);
-    let output_category = get_media_category(&output_format);
+    let output_category = get_media_category(output_format);
+
+    if input_ext == "pdf" {
+        if let Some(password) = options.pdf_password.as_deref() {
+            match pdf_security::decrypt_if_protected(input_path, password) {
+                Ok(Some(doc)) => {
+                    return convert_decrypted_pdf(input_path, doc, options, original_size, progress)
+                }
+                Ok(None) => {}
+                Err(e) => return ConversionResult::error(e.to_string()),
+            }
+        }
+    }
+
+    // Several PDF pages go to numbered files or a ZIP instead of one output
+    if input_ext == "pdf" && output_category == "image" && options.pdf_export.exports_pages() {
+        return pdf_export::export_pages(input_path, options, original_size);
+    }
 
     // Generate output path
     let output_path = match generate_output_path(
         input_path,
-        &output_format,
-        output_dir.as_deref(),
-        overwrite_existing,
+        output_format,
+        options.output_dir.as_deref(),
+        options.overwrite_existing,
     ) {
         Ok(p) => p,
         Err(e) => return ConversionResult::error(e.to_string()),
     };
+    let output = AtomicOutput::new(&output_path);
+    let mut extra_outputs = Vec::new();
 
     // Route to appropriate converter based on category
     let result = match (category, output_category) {
         // Image to image
         ("image", "image") => {
-            let img = if input_ext == "svg" {
-                match load_svg(input_path, None) {
-                    Ok(i) => i,
+            let target_format = match get_image_format(output_format) {
+                Ok(f) => f,
+                Err(e) => return ConversionResult::error(e.to_string()),
+            };
+
+            // Animated sources keep every frame when the target can animate too
+            let frames = if animation::can_animate(&input_ext)
+                && animation::can_animate(&output_format.to_lowercase())
+            {
+                match animation::load_frames(input_path, &input_ext) {
+                    Ok(frames) => frames,
                     Err(e) => return ConversionResult::error(e.to_string()),
                 }
             } else {
-                match load_image(input_path) {
-                    Ok(i) => i,
-                    Err(e) => return ConversionResult::error(e.to_string()),
-                }
+                None
             };
 
-            let target_format = match get_image_format(&output_format) {
-                Ok(f) => f,
-                Err(e) => return ConversionResult::error(e.to_string()),
+            let saved = match frames {
+                Some(frames) => {
+                    save_transformed_animation(frames, output.path(), target_format, options)
+                }
+                None => {
+                    let (img, metadata) = match load_transformed_image(
+                        input_path,
+                        &input_ext,
+                        options,
+                        metadata_mode,
+                    ) {
+                        Ok(loaded) => loaded,
+                        Err(e) => return ConversionResult::error(e.to_string()),
+                    };
+
+                    if let Err(e) = jobs::check_cancelled() {
+                        return ConversionResult::error(e.to_string());
+                    }
+
+                    save_image(
+                        &img,
+                        output.path(),
+                        target_format,
+                        quality.clamp(1, 100),
+                        options.lossless,
+                        &options.icon,
+                        &metadata,
+                    )
+                }
             };
 
-            save_image(&img, &output_path, target_format, quality.clamp(1, 100))
+            saved.and_then(|_| {
+                if options.layers.export_layers && layers::is_layered(&input_ext) {
+                    extra_outputs = export_layers(input_path, &input_ext, &output_path, options)?;
+                }
+                if options.animation.extract_frames && animation::can_animate(&input_ext) {
+                    let frames = export_frames(input_path, &input_ext, &output_path, options)?;
+                    extra_outputs.extend(frames);
+                }
+                Ok(())
+            })
         }
 
         // Image to PDF
+        ("image", "document")
+            if input_ext == "svg"
+                && output_format.to_lowercase() == "pdf"
+                && svg::can_convert_to_pdf(options) =>
+        {
+            svg::svg_to_pdf(input_path, &options.svg, output.path())
+        }
         ("image", "document") if output_format.to_lowercase() == "pdf" => {
-            let img = if input_ext == "svg" {
-                match load_svg(input_path, None) {
-                    Ok(i) => i,
-                    Err(e) => return ConversionResult::error(e.to_string()),
-                }
-            } else {
-                match load_image(input_path) {
-                    Ok(i) => i,
-                    Err(e) => return ConversionResult::error(e.to_string()),
-                }
-            };
-
-            image_to_pdf(&img, &output_path)
+            image_pdf::image_to_pdf(input_path, output.path(), options)
         }
 
         // PDF to image
         ("document", "image") if input_ext == "pdf" => {
-            pdf_to_image(input_path, &output_path, &output_format, quality, None)
+            pdf_to_image(input_path, output.path(), output_format, quality, None)
         }
 
-        // Document to document
-        ("document", "document") => {
-            convert_document(input_path, &output_path, &input_ext, &output_format)
+        // PDF to PDF shrinks the file
+        ("document", "document")
+            if input_ext == "pdf" && output_format.eq_ignore_ascii_case("pdf") =>
+        {
+            pdf_optimize::optimize_pdf(input_path, output.path(), options)
         }
 
+        // Document to document
+        ("document", "document") => convert_document_with_metadata(
+            input_path,
+            output.path(),
+            &input_ext,
+            output_format,
+            options.preserve_metadata,
+        ),
+
         // Document to other text-based format
-        ("document", _) | (_, "document") => {
-            convert_document(input_path, &output_path, &input_ext, &output_format)
-        }
+        ("document", _) | (_, "document") => convert_document_with_metadata(
+            input_path,
+            output.path(),
+            &input_ext,
+            output_format,
+            options.preserve_metadata,
+        ),
 
         // Audio/Video conversions via FFmpeg
         ("audio", "audio") | ("video", "video") | ("video", "audio") => {
             convert_with_ffmpeg(
                 input_path,
-                &output_path,
-                &output_format,
+                output.path(),
+                output_format,
                 quality,
-                bitrate.as_deref(),
+                options.bitrate.as_deref(),
+                metadata_mode,
+                progress,
             )
         }
 
+        // Video clips to animated GIF/WebP via FFmpeg
+        ("video", "image") if matches!(output_format.to_lowercase().as_str(), "gif" | "webp") => {
+            video_to_animation(input_path, output.path(), options, progress)
+        }
+
         _ => Err(ConversionError::UnsupportedFormat(format!(
             "Cannot convert {} to {}",
             input_ext, output_format
         ))),
     };
 
+    // Password protection is applied to the finished PDF, whichever route wrote it
+    let result = result.and_then(|_| {
+        if output_format.eq_ignore_ascii_case("pdf") {
+            pdf_security::protect(output.path(), &options.pdf_encryption)
+        } else {
+            Ok(())
+        }
+    });
+
+    // A cancelled job reports as cancelled even if the converter happened to finish.
+    // On any error the temp file is dropped, leaving the destination untouched.
+    let result = result
+        .and_then(|_| jobs::check_cancelled())
+        .and_then(|_| output.commit());
+
     if let Err(e) = result {
+        let e = if jobs::check_cancelled().is_err() {
+            ConversionError::Cancelled
+        } else {
+            e
+        };
+        remove_files(&extra_outputs);
         return ConversionResult::error(e.to_string());
     }
 
@@ -1328,15 +2199,70 @@ pub fn convert_file(
         Err(_) => 0,
     };
 
-    let _ = preserve_metadata; // Placeholder for future metadata preservation
-
-    ConversionResult::success(
+    let mut result = ConversionResult::success(
         output_path.to_string_lossy().to_string(),
         original_size,
         new_size,
-    )
+    );
+    result.output_paths.extend(extra_outputs);
+    result
 }
 
+/// Converts a decrypted PDF by saving it under the original file name in a
+/// scratch directory under the system temp folder, so every PDF route can
+/// read it and the outputs are named and placed as if converted from
+/// `input_path`.
+fn convert_decrypted_pdf(
+    input_path: &Path,
+    mut doc: lopdf::Document,
+    options: &ConversionOptions,
+    original_size: u64,
+    progress: Option<&ProgressReporter>,
+) -> ConversionResult {
+    let output_dir = match options.output_dir.as_deref().filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => input_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+    let (Some(file_name), Some(stem), Some(output_dir_str)) = (
+        input_path.file_name(),
+        input_path.file_stem().and_then(|s| s.to_str()),
+        output_dir.to_str(),
+    ) else {
+        return ConversionResult::error(ConversionError::InvalidPath.to_string());
+    };
+
+    let scratch_dir = std::env::temp_dir().join(scratch_name(stem));
+    if let Err(e) = fs::create_dir(&scratch_dir) {
+        return ConversionResult::error(ConversionError::WriteError(e.to_string()).to_string());
+    }
+    let copy = scratch_dir.join(file_name);
+
+    let mut result = match doc.save(&copy) {
+        Ok(_) => {
+            let options = ConversionOptions {
+                output_dir: Some(output_dir_str.to_string()),
+                pdf_password: None,
+                ..options.clone()
+            };
+            convert_file_with_options(&copy.to_string_lossy(), &options, progress)
+        }
+        Err(e) => ConversionResult::error(ConversionError::WriteError(e.to_string()).to_string()),
+    };
+    let _ = fs::remove_dir_all(&scratch_dir);
+
+    if result.success {
+        result.original_size = Some(original_size);
+    }
+    result
+}
+
+fn remove_files(paths: &[String]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(feature = "gui")]
 #[tauri::command]
 fn get_file_info(path: String) -> Result<MediaInfo, String> {
     let path = Path::new(&path);
@@ -1394,14 +2320,18 @@ fn get_file_info(path: String) -> Result<MediaInfo, String> {
     })
 }
 
-#[tauri::command]
-fn get_supported_formats(from_format: String) -> Vec<String> {
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_supported_formats(from_format: String) -> Vec<String> {
     let from = from_format.to_lowercase();
     let category = get_media_category(&from);
 
     match category {
+        "image" if !can_decode_image(&from) => vec![],
         "image" => {
-            let mut formats = vec!["jpg", "png", "webp", "gif", "bmp", "tiff", "ico", "avif", "pdf"];
+            let mut formats = vec![
+                "jpg", "png", "webp", "gif", "bmp", "tiff", "ico", "avif", "ppm", "pgm", "pbm",
+                "pam", "tga", "qoi", "hdr", "exr", "icns", "pdf",
+            ];
             // Remove self
             formats.retain(|&f| f != from);
             formats.iter().map(|s| s.to_string()).collect()
@@ -1415,35 +2345,41 @@ fn get_supported_formats(from_format: String) -> Vec<String> {
             if find_pandoc().is_some() {
                 formats.push("epub");
             }
-            formats.retain(|&f| f != from);
+            // PDF to PDF optimises the file
+            formats.retain(|&f| f != from || f == "pdf");
             formats.iter().map(|s| s.to_string()).collect()
         }
         "audio" => {
-            vec!["mp3", "wav", "flac", "ogg", "aac", "m4a", "opus", "wma", "aiff"]
+            ["mp3", "wav", "flac", "ogg", "aac", "m4a", "opus", "wma", "aiff"]
                 .iter()
                 .filter(|&&f| f != from)
                 .map(|s| s.to_string())
                 .collect()
         }
         "video" => {
-            vec!["mp4", "webm", "mkv", "avi", "mov", "flv", "wmv", "3gp", "mts", "ts", "ogv"]
-                .iter()
-                .filter(|&&f| f != from)
-                .map(|s| s.to_string())
-                .collect()
+            vec![
+                "mp4", "webm", "mkv", "avi", "mov", "flv", "wmv", "3gp", "mts", "ts", "ogv",
+                // Animated, from a clip of the video
+                "gif", "webp",
+            ]
+            .iter()
+            .filter(|&&f| f != from)
+            .map(|s| s.to_string())
+            .collect()
         }
         _ => vec![],
     }
 }
 
-#[tauri::command]
-fn is_conversion_supported(from_format: String, to_format: String) -> bool {
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn is_conversion_supported(from_format: String, to_format: String) -> bool {
     let from = from_format.to_lowercase();
     let to = to_format.to_lowercase();
     let from_category = get_media_category(&from);
     let to_category = get_media_category(&to);
 
     match (from_category, to_category) {
+        ("image", _) if !can_decode_image(&from) => false,
         ("image", "image") => true,
         ("image", "document") if to == "pdf" => true,
         ("document", "image") if from == "pdf" => true,
@@ -1464,17 +2400,18 @@ fn is_conversion_supported(from_format: String, to_format: String) -> bool {
         ("audio", "audio") => find_ffmpeg().is_some(),
         ("video", "video") => find_ffmpeg().is_some(),
         ("video", "audio") => find_ffmpeg().is_some(),
+        ("video", "image") if matches!(to.as_str(), "gif" | "webp") => find_ffmpeg().is_some(),
         _ => false,
     }
 }
 
-#[tauri::command]
-fn check_ffmpeg_available() -> bool {
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn check_ffmpeg_available() -> bool {
     find_ffmpeg().is_some()
 }
 
-#[tauri::command]
-fn check_tools_available() -> ToolAvailability {
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn check_tools_available() -> ToolAvailability {
     ToolAvailability {
         ffmpeg: find_ffmpeg().is_some(),
         libreoffice: find_libreoffice().is_some(),
@@ -1482,6 +2419,7 @@ fn check_tools_available() -> ToolAvailability {
     }
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 fn reveal_in_explorer(path: String) -> Result<(), String> {
     let path = Path::new(&path);
@@ -1525,30 +2463,271 @@ fn reveal_in_explorer(path: String) -> Result<(), String> {
 // Tauri Command Wrappers
 // ============================================================================
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-fn cmd_convert_file(
+#[allow(clippy::too_many_arguments)]
+async fn cmd_convert_file(
+    app: tauri::AppHandle,
     input_path: String,
     output_format: String,
     quality: u8,
     output_dir: Option<String>,
     preserve_metadata: bool,
+    strip_private_metadata: Option<bool>,
     overwrite_existing: bool,
     bitrate: Option<String>,
+    lossless: Option<bool>,
+    transform: Option<ImageTransform>,
+    heif: Option<HeifOptions>,
+    raw: Option<RawOptions>,
+    layers: Option<LayerOptions>,
+    icon: Option<IconOptions>,
+    animation: Option<AnimationOptions>,
+    svg: Option<SvgOptions>,
+    pdf_page: Option<PdfPageOptions>,
+    pdf_export: Option<PdfExportOptions>,
+    pdf_optimize: Option<PdfOptimizeOptions>,
+    pdf_encryption: Option<PdfEncryptionOptions>,
+    pdf_password: Option<String>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = ConversionOptions {
+        output_format,
+        quality,
+        output_dir,
+        preserve_metadata,
+        strip_private_metadata: strip_private_metadata.unwrap_or(false),
+        overwrite_existing,
+        bitrate,
+        lossless: lossless.unwrap_or(false),
+        transform: transform.unwrap_or_default(),
+        heif: heif.unwrap_or_default(),
+        raw: raw.unwrap_or_default(),
+        layers: layers.unwrap_or_default(),
+        icon: icon.unwrap_or_default(),
+        animation: animation.unwrap_or_default(),
+        svg: svg.unwrap_or_default(),
+        pdf_page: pdf_page.unwrap_or_default(),
+        pdf_export: pdf_export.unwrap_or_default(),
+        pdf_optimize: pdf_optimize.unwrap_or_default(),
+        pdf_encryption: pdf_encryption.unwrap_or_default(),
+        pdf_password,
+    };
+
+    // Progress is emitted as a Tauri event keyed by the caller's job id
+    let reporter = job_id.map(|id| {
+        ProgressReporter::new(
+            id,
+            Arc::new(move |progress: ConversionProgress| {
+                let _ = app.emit(PROGRESS_EVENT, progress);
+            }),
+        )
+    });
+
+    let registry = JobRegistry::global();
+    let job = registry.start(reporter.as_ref().map(|r| r.job_id().to_string()));
+
+    // Run off the main thread so cancel_conversion can be handled meanwhile
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = job.run(|| convert_file_with_options(&input_path, &options, reporter.as_ref()));
+        registry.finish(&job);
+        result
+    })
+    .await;
+
+    result.unwrap_or_else(|e| ConversionResult::error(e.to_string()))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_convert_batch(
+    input_paths: Vec<String>,
+    options: BatchOptions,
+    job_id: Option<String>,
+) -> Result<BatchResult, String> {
+    let registry = JobRegistry::global();
+    let job = registry.start(job_id);
+
+    // Run off the async runtime so long batches don't block other commands
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = job.run(|| convert_batch(&input_paths, &options));
+        registry.finish(&job);
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_images_to_pdf(
+    input_paths: Vec<String>,
+    options: ConversionOptions,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let registry = JobRegistry::global();
+    let job = registry.start(job_id);
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = job.run(|| images_to_pdf(&input_paths, &options));
+        registry.finish(&job);
+        result
+    })
+    .await;
+
+    result.unwrap_or_else(|e| ConversionResult::error(e.to_string()))
+}
+
+/// Runs a PDF page tool off the async runtime as a cancellable job.
+#[cfg(feature = "gui")]
+async fn run_pdf_tool(
+    job_id: Option<String>,
+    tool: impl FnOnce() -> ConversionResult + Send + 'static,
 ) -> ConversionResult {
-    convert_file(input_path, output_format, quality, output_dir, preserve_metadata, overwrite_existing, bitrate)
+    let registry = JobRegistry::global();
+    let job = registry.start(job_id);
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = job.run(tool);
+        registry.finish(&job);
+        result
+    })
+    .await;
+
+    result.unwrap_or_else(|e| ConversionResult::error(e.to_string()))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_merge_pdfs(
+    input_paths: Vec<String>,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || merge_pdfs(&input_paths, &options)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_split_pdf(
+    input_path: String,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || split_pdf(&input_path, &options)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_extract_pdf_pages(
+    input_path: String,
+    pages: String,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || extract_pdf_pages(&input_path, &pages, &options)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_delete_pdf_pages(
+    input_path: String,
+    pages: String,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || delete_pdf_pages(&input_path, &pages, &options)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_reorder_pdf_pages(
+    input_path: String,
+    order: String,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || reorder_pdf_pages(&input_path, &order, &options)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_rotate_pdf_pages(
+    input_path: String,
+    pages: Option<String>,
+    degrees: i32,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || {
+        rotate_pdf_pages(&input_path, pages.as_deref(), degrees, &options)
+    })
+    .await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn cmd_decrypt_pdf(
+    input_path: String,
+    password: String,
+    options: Option<PdfToolOptions>,
+    job_id: Option<String>,
+) -> ConversionResult {
+    let options = options.unwrap_or_default();
+    run_pdf_tool(job_id, move || {
+        decrypt_pdf(&input_path, &password, &options)
+    })
+    .await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn create_conversion_job() -> String {
+    JobRegistry::global().next_id()
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn cancel_conversion(job_id: String) -> bool {
+    JobRegistry::global().cancel(&job_id)
 }
 
 // ============================================================================
 // App Entry Point
 // ============================================================================
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            // PDFium ships as a bundle resource (see scripts/fetch-pdfium.mjs)
+            if let Ok(dir) = app.path().resource_dir() {
+                set_pdfium_library_dir(dir.join("pdfium"));
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             cmd_convert_file,
+            cmd_convert_batch,
+            cmd_images_to_pdf,
+            cmd_merge_pdfs,
+            cmd_split_pdf,
+            cmd_extract_pdf_pages,
+            cmd_delete_pdf_pages,
+            cmd_reorder_pdf_pages,
+            cmd_rotate_pdf_pages,
+            cmd_decrypt_pdf,
+            create_conversion_job,
+            cancel_conversion,
             get_file_info,
             get_supported_formats,
             is_conversion_supported,
@@ -0,0 +1,886 @@
+use crate::{ConversionError, ConversionOptions};
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+use std::fs;
+use std::path::Path;
+
+/// Identifier that prefixes an XMP packet in a JPEG APP1 segment.
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Largest XMP packet that fits in a single JPEG APP1 segment.
+const JPEG_MAX_XMP_LEN: usize = 65533 - JPEG_XMP_HEADER.len();
+
+// ============================================================================
+// Metadata Mode
+// ============================================================================
+
+/// How metadata from the source file is carried into the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MetadataMode {
+    /// Drop all descriptive metadata (EXIF, XMP, tags, document authors). ICC
+    /// colour profiles are still kept since they change how pixels render.
+    Strip,
+    /// Copy metadata, minus GPS location, camera/lens serial numbers, owner
+    /// names and maker notes.
+    PreservePublic,
+    /// Copy metadata as-is.
+    Preserve,
+}
+
+impl MetadataMode {
+    pub(crate) fn from_options(options: &ConversionOptions) -> Self {
+        match (options.preserve_metadata, options.strip_private_metadata) {
+            (false, _) => MetadataMode::Strip,
+            (true, true) => MetadataMode::PreservePublic,
+            (true, false) => MetadataMode::Preserve,
+        }
+    }
+}
+
+// ============================================================================
+// Image Metadata
+// ============================================================================
+
+#[derive(Debug, Default)]
+pub(crate) struct ImageMetadata {
+    pub icc: Option<Vec<u8>>,
+    /// Raw TIFF-structured EXIF data (without the `Exif\0\0` prefix).
+    pub exif: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+    /// Orientation of the source pixels, from EXIF or the container.
+    pub orientation: Option<Orientation>,
+}
+
+impl ImageMetadata {
+    /// Reads whatever metadata the decoder for `path` exposes. Unreadable
+    /// metadata is skipped rather than failing the conversion.
+    pub(crate) fn read(path: &Path) -> Self {
+        let decoder = ImageReader::open(path)
+            .ok()
+            .and_then(|r| r.with_guessed_format().ok())
+            .and_then(|r| r.into_decoder().ok());
+
+        let Some(mut decoder) = decoder else {
+            return Self::default();
+        };
+
+        Self {
+            icc: decoder.icc_profile().ok().flatten(),
+            exif: decoder.exif_metadata().ok().flatten(),
+            xmp: decoder.xmp_metadata().ok().flatten(),
+            orientation: decoder.orientation().ok(),
+        }
+    }
+
+    /// Keeps only the metadata allowed by `mode`.
+    pub(crate) fn for_mode(self, mode: MetadataMode) -> Self {
+        match mode {
+            MetadataMode::Preserve => self,
+            // XMP can embed GPS and serials anywhere in its RDF tree, so it
+            // is dropped rather than filtered
+            MetadataMode::PreservePublic => Self {
+                exif: self.exif.and_then(|exif| remove_private_exif(&exif)),
+                xmp: None,
+                ..self
+            },
+            MetadataMode::Strip => Self {
+                exif: None,
+                xmp: None,
+                ..self
+            },
+        }
+    }
+
+    /// Resets the EXIF orientation tag once the rotation has been applied to
+    /// the pixels, so viewers don't rotate the output a second time.
+    pub(crate) fn clear_orientation(&mut self) {
+        if let Some(exif) = &mut self.exif {
+            let _ = Orientation::remove_from_exif_chunk(exif);
+        }
+        self.orientation = Some(Orientation::NoTransforms);
+    }
+
+    /// Hands ICC and EXIF data to an encoder. Formats whose encoder cannot
+    /// store a given kind of metadata skip it; see `embed_in_file`.
+    pub(crate) fn apply_to<E: ImageEncoder>(&self, encoder: &mut E) {
+        if let Some(icc) = &self.icc {
+            let _ = encoder.set_icc_profile(icc.clone());
+        }
+        if let Some(exif) = &self.exif {
+            let _ = encoder.set_exif_metadata(exif.clone());
+        }
+    }
+
+    /// Embeds the metadata the `image` encoders can't write into an already
+    /// written file: the XMP packet for JPEG, PNG and WebP, and everything for
+    /// TIFF and AVIF, whose encoders store no metadata at all.
+    pub(crate) fn embed_in_file(
+        &self,
+        path: &Path,
+        format: ImageFormat,
+        img: &DynamicImage,
+    ) -> Result<(), ConversionError> {
+        let pending = match format {
+            ImageFormat::Tiff | ImageFormat::Avif => {
+                self.icc.is_some() || self.exif.is_some() || self.xmp.is_some()
+            }
+            _ => self.xmp.is_some(),
+        };
+        if !pending {
+            return Ok(());
+        }
+
+        let data = fs::read(path).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+        let (icc, exif, xmp) = (self.icc.as_deref(), self.exif.as_deref(), self.xmp.as_deref());
+        // Unlike a missing XMP packet, TIFF or AVIF output losing everything
+        // would go unnoticed, so failing to embed there is an error
+        let unsupported = |container: &str| {
+            ConversionError::EncodeError(format!("metadata not supported for this {} file", container))
+        };
+        let updated = match format {
+            ImageFormat::Jpeg => xmp.and_then(|xmp| insert_jpeg_xmp(&data, xmp)),
+            ImageFormat::Png => xmp.and_then(|xmp| insert_png_xmp(&data, xmp)),
+            ImageFormat::WebP => xmp.and_then(|xmp| insert_webp_chunks(&data, None, None, Some(xmp), img)),
+            ImageFormat::Tiff => Some(
+                insert_tiff_metadata(&data, icc, exif, xmp).ok_or_else(|| unsupported("TIFF"))?,
+            ),
+            ImageFormat::Avif => Some(
+                insert_avif_metadata(&data, icc, exif, xmp).ok_or_else(|| unsupported("AVIF"))?,
+            ),
+            _ => None,
+        };
+
+        match updated {
+            Some(updated) => {
+                fs::write(path, updated).map_err(|e| ConversionError::WriteError(e.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Embeds all kept metadata into WebP data produced by an encoder that
+    /// writes none of its own.
+    pub(crate) fn embed_in_webp(&self, data: Vec<u8>, img: &DynamicImage) -> Vec<u8> {
+        if self.icc.is_none() && self.exif.is_none() && self.xmp.is_none() {
+            return data;
+        }
+        insert_webp_chunks(
+            &data,
+            self.icc.as_deref(),
+            self.exif.as_deref(),
+            self.xmp.as_deref(),
+            img,
+        )
+        .unwrap_or(data)
+    }
+}
+
+/// Rebuilds EXIF data without GPS information, serial numbers, owner names,
+/// maker notes (which commonly embed serials) and the embedded thumbnail.
+/// Returns `None` if the data can't be parsed or rewritten, so nothing
+/// private can slip through on malformed input.
+fn remove_private_exif(exif: &[u8]) -> Option<Vec<u8>> {
+    use exif::{Context, Tag};
+
+    rewrite_exif(exif.to_vec(), |f| {
+        f.tag.context() != Context::Gps
+            && !matches!(
+                f.tag,
+                Tag::BodySerialNumber
+                    | Tag::LensSerialNumber
+                    | Tag::CameraOwnerName
+                    | Tag::ImageUniqueID
+                    | Tag::MakerNote
+            )
+    })
+}
+
+/// Extracts the EXIF tags from a TIFF-structured camera RAW file (CR2, NEF,
+/// ARW, DNG). The file's IFDs also describe its sensor data and previews, so
+/// the image-structure tags are dropped and only the shooting data is kept.
+pub(crate) fn exif_from_raw(data: Vec<u8>) -> Option<Vec<u8>> {
+    rewrite_exif(data, |f| !is_image_structure_tag(f.tag) && f.tag != exif::Tag::MakerNote)
+}
+
+/// Re-encodes the primary IFD of TIFF-structured EXIF data, keeping the
+/// fields accepted by `keep`. Unknown and vendor-private tags are always
+/// dropped since they may hold anything.
+fn rewrite_exif(data: Vec<u8>, keep: impl Fn(&exif::Field) -> bool) -> Option<Vec<u8>> {
+    use exif::experimental::Writer;
+    use exif::In;
+
+    let parsed = exif::Reader::new().read_raw(data).ok()?;
+    let little_endian = parsed.little_endian();
+
+    let kept: Vec<&exif::Field> = parsed
+        .fields()
+        .filter(|f| f.ifd_num == In::PRIMARY)
+        .filter(|f| f.tag.description().is_some())
+        .filter(|f| keep(f))
+        .collect();
+
+    if kept.is_empty() {
+        return None;
+    }
+
+    let mut writer = Writer::new();
+    for field in kept {
+        writer.push_field(field);
+    }
+
+    let mut buf = std::io::Cursor::new(Vec::new());
+    writer.write(&mut buf, little_endian).ok()?;
+    Some(buf.into_inner())
+}
+
+// ============================================================================
+// XMP Embedding
+// ============================================================================
+
+fn insert_jpeg_xmp(data: &[u8], xmp: &[u8]) -> Option<Vec<u8>> {
+    if xmp.len() > JPEG_MAX_XMP_LEN || !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    // Insert after SOI and any APP0 (JFIF) / APP1 (EXIF) segments
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF && matches!(data[pos + 1], 0xE0 | 0xE1) {
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        pos += 2 + len;
+    }
+    if pos > data.len() {
+        return None;
+    }
+
+    let segment_len = (2 + JPEG_XMP_HEADER.len() + xmp.len()) as u16;
+    let mut out = Vec::with_capacity(data.len() + segment_len as usize + 2);
+    out.extend_from_slice(&data[..pos]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&segment_len.to_be_bytes());
+    out.extend_from_slice(JPEG_XMP_HEADER);
+    out.extend_from_slice(xmp);
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
+
+fn insert_png_xmp(data: &[u8], xmp: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE_LEN: usize = 8;
+
+    // iTXt: keyword, NUL, compression flag, compression method,
+    // language tag NUL, translated keyword NUL, text
+    let mut chunk_data = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+    chunk_data.extend_from_slice(xmp);
+
+    // Metadata chunks must come before the image data
+    let mut pos = SIGNATURE_LEN;
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        if &data[pos + 4..pos + 8] == b"IDAT" {
+            break;
+        }
+        pos += 12 + len;
+    }
+    if pos + 8 > data.len() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(data.len() + chunk_data.len() + 12);
+    out.extend_from_slice(&data[..pos]);
+    out.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+    let crc_start = out.len();
+    out.extend_from_slice(b"iTXt");
+    out.extend_from_slice(&chunk_data);
+    let crc = crc32(&out[crc_start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
+
+/// Adds ICC, EXIF and XMP chunks to a WebP file, converting it to the
+/// extended (VP8X) layout if needed. Existing metadata chunks are kept unless
+/// replaced.
+fn insert_webp_chunks(
+    data: &[u8],
+    icc: Option<&[u8]>,
+    exif: Option<&[u8]>,
+    xmp: Option<&[u8]>,
+    img: &DynamicImage,
+) -> Option<Vec<u8>> {
+    const ICC_FLAG: u8 = 0b0010_0000;
+    const ALPHA_FLAG: u8 = 0b0001_0000;
+    const EXIF_FLAG: u8 = 0b0000_1000;
+    const XMP_FLAG: u8 = 0b0000_0100;
+
+    if data.len() < 20 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id: [u8; 4] = data[pos..pos + 4].try_into().ok()?;
+        let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let end = pos + 8 + len;
+        chunks.push((id, data.get(pos + 8..end)?.to_vec()));
+        pos = end + (len & 1);
+    }
+
+    // Metadata requires the extended (VP8X) header; simple files get one added
+    if !chunks.iter().any(|(id, _)| id == b"VP8X") {
+        let alpha = if img.color().has_alpha() { ALPHA_FLAG } else { 0 };
+        let mut vp8x = vec![alpha, 0, 0, 0];
+        vp8x.extend_from_slice(&(img.width() - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(img.height() - 1).to_le_bytes()[..3]);
+        chunks.insert(0, (*b"VP8X", vp8x));
+    }
+
+    let mut flags = 0;
+    if let Some(icc) = icc {
+        chunks.retain(|(id, _)| id != b"ICCP");
+        // ICCP must directly follow VP8X
+        chunks.insert(1, (*b"ICCP", icc.to_vec()));
+        flags |= ICC_FLAG;
+    }
+    if let Some(exif) = exif {
+        chunks.retain(|(id, _)| id != b"EXIF");
+        chunks.push((*b"EXIF", exif.to_vec()));
+        flags |= EXIF_FLAG;
+    }
+    if let Some(xmp) = xmp {
+        chunks.retain(|(id, _)| id != b"XMP ");
+        chunks.push((*b"XMP ", xmp.to_vec()));
+        flags |= XMP_FLAG;
+    }
+    chunks[0].1[0] |= flags;
+
+    let mut body = b"WEBP".to_vec();
+    for (id, payload) in &chunks {
+        body.extend_from_slice(id);
+        body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        body.extend_from_slice(payload);
+        if payload.len() & 1 == 1 {
+            body.push(0);
+        }
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+/// CRC-32 (IEEE) as used by PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// ============================================================================
+// TIFF and AVIF Embedding
+// ============================================================================
+
+/// TIFF tag holding an ICC profile.
+const TIFF_ICC_PROFILE: exif::Tag = exif::Tag(exif::Context::Tiff, 34675);
+/// TIFF tag holding an XMP packet.
+const TIFF_XML_PACKET: exif::Tag = exif::Tag(exif::Context::Tiff, 700);
+
+/// Tags describing how a TIFF stores its pixels. They belong to the image
+/// being written, never to the source's EXIF data.
+fn is_image_structure_tag(tag: exif::Tag) -> bool {
+    use exif::Tag;
+
+    matches!(
+        tag,
+        Tag::ImageWidth
+            | Tag::ImageLength
+            | Tag::BitsPerSample
+            | Tag::Compression
+            | Tag::PhotometricInterpretation
+            | Tag::StripOffsets
+            | Tag::SamplesPerPixel
+            | Tag::RowsPerStrip
+            | Tag::StripByteCounts
+            | Tag::PlanarConfiguration
+            | Tag::TileOffsets
+            | Tag::TileByteCounts
+            | Tag::JPEGInterchangeFormat
+            | Tag::JPEGInterchangeFormatLength
+    )
+}
+
+/// Rewrites a single-image, strip-based TIFF (as the `image` encoder writes
+/// it) with the EXIF fields, ICC profile and XMP packet added to its IFD.
+fn insert_tiff_metadata(
+    data: &[u8],
+    icc: Option<&[u8]>,
+    exif: Option<&[u8]>,
+    xmp: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    use exif::experimental::Writer;
+    use exif::{Field, In, Tag, Value};
+
+    let image = exif::Reader::new().read_raw(data.to_vec()).ok()?;
+    let offsets = image.get_field(Tag::StripOffsets, In::PRIMARY)?.value.iter_uint()?;
+    let counts = image.get_field(Tag::StripByteCounts, In::PRIMARY)?.value.iter_uint()?;
+    let strips: Vec<&[u8]> = offsets
+        .zip(counts)
+        .map(|(offset, count)| data.get(offset as usize..offset as usize + count as usize))
+        .collect::<Option<_>>()?;
+
+    let image_fields: Vec<&Field> = image.fields().filter(|f| f.ifd_num == In::PRIMARY).collect();
+    let source = exif.and_then(|exif| exif::Reader::new().read_raw(exif.to_vec()).ok());
+    let source_fields: Vec<&Field> = source
+        .iter()
+        .flat_map(|source| source.fields())
+        .filter(|f| f.ifd_num == In::PRIMARY)
+        .filter(|f| !is_image_structure_tag(f.tag) && f.tag != TIFF_ICC_PROFILE && f.tag != TIFF_XML_PACKET)
+        .filter(|f| !image_fields.iter().any(|i| i.tag == f.tag))
+        .collect();
+    if exif.is_some() && source_fields.is_empty() {
+        return None;
+    }
+
+    let icc = icc.map(|icc| Field {
+        tag: TIFF_ICC_PROFILE,
+        ifd_num: In::PRIMARY,
+        value: Value::Undefined(icc.to_vec(), 0),
+    });
+    let xmp = xmp.map(|xmp| Field {
+        tag: TIFF_XML_PACKET,
+        ifd_num: In::PRIMARY,
+        value: Value::Byte(xmp.to_vec()),
+    });
+
+    let mut writer = Writer::new();
+    for field in image_fields.into_iter().chain(source_fields).chain(&icc).chain(&xmp) {
+        writer.push_field(field);
+    }
+    writer.set_strips(&strips, In::PRIMARY);
+
+    let mut buf = std::io::Cursor::new(Vec::new());
+    writer.write(&mut buf, image.little_endian()).ok()?;
+    Some(buf.into_inner())
+}
+
+/// Position of a box within an ISOBMFF (HEIF/AVIF) file.
+struct IsoBox {
+    kind: [u8; 4],
+    start: usize,
+    /// Start of the payload, after the size and type header.
+    body: usize,
+    end: usize,
+}
+
+impl IsoBox {
+    fn payload<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.body..self.end]
+    }
+}
+
+/// Splits `data[start..end]` into consecutive boxes.
+fn iso_boxes(data: &[u8], start: usize, end: usize) -> Option<Vec<IsoBox>> {
+    let mut boxes = Vec::new();
+    let mut pos = start;
+    while pos < end {
+        let mut reader = ByteReader::new(data.get(pos..end)?);
+        let size = reader.uint(4)?;
+        let kind: [u8; 4] = reader.bytes(4)?.try_into().ok()?;
+        let size = match size {
+            0 => (end - pos) as u64,
+            1 => reader.uint(8)?,
+            size => size,
+        };
+        let body = pos + reader.pos;
+        let box_end = pos.checked_add(usize::try_from(size).ok()?)?;
+        if box_end > end || box_end < body {
+            return None;
+        }
+        boxes.push(IsoBox { kind, start: pos, body, end: box_end });
+        pos = box_end;
+    }
+    Some(boxes)
+}
+
+fn push_box(out: &mut Vec<u8>, kind: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+}
+
+/// Appends the low `size` bytes of `value`, big-endian.
+fn push_uint(out: &mut Vec<u8>, value: u64, size: usize) {
+    out.extend_from_slice(&value.to_be_bytes()[8 - size..]);
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Reads a big-endian unsigned integer of `size` (0 to 8) bytes.
+    fn uint(&mut self, size: usize) -> Option<u64> {
+        Some(self.bytes(size)?.iter().fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+}
+
+/// The item location (`iloc`) box: where each item's data lives.
+struct ItemLocations {
+    version: u8,
+    flags: [u8; 3],
+    offset_size: usize,
+    length_size: usize,
+    base_offset_size: usize,
+    index_size: usize,
+    items: Vec<ItemLocation>,
+}
+
+struct ItemLocation {
+    id: u32,
+    /// Reserved bits and the construction method (0 = file offsets).
+    method: u16,
+    data_reference: u16,
+    base_offset: u64,
+    /// (index, offset, length) of each extent.
+    extents: Vec<(u64, u64, u64)>,
+}
+
+impl ItemLocations {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let mut r = ByteReader::new(payload);
+        let version = r.uint(1)? as u8;
+        let flags = r.bytes(3)?.try_into().ok()?;
+        let sizes = r.uint(1)? as usize;
+        let more_sizes = r.uint(1)? as usize;
+        let mut locations = Self {
+            version,
+            flags,
+            offset_size: sizes >> 4,
+            length_size: sizes & 0xF,
+            base_offset_size: more_sizes >> 4,
+            index_size: if version > 0 { more_sizes & 0xF } else { 0 },
+            items: Vec::new(),
+        };
+
+        let count = r.uint(if version < 2 { 2 } else { 4 })?;
+        for _ in 0..count {
+            let id = r.uint(if version < 2 { 2 } else { 4 })? as u32;
+            let method = if version > 0 { r.uint(2)? as u16 } else { 0 };
+            let data_reference = r.uint(2)? as u16;
+            let base_offset = r.uint(locations.base_offset_size)?;
+            let extent_count = r.uint(2)?;
+            let mut extents = Vec::new();
+            for _ in 0..extent_count {
+                let index = r.uint(locations.index_size)?;
+                let offset = r.uint(locations.offset_size)?;
+                let length = r.uint(locations.length_size)?;
+                extents.push((index, offset, length));
+            }
+            locations.items.push(ItemLocation { id, method, data_reference, base_offset, extents });
+        }
+        Some(locations)
+    }
+
+    fn write(&self) -> Vec<u8> {
+        let id_size = if self.version < 2 { 2 } else { 4 };
+        let mut out = vec![self.version];
+        out.extend_from_slice(&self.flags);
+        out.push((self.offset_size << 4 | self.length_size) as u8);
+        out.push((self.base_offset_size << 4 | self.index_size) as u8);
+        push_uint(&mut out, self.items.len() as u64, id_size);
+        for item in &self.items {
+            push_uint(&mut out, item.id as u64, id_size);
+            if self.version > 0 {
+                push_uint(&mut out, item.method as u64, 2);
+            }
+            push_uint(&mut out, item.data_reference as u64, 2);
+            push_uint(&mut out, item.base_offset, self.base_offset_size);
+            push_uint(&mut out, item.extents.len() as u64, 2);
+            for &(index, offset, length) in &item.extents {
+                push_uint(&mut out, index, self.index_size);
+                push_uint(&mut out, offset, self.offset_size);
+                push_uint(&mut out, length, self.length_size);
+            }
+        }
+        out
+    }
+}
+
+/// Adds the ICC profile as a `colr` property of the primary image, and the
+/// EXIF and XMP as metadata items describing it, to an AVIF file. The new
+/// items' data goes in an extra `mdat` box at the end of the file.
+fn insert_avif_metadata(
+    data: &[u8],
+    icc: Option<&[u8]>,
+    exif: Option<&[u8]>,
+    xmp: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let top = iso_boxes(data, 0, data.len())?;
+    let meta = top.iter().find(|b| &b.kind == b"meta")?;
+    // meta is a full box: version and flags come before its children
+    let children = iso_boxes(data, meta.body + 4, meta.end)?;
+    let child = |kind: &[u8; 4]| children.iter().find(|b| &b.kind == kind);
+
+    let pitm = child(b"pitm")?.payload(data);
+    let primary = ByteReader::new(pitm.get(4..)?).uint(if pitm[0] == 0 { 2 } else { 4 })? as u32;
+
+    // Item info: existing entries are kept as-is, new ones get the next free ids
+    let iinf = child(b"iinf")?;
+    let iinf_payload = iinf.payload(data);
+    let count_size = if iinf_payload[0] == 0 { 2 } else { 4 };
+    let infe_start = iinf.body + 4 + count_size;
+    let entries = iso_boxes(data, infe_start, iinf.end)?;
+    let mut next_id = 1;
+    for entry in &entries {
+        let payload = entry.payload(data);
+        let id_size = if *payload.first()? < 3 { 2 } else { 4 };
+        next_id = next_id.max(ByteReader::new(payload.get(4..)?).uint(id_size)? as u32 + 1);
+    }
+
+    // (id, item type and name, item data)
+    let mut items: Vec<(u32, &[u8], Vec<u8>)> = Vec::new();
+    if let Some(exif) = exif {
+        // No bytes precede the TIFF header
+        let mut item = vec![0, 0, 0, 0];
+        item.extend_from_slice(exif);
+        items.push((next_id, b"Exif\0", item));
+    }
+    if let Some(xmp) = xmp {
+        // MIME items also carry their content type
+        let info = b"mime\0application/rdf+xml\0";
+        items.push((next_id + items.len() as u32, info, xmp.to_vec()));
+    }
+    if next_id as usize + items.len() > 0xFFFF {
+        return None;
+    }
+
+    let mut new_iinf = iinf_payload[..4].to_vec();
+    push_uint(&mut new_iinf, (entries.len() + items.len()) as u64, count_size);
+    new_iinf.extend_from_slice(&data[infe_start..iinf.end]);
+    for (id, info, _) in &items {
+        // infe version 2: id, protection index, then the type and name
+        let mut infe = vec![2, 0, 0, 0];
+        push_uint(&mut infe, *id as u64, 2);
+        push_uint(&mut infe, 0, 2);
+        infe.extend_from_slice(info);
+        push_box(&mut new_iinf, b"infe", &infe);
+    }
+
+    // Each new item describes ("cdsc") the primary image
+    let iref = child(b"iref");
+    let mut new_iref = iref.map_or(vec![0, 0, 0, 0], |iref| iref.payload(data).to_vec());
+    let iref_id_size = if new_iref[0] == 0 { 2 } else { 4 };
+    for (id, _, _) in &items {
+        let mut reference = Vec::new();
+        push_uint(&mut reference, *id as u64, iref_id_size);
+        push_uint(&mut reference, 1, 2);
+        push_uint(&mut reference, primary as u64, iref_id_size);
+        push_box(&mut new_iref, b"cdsc", &reference);
+    }
+
+    // Item properties: the ICC profile becomes a colr box linked to the primary image
+    let iprp = child(b"iprp")?;
+    let mut new_iprp = Vec::new();
+    let mut linked = icc.is_none();
+    let mut property_count = 0;
+    for prop in iso_boxes(data, iprp.body, iprp.end)? {
+        match &prop.kind {
+            b"ipco" => {
+                let mut ipco = prop.payload(data).to_vec();
+                property_count = iso_boxes(data, prop.body, prop.end)?.len();
+                if let Some(icc) = icc {
+                    let mut colr = b"prof".to_vec();
+                    colr.extend_from_slice(icc);
+                    push_box(&mut ipco, b"colr", &colr);
+                }
+                push_box(&mut new_iprp, b"ipco", &ipco);
+            }
+            b"ipma" if !linked => {
+                let ipma = associate_property(prop.payload(data), primary, property_count + 1)?;
+                push_box(&mut new_iprp, b"ipma", &ipma);
+                linked = true;
+            }
+            kind => push_box(&mut new_iprp, kind, prop.payload(data)),
+        }
+    }
+    if !linked {
+        return None;
+    }
+
+    let mut locations = ItemLocations::parse(child(b"iloc")?.payload(data))?;
+    // New offsets point past the end of the file, which the field sizes must allow for
+    let added = items.iter().map(|i| i.2.len()).sum::<usize>() + icc.map_or(0, <[u8]>::len);
+    let min_size = if (data.len() + added) as u64 + 0x10000 > u32::MAX as u64 { 8 } else { 4 };
+    locations.offset_size = locations.offset_size.max(min_size);
+    locations.length_size = locations.length_size.max(min_size);
+    for (id, _, item) in &items {
+        let extents = vec![(0, 0, item.len() as u64)];
+        locations.items.push(ItemLocation { id: *id, method: 0, data_reference: 0, base_offset: 0, extents });
+    }
+
+    let build_meta = |locations: &ItemLocations| {
+        let mut payload = data[meta.body..meta.body + 4].to_vec();
+        for child in &children {
+            match &child.kind {
+                b"iinf" => push_box(&mut payload, b"iinf", &new_iinf),
+                b"iref" => push_box(&mut payload, b"iref", &new_iref),
+                b"iprp" => push_box(&mut payload, b"iprp", &new_iprp),
+                b"iloc" => push_box(&mut payload, b"iloc", &locations.write()),
+                _ => payload.extend_from_slice(&data[child.start..child.end]),
+            }
+        }
+        if iref.is_none() && !items.is_empty() {
+            push_box(&mut payload, b"iref", &new_iref);
+        }
+        let mut meta_box = Vec::new();
+        push_box(&mut meta_box, b"meta", &payload);
+        meta_box
+    };
+
+    // Offsets don't change the box sizes, so the layout is known after one pass
+    let grown = (build_meta(&locations).len() - (meta.end - meta.start)) as u64;
+    let existing = locations.items.len() - items.len();
+    for item in &mut locations.items[..existing] {
+        // Data stored in the file after the meta box moves by the size it grew
+        if item.method & 0xF == 0 && item.data_reference == 0 {
+            for extent in &mut item.extents {
+                if item.base_offset + extent.1 >= meta.end as u64 {
+                    extent.1 += grown;
+                }
+            }
+        }
+    }
+    let mut offset = data.len() as u64 + grown + 8;
+    for item in &mut locations.items[existing..] {
+        item.extents[0].1 = offset;
+        offset += item.extents[0].2;
+    }
+
+    let mut out = data[..meta.start].to_vec();
+    out.extend_from_slice(&build_meta(&locations));
+    out.extend_from_slice(&data[meta.end..]);
+    if !items.is_empty() {
+        let item_data: Vec<u8> = items.iter().flat_map(|i| i.2.iter().copied()).collect();
+        push_box(&mut out, b"mdat", &item_data);
+    }
+    Some(out)
+}
+
+/// Rewrites an item property association (`ipma`) payload with property
+/// `index` (1-based) added to `item`, widening the indices if needed.
+fn associate_property(payload: &[u8], item: u32, index: usize) -> Option<Vec<u8>> {
+    let mut r = ByteReader::new(payload);
+    let version = r.uint(1)? as u8;
+    let flags = r.uint(3)? as u32;
+    let id_size = if version < 1 { 2 } else { 4 };
+    let wide = flags & 1 == 1;
+
+    // (item id, [(essential, property index)])
+    let mut entries = Vec::new();
+    for _ in 0..r.uint(4)? {
+        let id = r.uint(id_size)? as u32;
+        let mut associations = Vec::new();
+        for _ in 0..r.uint(1)? {
+            let (value, essential_bit) = if wide {
+                (r.uint(2)?, 0x8000)
+            } else {
+                (r.uint(1)?, 0x80)
+            };
+            associations.push((value & essential_bit != 0, value & (essential_bit - 1)));
+        }
+        entries.push((id, associations));
+    }
+
+    let (_, associations) = entries.iter_mut().find(|(id, _)| *id == item)?;
+    associations.push((false, index as u64));
+    if index > 0x7FFF || associations.len() > 0xFF {
+        return None;
+    }
+
+    let wide = wide || index > 0x7F;
+    let mut out = vec![version];
+    push_uint(&mut out, (flags | wide as u32) as u64, 3);
+    push_uint(&mut out, entries.len() as u64, 4);
+    for (id, associations) in &entries {
+        push_uint(&mut out, *id as u64, id_size);
+        push_uint(&mut out, associations.len() as u64, 1);
+        for &(essential, index) in associations {
+            if wide {
+                push_uint(&mut out, (essential as u64) << 15 | index, 2);
+            } else {
+                push_uint(&mut out, (essential as u64) << 7 | index, 1);
+            }
+        }
+    }
+    Some(out)
+}
+
+// ============================================================================
+// Document Metadata
+// ============================================================================
+
+/// Title and author carried across document conversions.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl DocumentInfo {
+    /// Extracts title and author from a text-based document's source.
+    pub(crate) fn extract(content: &str, input_ext: &str) -> Self {
+        match input_ext {
+            "html" | "htm" => Self {
+                title: capture(content, r"(?is)<title[^>]*>(.*?)</title>"),
+                author: capture(
+                    content,
+                    r#"(?is)<meta\s+name\s*=\s*["']author["']\s+content\s*=\s*["']([^"']*)["']"#,
+                ),
+            },
+            "md" | "markdown" => {
+                let front_matter = content
+                    .strip_prefix("---")
+                    .and_then(|rest| rest.split_once("\n---"))
+                    .map(|(fm, _)| fm)
+                    .unwrap_or("");
+                Self {
+                    title: capture(front_matter, r#"(?m)^title:\s*["']?(.*?)["']?\s*$"#)
+                        .or_else(|| capture(content, r"(?m)^#\s+(.+?)\s*#*\s*$")),
+                    author: capture(front_matter, r#"(?m)^author:\s*["']?(.*?)["']?\s*$"#),
+                }
+            }
+            "rtf" => Self {
+                title: capture(content, r"\{\\title\s+([^}]*)\}"),
+                author: capture(content, r"\{\\author\s+([^}]*)\}"),
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+fn capture(content: &str, pattern: &str) -> Option<String> {
+    let re = regex::Regex::new(pattern).ok()?;
+    re.captures(content)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().trim().to_string())
+        .filter(|s| !s.is_empty())
+}
@@ -0,0 +1,270 @@
+use crate::{
+    generate_output_path, jobs, pdf_render, pdf_render_dpi, pdf_to_image, remove_files,
+    save_pdf_page, scratch_name, AtomicOutput, ConversionError, ConversionOptions,
+    ConversionResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// ============================================================================
+// PDF Export Options
+// ============================================================================
+
+/// Which pages of a PDF are written when converting it to images.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfExportOptions {
+    /// Pages to export: `all`, or 1-based pages and ranges such as `1-3,7`
+    /// or `5-` (page 5 to the end). Each page goes to its own file named
+    /// `<name>_page<N>`. Unset converts the first page only.
+    pub pages: Option<String>,
+    /// Pack the exported pages into one `<name>.zip` instead of loose files.
+    /// Exports every page when `pages` is unset.
+    pub zip: bool,
+}
+
+impl PdfExportOptions {
+    /// Whether pages are exported to numbered files rather than the first
+    /// page to a single image.
+    pub(crate) fn exports_pages(&self) -> bool {
+        self.pages.is_some() || self.zip
+    }
+}
+
+// ============================================================================
+// Page Ranges
+// ============================================================================
+
+/// Number of pages in a PDF.
+pub(crate) fn page_count(path: &Path) -> Result<usize, ConversionError> {
+    lopdf::Document::load(path)
+        .map(|doc| doc.get_pages().len())
+        .map_err(|e| ConversionError::PdfError(e.to_string()))
+}
+
+/// Parses `all` or a list of 1-based pages and ranges (`1-3,7`, `5-`) into
+/// zero-based page indices, in the order given. A page named more than once
+/// is kept at its first mention.
+pub(crate) fn parse_page_range(
+    spec: &str,
+    page_count: usize,
+) -> Result<Vec<usize>, ConversionError> {
+    let invalid = |reason: String| {
+        ConversionError::PdfError(format!("Invalid page range '{}': {}", spec, reason))
+    };
+
+    let spec = spec.trim();
+    if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
+        return Ok((0..page_count).collect());
+    }
+
+    let page_number = |value: &str| {
+        let value = value.trim();
+        let page: usize = value
+            .parse()
+            .map_err(|_| invalid(format!("'{}' is not a page number", value)))?;
+        if page == 0 || page > page_count {
+            return Err(invalid(format!(
+                "page {} does not exist; the document has {} pages",
+                page, page_count
+            )));
+        }
+        Ok(page)
+    };
+
+    let mut pages = Vec::new();
+    let mut seen = HashSet::new();
+    for part in spec.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) if last.trim().is_empty() => (page_number(first)?, page_count),
+            Some((first, last)) => (page_number(first)?, page_number(last)?),
+            None => {
+                let page = page_number(part)?;
+                (page, page)
+            }
+        };
+        if first > last {
+            return Err(invalid(format!("{}-{} runs backwards", first, last)));
+        }
+        pages.extend((first - 1..last).filter(|&page| seen.insert(page)));
+    }
+
+    Ok(pages)
+}
+
+// ============================================================================
+// Export
+// ============================================================================
+
+/// Renders the pages picked by `options.pdf_export` to numbered image files
+/// next to the usual output, or into one ZIP of them. `output_path` is the
+/// first file (or the ZIP) and `output_paths` lists every file written.
+pub(crate) fn export_pages(
+    input_path: &Path,
+    options: &ConversionOptions,
+    original_size: u64,
+) -> ConversionResult {
+    match export(input_path, options) {
+        Ok(paths) => {
+            let new_size = paths
+                .iter()
+                .filter_map(|path| fs::metadata(path).ok())
+                .map(|meta| meta.len())
+                .sum();
+            let mut result = ConversionResult::success(paths[0].clone(), original_size, new_size);
+            result.output_paths = paths;
+            result
+        }
+        Err(e) => {
+            let e = if jobs::check_cancelled().is_err() {
+                ConversionError::Cancelled
+            } else {
+                e
+            };
+            ConversionResult::error(e.to_string())
+        }
+    }
+}
+
+fn export(input_path: &Path, options: &ConversionOptions) -> Result<Vec<String>, ConversionError> {
+    let export = &options.pdf_export;
+    let pages = parse_page_range(
+        export.pages.as_deref().unwrap_or("all"),
+        page_count(input_path)?,
+    )?;
+    if pages.is_empty() {
+        return Err(ConversionError::PdfError(
+            "The document has no pages".to_string(),
+        ));
+    }
+
+    if !export.zip {
+        return render_pages(
+            input_path,
+            &pages,
+            options.output_dir.as_deref(),
+            options.overwrite_existing,
+            options,
+        );
+    }
+
+    // Pages are rendered into a hidden directory beside the ZIP, then packed
+    let zip_path = generate_output_path(
+        input_path,
+        "zip",
+        options.output_dir.as_deref(),
+        options.overwrite_existing,
+    )?;
+    let stem = zip_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("pages");
+    let scratch_dir = zip_path.with_file_name(scratch_name(stem));
+    fs::create_dir(&scratch_dir).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+
+    let packed = render_pages(input_path, &pages, scratch_dir.to_str(), true, options)
+        .and_then(|files| write_zip(&files, &zip_path));
+    let _ = fs::remove_dir_all(&scratch_dir);
+
+    packed.map(|_| vec![zip_path.to_string_lossy().to_string()])
+}
+
+/// Renders each page to `<stem>_page<N>.<ext>` in `output_dir` (or beside
+/// the PDF). On failure the pages already written are removed.
+fn render_pages(
+    input_path: &Path,
+    pages: &[usize],
+    output_dir: Option<&str>,
+    overwrite: bool,
+    options: &ConversionOptions,
+) -> Result<Vec<String>, ConversionError> {
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or(ConversionError::InvalidPath)?;
+
+    let mut written = Vec::new();
+    let mut write_page = |page: usize,
+                          write: &dyn Fn(&Path) -> Result<(), ConversionError>|
+     -> Result<(), ConversionError> {
+        jobs::check_cancelled()?;
+        let path = generate_output_path(
+            &input_path.with_file_name(format!("{}_page{:03}.pdf", stem, page + 1)),
+            &options.output_format,
+            output_dir,
+            overwrite,
+        )?;
+        let output = AtomicOutput::new(&path);
+        write(output.path())?;
+        output.commit()?;
+        written.push(path.to_string_lossy().to_string());
+        Ok(())
+    };
+
+    // PDFium renders every page from a single load of the document; without
+    // it each page goes through the external tools on its own
+    let dpi = pdf_render_dpi(options.quality) as f32;
+    let rendered = pdf_render::render_pages(input_path, pages, dpi, |page, img| {
+        write_page(page, &|path| {
+            save_pdf_page(&img, path, &options.output_format, options.quality)
+        })
+    })
+    .and_then(|in_process| {
+        if in_process {
+            return Ok(());
+        }
+        pages.iter().try_for_each(|&page| {
+            write_page(page, &|path| {
+                pdf_to_image(
+                    input_path,
+                    path,
+                    &options.output_format,
+                    options.quality,
+                    Some(page),
+                )
+            })
+        })
+    });
+
+    match rendered {
+        Ok(()) => Ok(written),
+        Err(e) => {
+            remove_files(&written);
+            Err(e)
+        }
+    }
+}
+
+/// Packs the files into a ZIP at `zip_path`, each under its file name.
+fn write_zip(files: &[String], zip_path: &Path) -> Result<(), ConversionError> {
+    let write_error = |e: zip::result::ZipError| ConversionError::WriteError(e.to_string());
+
+    let output = AtomicOutput::new(zip_path);
+    let file =
+        File::create(output.path()).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let entry_options =
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for path in files {
+        jobs::check_cancelled()?;
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(ConversionError::InvalidPath)?;
+        zip.start_file(name, entry_options).map_err(write_error)?;
+        let mut page = File::open(path).map_err(|e| ConversionError::ReadError(e.to_string()))?;
+        io::copy(&mut page, &mut zip).map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    }
+
+    zip.finish()
+        .map_err(write_error)?
+        .into_inner()
+        .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    output.commit()
+}
@@ -0,0 +1,561 @@
+use crate::image_pdf::jpeg_components;
+use crate::pdf_security::check_not_encrypted;
+use crate::pdf_tools::inherited;
+use crate::{jobs, ConversionError, ConversionOptions};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+// ============================================================================
+// PDF Optimize Options
+// ============================================================================
+
+/// How a PDF is shrunk when converted to PDF.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfOptimizeOptions {
+    /// Images shown at a higher resolution than this are downsampled to it.
+    /// 0 keeps every image at its own resolution.
+    pub image_dpi: u32,
+}
+
+impl Default for PdfOptimizeOptions {
+    fn default() -> Self {
+        Self { image_dpi: 150 }
+    }
+}
+
+/// Images less than this far above the target resolution are left alone,
+/// since resampling them would lose quality for little gain.
+const DPI_TOLERANCE: f32 = 1.1;
+
+/// Form XObjects nested deeper than this are not searched for images.
+const MAX_FORM_DEPTH: usize = 8;
+
+const POINTS_PER_INCH: f32 = 72.0;
+
+fn pdf_error(e: impl std::fmt::Display) -> ConversionError {
+    ConversionError::PdfError(e.to_string())
+}
+
+/// Writes a smaller copy of the PDF: images shown above `image_dpi` are
+/// downsampled, duplicate fonts and streams are merged, unused objects are
+/// dropped and streams are recompressed. If that does not make the file
+/// smaller, the original is copied unchanged.
+pub(crate) fn optimize_pdf(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConversionOptions,
+) -> Result<(), ConversionError> {
+    let original = fs::read(input_path).map_err(|e| ConversionError::ReadError(e.to_string()))?;
+    let mut doc = Document::load_mem(&original).map_err(pdf_error)?;
+    check_not_encrypted(&doc)?;
+
+    let image_dpi = options.pdf_optimize.image_dpi;
+    if image_dpi > 0 {
+        downsample_images(&mut doc, image_dpi as f32, options.quality.clamp(1, 100))?;
+    }
+
+    jobs::check_cancelled()?;
+    merge_duplicates(&mut doc);
+    doc.prune_objects();
+    recompress_streams(&mut doc);
+    doc.renumber_objects();
+    doc.compress();
+
+    let mut optimized = Vec::new();
+    doc.save_to(&mut optimized)
+        .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+
+    let smallest = if optimized.len() < original.len() {
+        optimized
+    } else {
+        original
+    };
+    fs::write(output_path, smallest).map_err(|e| ConversionError::WriteError(e.to_string()))
+}
+
+// ============================================================================
+// Image Resolution
+// ============================================================================
+
+fn matrix(operands: &[Object]) -> Option<[f32; 6]> {
+    let values: Vec<f32> = operands.iter().filter_map(|o| o.as_float().ok()).collect();
+    values.try_into().ok()
+}
+
+/// `m` then `n`, for PDF's row-vector convention.
+fn multiply(m: &[f32; 6], n: &[f32; 6]) -> [f32; 6] {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+/// Finds the highest resolution each image XObject is drawn at, walking the
+/// content streams of every page and the forms they use.
+fn image_resolutions(doc: &Document) -> HashMap<ObjectId, f32> {
+    let mut resolutions = HashMap::new();
+    for page_id in doc.get_pages().into_values() {
+        let content = doc.get_page_content(page_id).unwrap_or_default();
+        let resources = inherited(doc, page_id, b"Resources")
+            .and_then(|r| doc.dereference(&r).ok().map(|(_, r)| r.clone()))
+            .and_then(|r| r.as_dict().ok().cloned())
+            .unwrap_or_default();
+        let identity = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        scan_content(doc, &content, &resources, identity, 0, &mut resolutions);
+    }
+    resolutions
+}
+
+fn scan_content(
+    doc: &Document,
+    content: &[u8],
+    resources: &Dictionary,
+    base: [f32; 6],
+    depth: usize,
+    resolutions: &mut HashMap<ObjectId, f32>,
+) {
+    let Ok(content) = Content::decode(content) else {
+        return;
+    };
+    let xobjects = resources
+        .get_deref(b"XObject", doc)
+        .and_then(Object::as_dict)
+        .ok();
+
+    let mut ctm = base;
+    let mut saved = Vec::new();
+    for op in &content.operations {
+        match op.operator.as_str() {
+            "q" => saved.push(ctm),
+            "Q" => ctm = saved.pop().unwrap_or(base),
+            "cm" => {
+                if let Some(m) = matrix(&op.operands) {
+                    ctm = multiply(&m, &ctm);
+                }
+            }
+            "Do" => {
+                let Some(id) = op
+                    .operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| xobjects?.get(name).ok())
+                    .and_then(|xobject| xobject.as_reference().ok())
+                else {
+                    continue;
+                };
+                let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+                    continue;
+                };
+
+                match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+                    Ok(b"Image") => {
+                        let size = |key: &[u8]| {
+                            stream
+                                .dict
+                                .get(key)
+                                .and_then(Object::as_float)
+                                .unwrap_or(0.0)
+                        };
+                        // The image fills the unit square, scaled to points by the CTM
+                        let shown_width = ctm[0].hypot(ctm[1]) / POINTS_PER_INCH;
+                        let shown_height = ctm[2].hypot(ctm[3]) / POINTS_PER_INCH;
+                        if shown_width <= 0.0 || shown_height <= 0.0 {
+                            continue;
+                        }
+                        let dpi =
+                            (size(b"Width") / shown_width).max(size(b"Height") / shown_height);
+                        let highest = resolutions.entry(id).or_insert(0.0);
+                        *highest = highest.max(dpi);
+                    }
+                    Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                        let form_matrix = stream
+                            .dict
+                            .get(b"Matrix")
+                            .and_then(Object::as_array)
+                            .ok()
+                            .and_then(|m| matrix(m))
+                            .unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+                        let form_resources = stream
+                            .dict
+                            .get_deref(b"Resources", doc)
+                            .and_then(Object::as_dict)
+                            .unwrap_or(resources);
+                        if let Ok(data) = stream.get_plain_content() {
+                            scan_content(
+                                doc,
+                                &data,
+                                form_resources,
+                                multiply(&form_matrix, &ctm),
+                                depth + 1,
+                                resolutions,
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ============================================================================
+// Image Downsampling
+// ============================================================================
+
+/// How an image stream's pixels are compressed.
+#[derive(Clone, Copy, PartialEq)]
+enum ImageCodec {
+    Jpeg,
+    Flate,
+}
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut plain = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut plain).ok()?;
+    Some(plain)
+}
+
+fn deflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).ok()?;
+    encoder.finish().ok()
+}
+
+/// Number of colour components of a grey or RGB colour space, or `None` for
+/// anything else (CMYK, indexed, separations), which is left alone.
+fn color_components(doc: &Document, color_space: &Object) -> Option<u8> {
+    let (_, color_space) = doc.dereference(color_space).ok()?;
+    match color_space {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"CalGray" => Some(1),
+            b"DeviceRGB" | b"CalRGB" => Some(3),
+            _ => None,
+        },
+        Object::Array(items) => {
+            let family = items.first()?.as_name().ok()?;
+            match family {
+                b"CalGray" => Some(1),
+                b"CalRGB" => Some(3),
+                b"ICCBased" => {
+                    let (_, profile) = doc.dereference(items.get(1)?).ok()?;
+                    match profile
+                        .as_stream()
+                        .ok()?
+                        .dict
+                        .get(b"N")
+                        .ok()?
+                        .as_i64()
+                        .ok()?
+                    {
+                        1 => Some(1),
+                        3 => Some(3),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Decodes an 8-bit grey or RGB image stored as JPEG or plain Flate.
+/// Images with predictors, decode arrays, masks or other colour spaces are
+/// not touched.
+fn decode_image(doc: &Document, stream: &Stream) -> Option<(DynamicImage, ImageCodec)> {
+    let dict = &stream.dict;
+    let unsupported = [b"DecodeParms".as_slice(), b"Decode", b"Mask"]
+        .iter()
+        .any(|key| dict.has(key))
+        || dict
+            .get(b"ImageMask")
+            .and_then(Object::as_bool)
+            .unwrap_or(false)
+        || dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8);
+    if unsupported {
+        return None;
+    }
+
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()? as u32;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()? as u32;
+    let components = color_components(doc, dict.get(b"ColorSpace").ok()?)?;
+    let filters = stream.filters().ok()?;
+
+    match filters.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["DCTDecode"] => {
+            if jpeg_components(&stream.content) != Some(components) {
+                return None;
+            }
+            let img =
+                image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).ok()?;
+            Some((img, ImageCodec::Jpeg))
+        }
+        ["FlateDecode"] => {
+            let pixels = inflate(&stream.content)?;
+            let img = if components == 1 {
+                DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, pixels)?)
+            } else {
+                DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, pixels)?)
+            };
+            Some((img, ImageCodec::Flate))
+        }
+        _ => None,
+    }
+}
+
+/// Resizes the image and encodes it as before: JPEG at `quality`, or Flate.
+fn encode_image(
+    img: &DynamicImage,
+    codec: ImageCodec,
+    width: u32,
+    height: u32,
+    quality: u8,
+) -> Option<Vec<u8>> {
+    let resized = img.resize_exact(width, height, FilterType::Lanczos3);
+    let resized = if img.color().channel_count() == 1 {
+        DynamicImage::ImageLuma8(resized.to_luma8())
+    } else {
+        DynamicImage::ImageRgb8(resized.to_rgb8())
+    };
+
+    match codec {
+        ImageCodec::Jpeg => {
+            let mut data = Vec::new();
+            resized
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))
+                .ok()?;
+            Some(data)
+        }
+        ImageCodec::Flate => deflate(resized.as_bytes()),
+    }
+}
+
+/// Replaces the image stream with a `width` × `height` copy, if it can be
+/// decoded and the copy is smaller.
+fn resample_stream(doc: &mut Document, id: ObjectId, width: u32, height: u32, quality: u8) {
+    let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+        return;
+    };
+    let Some((img, codec)) = decode_image(doc, stream) else {
+        return;
+    };
+    let Some(data) = encode_image(&img, codec, width, height, quality) else {
+        return;
+    };
+    if data.len() >= stream.content.len() {
+        return;
+    }
+
+    let mut resampled = stream.clone();
+    resampled.dict.set("Width", width as i64);
+    resampled.dict.set("Height", height as i64);
+    resampled.dict.set(
+        "Filter",
+        if codec == ImageCodec::Jpeg {
+            "DCTDecode"
+        } else {
+            "FlateDecode"
+        },
+    );
+    resampled.set_content(data);
+    doc.objects.insert(id, Object::Stream(resampled));
+}
+
+/// Downsamples every image drawn above `target_dpi` (at its sharpest use)
+/// to that resolution, along with its soft mask.
+fn downsample_images(
+    doc: &mut Document,
+    target_dpi: f32,
+    quality: u8,
+) -> Result<(), ConversionError> {
+    let mut resolutions: Vec<(ObjectId, f32)> = image_resolutions(doc).into_iter().collect();
+    resolutions.sort_by_key(|&(id, _)| id);
+
+    let mut resampled = HashSet::new();
+    for (id, dpi) in resolutions {
+        jobs::check_cancelled()?;
+        if dpi <= target_dpi * DPI_TOLERANCE {
+            continue;
+        }
+        let Ok(dict) = doc
+            .get_object(id)
+            .and_then(Object::as_stream)
+            .map(|s| &s.dict)
+        else {
+            continue;
+        };
+        let size = |key: &[u8]| dict.get(key).and_then(Object::as_i64).unwrap_or(0) as f32;
+        let scale = target_dpi / dpi;
+        let scaled = |pixels: f32| ((pixels * scale).round() as u32).max(1);
+        let soft_mask = dict
+            .get(b"SMask")
+            .and_then(Object::as_reference)
+            .ok()
+            .map(|mask| (mask, scaled(size(b"Width")), scaled(size(b"Height"))));
+
+        let mut targets = vec![(id, scaled(size(b"Width")), scaled(size(b"Height")))];
+        targets.extend(soft_mask);
+        for (id, width, height) in targets {
+            // An image shared between pages is resampled once, for its sharpest use
+            if resampled.insert(id) {
+                resample_stream(doc, id, width, height, quality);
+            }
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Duplicates and Compression
+// ============================================================================
+
+/// Appends a canonical encoding of the object, so that equal objects (with
+/// dictionary keys in any order) get equal keys.
+fn object_key(object: &Object, key: &mut Vec<u8>) {
+    match object {
+        Object::Null => key.push(b'n'),
+        Object::Boolean(b) => key.extend([b'b', *b as u8]),
+        Object::Integer(i) => {
+            key.push(b'i');
+            key.extend(i.to_le_bytes());
+        }
+        Object::Real(r) => {
+            key.push(b'r');
+            key.extend(r.to_bits().to_le_bytes());
+        }
+        Object::Name(name) => {
+            key.push(b'/');
+            key.extend((name.len() as u64).to_le_bytes());
+            key.extend(name);
+        }
+        Object::String(s, _) => {
+            key.push(b's');
+            key.extend((s.len() as u64).to_le_bytes());
+            key.extend(s);
+        }
+        Object::Array(items) => {
+            key.push(b'[');
+            items.iter().for_each(|item| object_key(item, key));
+            key.push(b']');
+        }
+        Object::Dictionary(dict) => dictionary_key(dict, key),
+        Object::Stream(stream) => {
+            key.push(b'S');
+            dictionary_key(&stream.dict, key);
+            key.extend((stream.content.len() as u64).to_le_bytes());
+            key.extend(&stream.content);
+        }
+        Object::Reference((number, generation)) => {
+            key.push(b'R');
+            key.extend(number.to_le_bytes());
+            key.extend(generation.to_le_bytes());
+        }
+    }
+}
+
+fn dictionary_key(dict: &Dictionary, key: &mut Vec<u8>) {
+    let mut entries: Vec<(&Vec<u8>, &Object)> = dict.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    key.push(b'<');
+    for (name, value) in entries {
+        object_key(&Object::Name(name.clone()), key);
+        object_key(value, key);
+    }
+    key.push(b'>');
+}
+
+/// Streams (embedded font files, images, repeated content) and font
+/// dictionaries are shared when identical. Pages and other structural
+/// dictionaries are not, since their identity matters.
+fn can_share(object: &Object) -> bool {
+    match object {
+        Object::Stream(stream) => !matches!(
+            stream.dict.get(b"Type").and_then(Object::as_name),
+            Ok(b"ObjStm" | b"XRef")
+        ),
+        Object::Dictionary(dict) => matches!(
+            dict.get(b"Type").and_then(Object::as_name),
+            Ok(b"Font" | b"FontDescriptor" | b"Encoding")
+        ),
+        _ => false,
+    }
+}
+
+/// Points every reference to a duplicate object at the first copy, so that
+/// fonts embedded once per merged document or per page are stored once.
+/// Repeats until nothing changes, since fonts only become identical once
+/// their font files have been merged.
+fn merge_duplicates(doc: &mut Document) {
+    loop {
+        let mut first_copy: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+        let mut replaced: HashMap<ObjectId, ObjectId> = HashMap::new();
+        for (&id, object) in &doc.objects {
+            if !can_share(object) {
+                continue;
+            }
+            let mut key = Vec::new();
+            object_key(object, &mut key);
+            match first_copy.get(&key) {
+                Some(&first) => {
+                    replaced.insert(id, first);
+                }
+                None => {
+                    first_copy.insert(key, id);
+                }
+            }
+        }
+        if replaced.is_empty() {
+            return;
+        }
+
+        doc.traverse_objects(|object| {
+            if let Object::Reference(id) = object {
+                if let Some(&first) = replaced.get(id) {
+                    *id = first;
+                }
+            }
+        });
+        doc.objects.retain(|id, _| !replaced.contains_key(id));
+    }
+}
+
+/// Recompresses Flate streams at the highest level, keeping the result only
+/// when it is smaller. Uncompressed streams are left to `Document::compress`.
+fn recompress_streams(doc: &mut Document) {
+    for object in doc.objects.values_mut() {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        if !stream
+            .filters()
+            .is_ok_and(|filters| filters == ["FlateDecode"])
+        {
+            continue;
+        }
+
+        // Predictor parameters stay valid since the decoded bytes are unchanged
+        if let Some(data) = inflate(&stream.content).and_then(|plain| deflate(&plain)) {
+            if data.len() < stream.content.len() {
+                stream.dict.set("Filter", "FlateDecode");
+                stream.set_content(data);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::ConversionError;
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// ============================================================================
+// In-Process PDF Rendering
+// ============================================================================
+
+/// Folder holding the PDFium library bundled with the app, set at startup.
+static BUNDLED_LIBRARY_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Tells the renderer where the app bundle keeps its PDFium library. The
+/// desktop app calls this with its resource directory; `FILEFLIP_PDFIUM_DIR`
+/// still takes precedence, and a system-wide install is the last resort.
+pub fn set_pdfium_library_dir(dir: PathBuf) {
+    let _ = BUNDLED_LIBRARY_DIR.set(dir);
+}
+
+/// Renders one page (zero-based) of a PDF at `dpi` with PDFium, without any
+/// external tool. Returns `None` when this build has no `pdfium` feature or
+/// the PDFium library cannot be found, so callers can fall back to other
+/// renderers.
+pub(crate) fn render_page(
+    path: &Path,
+    page: usize,
+    dpi: f32,
+) -> Result<Option<DynamicImage>, ConversionError> {
+    let mut rendered = None;
+    render_pages(path, &[page], dpi, |_, img| {
+        rendered = Some(img);
+        Ok(())
+    })?;
+    Ok(rendered)
+}
+
+/// Renders the given pages (zero-based, in order) of a PDF at `dpi`, handing
+/// each to `each` as soon as it is drawn. PDFium is bound and the document
+/// loaded once for all of them. Returns `false` without rendering anything
+/// when PDFium is unavailable, like `render_page`.
+pub(crate) fn render_pages(
+    path: &Path,
+    pages: &[usize],
+    dpi: f32,
+    each: impl FnMut(usize, DynamicImage) -> Result<(), ConversionError>,
+) -> Result<bool, ConversionError> {
+    #[cfg(feature = "pdfium")]
+    {
+        pdfium::render_pages(path, pages, dpi, each)
+    }
+    #[cfg(not(feature = "pdfium"))]
+    {
+        let _ = (path, pages, dpi, each);
+        Ok(false)
+    }
+}
+
+#[cfg(feature = "pdfium")]
+mod pdfium {
+    use super::BUNDLED_LIBRARY_DIR;
+    use crate::ConversionError;
+    use image::DynamicImage;
+    use pdfium_render::prelude::*;
+    use std::path::{Path, PathBuf};
+
+    /// Environment variable naming a directory that holds the PDFium
+    /// library, checked before the bundled copy.
+    const PDFIUM_DIR_VAR: &str = "FILEFLIP_PDFIUM_DIR";
+
+    fn pdf_error(e: impl std::fmt::Display) -> ConversionError {
+        ConversionError::PdfError(format!("PDFium: {}", e))
+    }
+
+    /// Directories searched for the PDFium library, before the system's
+    /// library path.
+    fn library_dirs() -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = std::env::var_os(PDFIUM_DIR_VAR)
+            .map(PathBuf::from)
+            .into_iter()
+            .collect();
+        dirs.extend(BUNDLED_LIBRARY_DIR.get().cloned());
+        // The CLI has no bundle, but may have the library beside it
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            dirs.push(exe_dir);
+        }
+        dirs
+    }
+
+    fn bind() -> Option<Pdfium> {
+        library_dirs()
+            .iter()
+            .map(Pdfium::pdfium_platform_library_name_at_path)
+            .filter(|path| path.exists())
+            .find_map(|path| Pdfium::bind_to_library(path).ok())
+            .or_else(|| Pdfium::bind_to_system_library().ok())
+            .map(Pdfium::new)
+    }
+
+    pub(super) fn render_pages(
+        path: &Path,
+        pages: &[usize],
+        dpi: f32,
+        mut each: impl FnMut(usize, DynamicImage) -> Result<(), ConversionError>,
+    ) -> Result<bool, ConversionError> {
+        let Some(pdfium) = bind() else {
+            return Ok(false);
+        };
+
+        let document = pdfium.load_pdf_from_file(path, None).map_err(pdf_error)?;
+        let document_pages = document.pages();
+        let page_count = document_pages.len() as usize;
+        if let Some(&missing) = pages.iter().find(|&&page| page >= page_count) {
+            return Err(ConversionError::PdfError(format!(
+                "Page {} does not exist; the document has {} pages",
+                missing + 1,
+                page_count
+            )));
+        }
+
+        // PDF user space is 72 units per inch
+        let config = PdfRenderConfig::new()
+            .scale_page_by_factor(dpi / 72.0)
+            .render_form_data(true);
+        for &index in pages {
+            let page = document_pages
+                .get(index as PdfPageIndex)
+                .map_err(pdf_error)?;
+            let bitmap = page.render_with_config(&config).map_err(pdf_error)?;
+            each(index, bitmap.as_image())?;
+        }
+
+        Ok(true)
+    }
+}
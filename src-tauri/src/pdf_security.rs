@@ -0,0 +1,739 @@
+use crate::ConversionError;
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use lopdf::xref::XrefEntry;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, ObjectStream, StringFormat};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::path::Path;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+// ============================================================================
+// PDF Encryption Options
+// ============================================================================
+
+/// Password protection for PDF output. Nothing is encrypted unless a password
+/// is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfEncryptionOptions {
+    /// Needed to open the document. Without it the document opens without a
+    /// prompt, but the restrictions below still apply.
+    pub user_password: Option<String>,
+    /// Opens the document without the restrictions below. A random one is
+    /// used when only the user password is set.
+    pub owner_password: Option<String>,
+    pub allow_print: bool,
+    pub allow_modify: bool,
+    pub allow_copy: bool,
+    pub allow_annotate: bool,
+    pub allow_fill_forms: bool,
+    /// Inserting, deleting and rotating pages.
+    pub allow_assemble: bool,
+}
+
+impl Default for PdfEncryptionOptions {
+    fn default() -> Self {
+        Self {
+            user_password: None,
+            owner_password: None,
+            allow_print: true,
+            allow_modify: true,
+            allow_copy: true,
+            allow_annotate: true,
+            allow_fill_forms: true,
+            allow_assemble: true,
+        }
+    }
+}
+
+impl PdfEncryptionOptions {
+    pub(crate) fn is_enabled(&self) -> bool {
+        [&self.user_password, &self.owner_password]
+            .iter()
+            .any(|password| password.as_deref().is_some_and(|p| !p.is_empty()))
+    }
+
+    /// The `P` entry: bits 3-6 and 9-12 grant the permissions, the reserved
+    /// bits are set and content extraction for accessibility is always
+    /// allowed.
+    fn permissions(&self) -> i32 {
+        let mut permissions: u32 = 0xFFFF_F0C0 | 1 << 9;
+        let grants = [
+            (self.allow_print, 1 << 2 | 1 << 11),
+            (self.allow_modify, 1 << 3),
+            (self.allow_copy, 1 << 4),
+            (self.allow_annotate, 1 << 5),
+            (self.allow_fill_forms, 1 << 8),
+            (self.allow_assemble, 1 << 10),
+        ];
+        for (allowed, bits) in grants {
+            if allowed {
+                permissions |= bits;
+            }
+        }
+        permissions as i32
+    }
+}
+
+/// Passwords of the AES-256 handler are UTF-8, cut to this many bytes.
+const MAX_PASSWORD_LEN: usize = 127;
+
+/// Pads passwords of the RC4 and AES-128 handlers to 32 bytes.
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Stands in for `ObjStm` while loading, so lopdf keeps encrypted object
+/// streams (which it cannot parse) instead of dropping them.
+const ENCRYPTED_OBJECT_STREAM: &[u8] = b"FileflipEncryptedObjStm";
+
+fn pdf_error(e: impl std::fmt::Display) -> ConversionError {
+    ConversionError::PdfError(e.to_string())
+}
+
+/// Fails with a hint to decrypt first, since the strings and streams of an
+/// encrypted PDF cannot be read as they are.
+pub(crate) fn check_not_encrypted(doc: &Document) -> Result<(), ConversionError> {
+    if doc.trailer.has(b"Encrypt") {
+        return Err(ConversionError::PdfError(
+            "The PDF is password protected; decrypt it with its password first".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Ciphers
+// ============================================================================
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], ConversionError> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| ConversionError::PdfError(format!("No random data for encryption: {}", e)))?;
+    Ok(bytes)
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// AES-256-CBC with a random IV written in front, as used for every string
+/// and stream.
+fn aes_encrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    let iv = random_bytes::<16>()?;
+    let mut encrypted = iv.to_vec();
+    encrypted.extend(
+        Aes256CbcEnc::new_from_slices(key, &iv)
+            .map_err(pdf_error)?
+            .encrypt_padded_vec_mut::<Pkcs7>(data),
+    );
+    Ok(encrypted)
+}
+
+/// Reverses `aes_encrypt` with a 128 or 256-bit key.
+fn aes_decrypt(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < 16 {
+        return None;
+    }
+    let (iv, data) = data.split_at(16);
+    // Some writers encrypt an empty string as just the IV
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+    match key.len() {
+        16 => Aes128CbcDec::new_from_slices(key, iv)
+            .ok()?
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .ok(),
+        _ => Aes256CbcDec::new_from_slices(key, iv)
+            .ok()?
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .ok(),
+    }
+}
+
+/// Encrypts whole blocks with a zero IV, which is how the AES-256 handler
+/// wraps the file key and permissions.
+fn aes256_wrap(key: &[u8], data: &[u8]) -> Vec<u8> {
+    Aes256CbcEnc::new_from_slices(key, &[0; 16])
+        .expect("32-byte key")
+        .encrypt_padded_vec_mut::<NoPadding>(data)
+}
+
+fn aes256_unwrap(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    Aes256CbcDec::new_from_slices(key, &[0; 16])
+        .ok()?
+        .decrypt_padded_vec_mut::<NoPadding>(data.get(..32)?)
+        .ok()
+}
+
+/// The password hash of the AES-256 handler (ISO 32000-2 algorithm 2.B;
+/// revision 5 used plain SHA-256). `user_entry` is the 48-byte `U` entry when
+/// hashing the owner password and empty otherwise.
+fn password_hash(password: &[u8], salt: &[u8], user_entry: &[u8], revision: i64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(password);
+    hasher.update(salt);
+    hasher.update(user_entry);
+    let mut hash = hasher.finalize().to_vec();
+    if revision < 6 {
+        return hash;
+    }
+
+    let mut round = 0u32;
+    loop {
+        let block = [password, &hash, user_entry].concat().repeat(64);
+        let encrypted = Aes128CbcEnc::new_from_slices(&hash[..16], &hash[16..32])
+            .expect("16-byte key and IV")
+            .encrypt_padded_vec_mut::<NoPadding>(&block);
+        // The first 16 bytes taken as a number, modulo 3
+        let selector: u32 = encrypted[..16].iter().map(|&b| b as u32).sum();
+        hash = match selector % 3 {
+            0 => Sha256::digest(&encrypted).to_vec(),
+            1 => Sha384::digest(&encrypted).to_vec(),
+            _ => Sha512::digest(&encrypted).to_vec(),
+        };
+        round += 1;
+        let last = encrypted[encrypted.len() - 1] as u32;
+        if round >= 64 && last + 32 <= round {
+            break;
+        }
+    }
+    hash.truncate(32);
+    hash
+}
+
+fn padded_password(password: &[u8]) -> [u8; 32] {
+    let mut padded = PASSWORD_PADDING;
+    let len = password.len().min(32);
+    padded[..len].copy_from_slice(&password[..len]);
+    padded[len..].copy_from_slice(&PASSWORD_PADDING[..32 - len]);
+    padded
+}
+
+/// Applies `crypt` to every string and stream, except those the format leaves
+/// in the clear: the encryption dictionary, cross-reference streams and, when
+/// `include_metadata` is false, XMP metadata. `crypt` is told which object the
+/// data belongs to and whether it is a stream; `None` leaves the data as is.
+/// Changed strings are written in `format`.
+fn crypt_objects(
+    doc: &mut Document,
+    skip: Option<ObjectId>,
+    include_metadata: bool,
+    format: StringFormat,
+    crypt: &mut impl FnMut(ObjectId, bool, &[u8]) -> Option<Vec<u8>>,
+) {
+    for (&id, object) in doc.objects.iter_mut() {
+        if Some(id) == skip {
+            continue;
+        }
+        if let Object::Stream(stream) = object {
+            if stream.dict.type_is(b"XRef")
+                || (!include_metadata && stream.dict.type_is(b"Metadata"))
+            {
+                continue;
+            }
+            if let Some(content) = crypt(id, true, &stream.content) {
+                stream.set_content(content);
+            }
+        }
+        crypt_strings(object, format, &mut |data| crypt(id, false, data));
+    }
+}
+
+fn crypt_strings(
+    object: &mut Object,
+    format: StringFormat,
+    crypt: &mut impl FnMut(&[u8]) -> Option<Vec<u8>>,
+) {
+    match object {
+        Object::String(bytes, string_format) => {
+            if let Some(crypted) = crypt(bytes) {
+                *bytes = crypted;
+                *string_format = format;
+            }
+        }
+        Object::Array(items) => items
+            .iter_mut()
+            .for_each(|item| crypt_strings(item, format, crypt)),
+        Object::Dictionary(dict) => dict
+            .iter_mut()
+            .for_each(|(_, value)| crypt_strings(value, format, crypt)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, value)| crypt_strings(value, format, crypt)),
+        _ => {}
+    }
+}
+
+// ============================================================================
+// Encryption
+// ============================================================================
+
+/// Encrypts the PDF at `path` in place with AES-256 (revision 6 of the
+/// standard security handler) when `options` sets a password.
+pub(crate) fn protect(path: &Path, options: &PdfEncryptionOptions) -> Result<(), ConversionError> {
+    if !options.is_enabled() {
+        return Ok(());
+    }
+    let mut doc = Document::load(path).map_err(pdf_error)?;
+    encrypt(&mut doc, options)?;
+    doc.save(path)
+        .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    Ok(())
+}
+
+/// `U`/`O` and `UE`/`OE` for one password: its hash with a validation salt
+/// and a key salt, and the file key wrapped with a key derived from the
+/// password.
+fn password_entries(
+    password: &[u8],
+    file_key: &[u8],
+    user_entry: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), ConversionError> {
+    let salts = random_bytes::<16>()?;
+    let (validation_salt, key_salt) = salts.split_at(8);
+    let mut entry = password_hash(password, validation_salt, user_entry, 6);
+    entry.extend_from_slice(&salts);
+    let key = password_hash(password, key_salt, user_entry, 6);
+    Ok((entry, aes256_wrap(&key, file_key)))
+}
+
+fn encrypt(doc: &mut Document, options: &PdfEncryptionOptions) -> Result<(), ConversionError> {
+    check_not_encrypted(doc).map_err(|_| {
+        ConversionError::PdfError("The PDF is already password protected".to_string())
+    })?;
+    doc.prune_objects();
+
+    let password = |value: &Option<String>| {
+        value
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .map(|p| p.as_bytes()[..p.len().min(MAX_PASSWORD_LEN)].to_vec())
+    };
+    let user_password = password(&options.user_password).unwrap_or_default();
+    let owner_password = match password(&options.owner_password) {
+        Some(owner) => owner,
+        None => random_bytes::<32>()?.to_vec(),
+    };
+
+    let file_key = random_bytes::<32>()?;
+    let (user_entry, user_key) = password_entries(&user_password, &file_key, &[])?;
+    let (owner_entry, owner_key) = password_entries(&owner_password, &file_key, &user_entry)?;
+
+    let permissions = options.permissions();
+    let mut perms = [0u8; 16];
+    perms[..4].copy_from_slice(&permissions.to_le_bytes());
+    perms[4..8].fill(0xFF);
+    perms[8] = b'T';
+    perms[9..12].copy_from_slice(b"adb");
+    perms[12..].copy_from_slice(&random_bytes::<4>()?);
+
+    let mut failed = None;
+    crypt_objects(
+        doc,
+        None,
+        true,
+        StringFormat::Hexadecimal,
+        &mut |_, _, data| {
+            aes_encrypt(&file_key, data)
+                .map_err(|e| failed = Some(e))
+                .ok()
+        },
+    );
+    if let Some(e) = failed {
+        return Err(e);
+    }
+
+    let hex = |bytes: Vec<u8>| Object::String(bytes, StringFormat::Hexadecimal);
+    let encrypt_id = doc.add_object(dictionary! {
+        "Filter" => "Standard",
+        "V" => 5,
+        "R" => 6,
+        "Length" => 256,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! {
+                "Type" => "CryptFilter",
+                "CFM" => "AESV3",
+                "AuthEvent" => "DocOpen",
+                "Length" => 32,
+            },
+        },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+        "P" => permissions as i64,
+        "U" => hex(user_entry),
+        "O" => hex(owner_entry),
+        "UE" => hex(user_key),
+        "OE" => hex(owner_key),
+        "Perms" => hex(aes256_wrap(&file_key, &perms)),
+        "EncryptMetadata" => true,
+    });
+    doc.trailer.set("Encrypt", encrypt_id);
+    if !doc.trailer.has(b"ID") {
+        let id = random_bytes::<16>()?.to_vec();
+        doc.trailer.set("ID", vec![hex(id.clone()), hex(id)]);
+    }
+
+    // AES-256 arrived with PDF 2.0 and, before that, Adobe's extension level 8
+    // to PDF 1.7
+    if doc.version.starts_with("1.") {
+        doc.version = "1.7".to_string();
+        doc.catalog_mut().map_err(pdf_error)?.set(
+            "Extensions",
+            dictionary! {
+                "ADBE" => dictionary! { "BaseVersion" => "1.7", "ExtensionLevel" => 8 },
+            },
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Decryption
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CryptMethod {
+    Identity,
+    Rc4,
+    AesV2,
+    AesV3,
+}
+
+/// The standard security handler's encryption dictionary, revisions 2 to 6.
+struct SecurityHandler {
+    revision: i64,
+    /// File key length in bytes.
+    key_length: usize,
+    owner_entry: Vec<u8>,
+    user_entry: Vec<u8>,
+    owner_key: Vec<u8>,
+    user_key: Vec<u8>,
+    permissions: i32,
+    encrypt_metadata: bool,
+    string_method: CryptMethod,
+    stream_method: CryptMethod,
+}
+
+/// The method of the crypt filter named by `key` (`StmF` or `StrF`).
+fn crypt_method(dict: &Dictionary, key: &[u8]) -> CryptMethod {
+    let name = dict
+        .get(key)
+        .and_then(Object::as_name)
+        .unwrap_or(b"Identity");
+    if name == b"Identity" {
+        return CryptMethod::Identity;
+    }
+    let method = dict
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|filters| filters.get(name))
+        .and_then(Object::as_dict)
+        .and_then(|filter| filter.get(b"CFM"))
+        .and_then(Object::as_name)
+        .unwrap_or(b"None");
+    match method {
+        b"V2" => CryptMethod::Rc4,
+        b"AESV2" => CryptMethod::AesV2,
+        b"AESV3" => CryptMethod::AesV3,
+        _ => CryptMethod::Identity,
+    }
+}
+
+impl SecurityHandler {
+    fn new(dict: &Dictionary) -> Result<Self, ConversionError> {
+        let filter = dict.get(b"Filter").and_then(Object::as_name).unwrap_or(b"");
+        if filter != b"Standard" {
+            return Err(ConversionError::PdfError(format!(
+                "The PDF is protected with the '{}' security handler; only password protection can be removed",
+                String::from_utf8_lossy(filter)
+            )));
+        }
+
+        let int =
+            |key: &[u8], default: i64| dict.get(key).and_then(Object::as_i64).unwrap_or(default);
+        let bytes = |key: &[u8]| {
+            dict.get(key)
+                .and_then(Object::as_str)
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default()
+        };
+
+        let version = int(b"V", 0);
+        let (key_length, string_method, stream_method) = match version {
+            1 => (5, CryptMethod::Rc4, CryptMethod::Rc4),
+            2 => (
+                (int(b"Length", 40) / 8).clamp(5, 16) as usize,
+                CryptMethod::Rc4,
+                CryptMethod::Rc4,
+            ),
+            4 => (16, crypt_method(dict, b"StrF"), crypt_method(dict, b"StmF")),
+            5 => (32, crypt_method(dict, b"StrF"), crypt_method(dict, b"StmF")),
+            _ => {
+                return Err(ConversionError::PdfError(format!(
+                    "Unsupported PDF encryption (version {})",
+                    version
+                )))
+            }
+        };
+
+        Ok(Self {
+            revision: int(b"R", 2),
+            key_length,
+            owner_entry: bytes(b"O"),
+            user_entry: bytes(b"U"),
+            owner_key: bytes(b"OE"),
+            user_key: bytes(b"UE"),
+            permissions: int(b"P", 0) as i32,
+            encrypt_metadata: dict
+                .get(b"EncryptMetadata")
+                .and_then(Object::as_bool)
+                .unwrap_or(true),
+            string_method,
+            stream_method,
+        })
+    }
+
+    /// The file key, if `password` is the user or the owner password.
+    /// `document_id` is the first part of the trailer's `ID`.
+    fn file_key(&self, password: &[u8], document_id: &[u8]) -> Option<Vec<u8>> {
+        if self.revision >= 5 {
+            return self.aes256_file_key(password);
+        }
+        let key = self.md5_file_key(password, document_id);
+        if self.user_entry_matches(&key, document_id) {
+            return Some(key);
+        }
+        let user_password = self.recover_user_password(password);
+        let key = self.md5_file_key(&user_password, document_id);
+        self.user_entry_matches(&key, document_id).then_some(key)
+    }
+
+    fn aes256_file_key(&self, password: &[u8]) -> Option<Vec<u8>> {
+        let password = &password[..password.len().min(MAX_PASSWORD_LEN)];
+        let owner = self.owner_entry.get(..48)?;
+        let user = self.user_entry.get(..48)?;
+        let hash = |salt: &[u8], user_entry: &[u8]| {
+            password_hash(password, salt, user_entry, self.revision)
+        };
+
+        if hash(&owner[32..40], user) == owner[..32] {
+            aes256_unwrap(&hash(&owner[40..48], user), &self.owner_key)
+        } else if hash(&user[32..40], &[]) == user[..32] {
+            aes256_unwrap(&hash(&user[40..48], &[]), &self.user_key)
+        } else {
+            None
+        }
+    }
+
+    /// Algorithm 2: the RC4 and AES-128 file key for a user password.
+    fn md5_file_key(&self, password: &[u8], document_id: &[u8]) -> Vec<u8> {
+        let mut md5 = Md5::new();
+        md5.update(padded_password(password));
+        md5.update(self.owner_entry.get(..32).unwrap_or(&self.owner_entry));
+        md5.update(self.permissions.to_le_bytes());
+        md5.update(document_id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            md5.update([0xFF; 4]);
+        }
+        let mut key = md5.finalize().to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                key = Md5::digest(&key[..self.key_length]).to_vec();
+            }
+        }
+        key.truncate(self.key_length);
+        key
+    }
+
+    /// Algorithms 4 and 5: whether `key` reproduces the `U` entry.
+    fn user_entry_matches(&self, key: &[u8], document_id: &[u8]) -> bool {
+        if self.revision == 2 {
+            return self.user_entry.get(..32) == Some(&rc4(key, &PASSWORD_PADDING)[..]);
+        }
+        let mut md5 = Md5::new();
+        md5.update(PASSWORD_PADDING);
+        md5.update(document_id);
+        let mut hash = md5.finalize().to_vec();
+        for round in 0..20u8 {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ round).collect();
+            hash = rc4(&round_key, &hash);
+        }
+        self.user_entry.get(..16) == Some(&hash[..])
+    }
+
+    /// Algorithm 7: the (padded) user password hidden in `O`, taking
+    /// `password` as the owner password.
+    fn recover_user_password(&self, password: &[u8]) -> Vec<u8> {
+        let mut hash = Md5::digest(padded_password(password)).to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = Md5::digest(&hash).to_vec();
+            }
+        }
+        let key = &hash[..self.key_length];
+        let rounds = if self.revision == 2 { 1 } else { 20 };
+        let mut user_password = self
+            .owner_entry
+            .get(..32)
+            .unwrap_or(&self.owner_entry)
+            .to_vec();
+        for round in (0..rounds).rev() {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ round).collect();
+            user_password = rc4(&round_key, &user_password);
+        }
+        user_password
+    }
+
+    /// Decrypts one string or stream of object `id`; `None` when it is stored
+    /// in the clear or cannot be decrypted.
+    fn decrypt(&self, file_key: &[u8], id: ObjectId, stream: bool, data: &[u8]) -> Option<Vec<u8>> {
+        let method = if stream {
+            self.stream_method
+        } else {
+            self.string_method
+        };
+        // RC4 and AES-128 use a key per object
+        let object_key = |aes: bool| {
+            let mut md5 = Md5::new();
+            md5.update(file_key);
+            md5.update(&id.0.to_le_bytes()[..3]);
+            md5.update(&id.1.to_le_bytes()[..2]);
+            if aes {
+                md5.update(b"sAlT");
+            }
+            let hash = md5.finalize();
+            hash[..(file_key.len() + 5).min(16)].to_vec()
+        };
+        match method {
+            CryptMethod::Identity => None,
+            CryptMethod::Rc4 => Some(rc4(&object_key(false), data)),
+            CryptMethod::AesV2 => aes_decrypt(&object_key(true), data),
+            CryptMethod::AesV3 => aes_decrypt(file_key, data),
+        }
+    }
+}
+
+/// Load filter that renames object streams so they survive loading.
+fn keep_object_streams(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+    if let Object::Stream(stream) = object {
+        if stream.dict.type_is(b"ObjStm") {
+            stream
+                .dict
+                .set("Type", Object::Name(ENCRYPTED_OBJECT_STREAM.to_vec()));
+        }
+    }
+    Some((id, object.clone()))
+}
+
+/// Loads the objects out of the (now decrypted) object streams.
+fn unpack_object_streams(doc: &mut Document) {
+    let containers: Vec<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, object)| {
+            object
+                .as_stream()
+                .is_ok_and(|stream| stream.dict.type_is(ENCRYPTED_OBJECT_STREAM))
+        })
+        .map(|(&id, _)| id)
+        .collect();
+
+    for container_id in containers {
+        let Some(Object::Stream(mut stream)) = doc.objects.remove(&container_id) else {
+            continue;
+        };
+        stream.dict.set("Type", "ObjStm");
+        let Ok(container) = ObjectStream::new(&mut stream) else {
+            continue;
+        };
+        for (id, object) in container.objects {
+            // Only take what the cross-reference table places here, so an
+            // older copy never replaces a newer one
+            let current = matches!(
+                doc.reference_table.get(id.0),
+                Some(XrefEntry::Compressed { container, .. }) if *container == container_id.0
+            );
+            if current {
+                doc.objects.entry(id).or_insert(object);
+            }
+        }
+    }
+}
+
+/// Loads an encrypted PDF and decrypts it with `password`, which may be
+/// either its user or its owner password.
+pub(crate) fn decrypt(path: &Path, password: &str) -> Result<Document, ConversionError> {
+    decrypt_if_protected(path, password)?
+        .ok_or_else(|| ConversionError::PdfError("The PDF is not password protected".to_string()))
+}
+
+/// Like `decrypt`, but a PDF without password protection is `None` rather
+/// than an error.
+pub(crate) fn decrypt_if_protected(
+    path: &Path,
+    password: &str,
+) -> Result<Option<Document>, ConversionError> {
+    let mut doc = Document::load_filtered(path, keep_object_streams)
+        .map_err(|e| ConversionError::PdfError(format!("{}: {}", path.display(), e)))?;
+
+    let (encrypt_id, dict) = match doc.trailer.get(b"Encrypt") {
+        Ok(Object::Reference(id)) => (
+            Some(*id),
+            doc.get_dictionary(*id).map_err(pdf_error)?.clone(),
+        ),
+        Ok(Object::Dictionary(dict)) => (None, dict.clone()),
+        _ => return Ok(None),
+    };
+    let handler = SecurityHandler::new(&dict)?;
+    let document_id = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|id| id.first())
+        .and_then(|first| first.as_str().ok())
+        .unwrap_or_default()
+        .to_vec();
+    let file_key = handler
+        .file_key(password.as_bytes(), &document_id)
+        .ok_or_else(|| ConversionError::PdfError("The password is incorrect".to_string()))?;
+
+    crypt_objects(
+        &mut doc,
+        encrypt_id,
+        handler.encrypt_metadata,
+        StringFormat::Literal,
+        &mut |id, stream, data| handler.decrypt(&file_key, id, stream, data),
+    );
+    doc.trailer.remove(b"Encrypt");
+    if let Some(id) = encrypt_id {
+        doc.objects.remove(&id);
+    }
+    unpack_object_streams(&mut doc);
+    Ok(Some(doc))
+}
@@ -0,0 +1,1109 @@
+use crate::metadata::DocumentInfo;
+use crate::pdf_security::check_not_encrypted;
+use crate::pdf_tools::inherited;
+use crate::{html_escape, jobs, ConversionError};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::HashMap;
+use std::path::Path;
+
+// ============================================================================
+// Extracted Text
+// ============================================================================
+
+/// A paragraph or heading recovered from a page.
+pub(crate) struct TextBlock {
+    /// The lines as laid out on the page.
+    pub lines: Vec<String>,
+    /// Heading level from 1 to 3, judged by font size and weight.
+    pub heading: Option<u8>,
+}
+
+/// The text blocks of one page, in reading order.
+pub(crate) struct PageText {
+    pub blocks: Vec<TextBlock>,
+}
+
+/// Form XObjects nested deeper than this are skipped.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// A `TJ` adjustment wider than this (thousandths of an em) stands for a space.
+const TJ_SPACE: f32 = 200.0;
+
+/// Gaps between text, in multiples of the font size, that separate columns,
+/// stacked sections, and words on a line.
+const COLUMN_GAP: f32 = 2.0;
+const SECTION_GAP: f32 = 1.2;
+const WORD_GAP: f32 = 0.2;
+
+fn pdf_error(e: lopdf::Error) -> ConversionError {
+    ConversionError::PdfError(e.to_string())
+}
+
+/// Extracts the text of every page in reading order.
+pub(crate) fn extract(path: &Path) -> Result<Vec<PageText>, ConversionError> {
+    let doc = Document::load(path).map_err(pdf_error)?;
+    check_not_encrypted(&doc)?;
+
+    let mut pages = Vec::new();
+    for page_id in doc.get_pages().into_values() {
+        jobs::check_cancelled()?;
+        let spans = page_spans(&doc, page_id)?;
+        pages.push(
+            split_regions(spans)
+                .into_iter()
+                .flat_map(|region| paragraphs(group_lines(region)))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    if pages.iter().all(|lines| lines.is_empty()) {
+        return Err(ConversionError::DocumentError(
+            "The PDF has no extractable text; it may be a scanned image".to_string(),
+        ));
+    }
+
+    let body_size = body_font_size(&pages);
+    Ok(pages
+        .into_iter()
+        .map(|paragraphs| PageText {
+            blocks: paragraphs
+                .into_iter()
+                .map(|lines| TextBlock {
+                    heading: heading_level(&lines, body_size),
+                    lines: lines.into_iter().map(|line| line.text).collect(),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Title and author from the PDF's document information dictionary.
+pub(crate) fn document_info(path: &Path) -> DocumentInfo {
+    let Ok(doc) = Document::load(path) else {
+        return DocumentInfo::default();
+    };
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|info| doc.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .ok();
+    let field = |key: &[u8]| {
+        info.and_then(|info| info.get(key).ok())
+            .and_then(|value| lopdf::decode_text_string(value).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    DocumentInfo {
+        title: field(b"Title"),
+        author: field(b"Author"),
+    }
+}
+
+// ============================================================================
+// Fonts
+// ============================================================================
+
+/// What is needed to turn a font's character codes into text and advances.
+struct Font {
+    /// Type0 (composite) fonts use two-byte codes, simple fonts one byte.
+    two_byte: bool,
+    bold: bool,
+    to_unicode: HashMap<u32, String>,
+    /// Text for each byte of a simple font, from its encoding and Differences.
+    simple: Vec<String>,
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl Font {
+    fn load(doc: &Document, font: &Dictionary) -> Self {
+        let name = |key: &[u8]| {
+            font.get_deref(key, doc)
+                .and_then(Object::as_name_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        let base_font = name(b"BaseFont");
+        let two_byte = name(b"Subtype") == "Type0";
+        let lower = base_font.to_lowercase();
+        let bold = ["bold", "black", "heavy"].iter().any(|w| lower.contains(w));
+
+        let to_unicode = font
+            .get_deref(b"ToUnicode", doc)
+            .and_then(Object::as_stream)
+            .and_then(|stream| stream.get_plain_content())
+            .map(|data| parse_to_unicode(&data))
+            .unwrap_or_default();
+
+        let (widths, default_width) = if two_byte {
+            cid_widths(doc, font)
+        } else {
+            simple_widths(doc, font, &lower)
+        };
+
+        Self {
+            two_byte,
+            bold,
+            to_unicode,
+            simple: if two_byte {
+                Vec::new()
+            } else {
+                simple_encoding(doc, font)
+            },
+            widths,
+            default_width,
+        }
+    }
+
+    fn codes<'a>(&self, bytes: &'a [u8]) -> impl Iterator<Item = u32> + 'a {
+        let size = if self.two_byte { 2 } else { 1 };
+        bytes
+            .chunks(size)
+            .map(|chunk| chunk.iter().fold(0, |code, &b| (code << 8) | b as u32))
+    }
+
+    fn text(&self, code: u32) -> &str {
+        if let Some(text) = self.to_unicode.get(&code) {
+            return text;
+        }
+        self.simple.get(code as usize).map_or("", String::as_str)
+    }
+
+    /// Advance width in text space units (thousandths of an em).
+    fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+}
+
+fn number(object: &Object) -> Option<f32> {
+    object.as_float().ok()
+}
+
+/// Widths of a simple font from `FirstChar` and `Widths`. The standard 14
+/// fonts may have none: Courier is monospaced, the rest get an average.
+fn simple_widths(doc: &Document, font: &Dictionary, base_font: &str) -> (HashMap<u32, f32>, f32) {
+    let default_width = if base_font.contains("courier") {
+        600.0
+    } else {
+        500.0
+    };
+    let first = font
+        .get_deref(b"FirstChar", doc)
+        .ok()
+        .and_then(number)
+        .unwrap_or(0.0) as u32;
+    let widths = font
+        .get_deref(b"Widths", doc)
+        .and_then(Object::as_array)
+        .map(|widths| {
+            widths
+                .iter()
+                .enumerate()
+                .filter_map(|(i, w)| {
+                    let w = doc.dereference(w).ok().and_then(|(_, w)| number(w))?;
+                    Some((first + i as u32, w))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    (widths, default_width)
+}
+
+/// Widths of a composite font from its descendant's `W` array, which mixes
+/// `c [w1 w2 ...]` and `c_first c_last w` entries.
+fn cid_widths(doc: &Document, font: &Dictionary) -> (HashMap<u32, f32>, f32) {
+    let descendant = font
+        .get_deref(b"DescendantFonts", doc)
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|fonts| fonts.first())
+        .and_then(|font| doc.dereference(font).ok())
+        .and_then(|(_, font)| font.as_dict().ok());
+    let Some(descendant) = descendant else {
+        return (HashMap::new(), 1000.0);
+    };
+
+    let default_width = descendant
+        .get_deref(b"DW", doc)
+        .ok()
+        .and_then(number)
+        .unwrap_or(1000.0);
+    let entries: Vec<&Object> = descendant
+        .get_deref(b"W", doc)
+        .and_then(Object::as_array)
+        .map(|w| {
+            w.iter()
+                .filter_map(|o| doc.dereference(o).ok().map(|(_, o)| o))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut widths = HashMap::new();
+    let mut i = 0;
+    while i + 1 < entries.len() {
+        let Some(first) = number(entries[i]) else {
+            break;
+        };
+        let first = first as u32;
+        if let Ok(list) = entries[i + 1].as_array() {
+            for (offset, w) in list.iter().enumerate() {
+                if let Some(w) = number(w) {
+                    widths.insert(first + offset as u32, w);
+                }
+            }
+            i += 2;
+        } else if let (Some(last), Some(w)) = (
+            number(entries[i + 1]),
+            entries.get(i + 2).and_then(|o| number(o)),
+        ) {
+            for code in first..=(last as u32).min(first + 0xFFFF) {
+                widths.insert(code, w);
+            }
+            i += 3;
+        } else {
+            break;
+        }
+    }
+    (widths, default_width)
+}
+
+/// Text for each byte of a simple font: its base encoding (WinAnsi, which
+/// covers Standard and PDFDoc text well enough, or MacRoman) overridden by
+/// any `Differences`.
+fn simple_encoding(doc: &Document, font: &Dictionary) -> Vec<String> {
+    let encoding = font.get_deref(b"Encoding", doc).ok();
+    let (base, differences) = match encoding {
+        Some(Object::Dictionary(dict)) => (
+            dict.get_deref(b"BaseEncoding", doc)
+                .and_then(Object::as_name_str)
+                .ok(),
+            dict.get_deref(b"Differences", doc)
+                .and_then(Object::as_array)
+                .ok(),
+        ),
+        Some(Object::Name(name)) => (std::str::from_utf8(name).ok(), None),
+        _ => (None, None),
+    };
+
+    let charset = if base == Some("MacRomanEncoding") {
+        encoding_rs::MACINTOSH
+    } else {
+        encoding_rs::WINDOWS_1252
+    };
+    let mut table: Vec<String> = (0..=255u8)
+        .map(|b| {
+            let byte = [b];
+            let (text, _) = charset.decode_without_bom_handling(&byte);
+            text.chars().filter(|c| !c.is_control()).collect()
+        })
+        .collect();
+
+    // [code /name /name ... code /name ...]
+    let mut code = 0usize;
+    for item in differences.into_iter().flatten() {
+        match item {
+            Object::Integer(c) => code = *c as usize,
+            Object::Name(name) => {
+                if let (Some(slot), Some(text)) = (
+                    table.get_mut(code),
+                    std::str::from_utf8(name).ok().and_then(glyph_text),
+                ) {
+                    *slot = text;
+                }
+                code += 1;
+            }
+            _ => {}
+        }
+    }
+    table
+}
+
+/// Text for the common Adobe glyph names, and `uniXXXX` names.
+fn glyph_text(name: &str) -> Option<String> {
+    if let Some(hex) = name.strip_prefix("uni").filter(|hex| hex.len() == 4) {
+        return u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map(String::from);
+    }
+    if name.len() == 1 {
+        return Some(name.to_string());
+    }
+    let text = match name {
+        "space" | "nbspace" => " ",
+        "zero" => "0",
+        "one" => "1",
+        "two" => "2",
+        "three" => "3",
+        "four" => "4",
+        "five" => "5",
+        "six" => "6",
+        "seven" => "7",
+        "eight" => "8",
+        "nine" => "9",
+        "period" => ".",
+        "comma" => ",",
+        "colon" => ":",
+        "semicolon" => ";",
+        "hyphen" | "minus" => "-",
+        "endash" => "\u{2013}",
+        "emdash" => "\u{2014}",
+        "question" => "?",
+        "exclam" => "!",
+        "quotesingle" => "'",
+        "quotedbl" => "\"",
+        "quoteleft" => "\u{2018}",
+        "quoteright" => "\u{2019}",
+        "quotedblleft" => "\u{201C}",
+        "quotedblright" => "\u{201D}",
+        "parenleft" => "(",
+        "parenright" => ")",
+        "bracketleft" => "[",
+        "bracketright" => "]",
+        "slash" => "/",
+        "ampersand" => "&",
+        "percent" => "%",
+        "bullet" => "\u{2022}",
+        "ellipsis" => "\u{2026}",
+        "fi" => "fi",
+        "fl" => "fl",
+        "ff" => "ff",
+        "ffi" => "ffi",
+        "ffl" => "ffl",
+        _ => return None,
+    };
+    Some(text.to_string())
+}
+
+/// Parses the `bfchar` and `bfrange` sections of a ToUnicode CMap.
+fn parse_to_unicode(data: &[u8]) -> HashMap<u32, String> {
+    #[derive(PartialEq)]
+    enum Token {
+        Hex(Vec<u8>),
+        Open,
+        Close,
+        Word(String),
+    }
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'%' => {
+                while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'>' if data.get(i + 1) == Some(&b'>') => i += 2,
+            b'<' => {
+                let end = data[i..]
+                    .iter()
+                    .position(|&b| b == b'>')
+                    .map_or(data.len(), |p| i + p);
+                let digits: Vec<u8> = data[i + 1..end]
+                    .iter()
+                    .copied()
+                    .filter(u8::is_ascii_hexdigit)
+                    .collect();
+                let bytes = digits
+                    .chunks(2)
+                    .filter_map(|pair| {
+                        let pair = if pair.len() == 2 {
+                            pair.to_vec()
+                        } else {
+                            vec![pair[0], b'0']
+                        };
+                        u8::from_str_radix(std::str::from_utf8(&pair).ok()?, 16).ok()
+                    })
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len()
+                    && !data[i].is_ascii_whitespace()
+                    && !b"<>[]%".contains(&data[i])
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Word(
+                    String::from_utf8_lossy(&data[start..i]).to_string(),
+                ));
+            }
+        }
+    }
+
+    let code = |bytes: &[u8]| bytes.iter().fold(0u32, |code, &b| (code << 8) | b as u32);
+    let utf16 = |bytes: &[u8]| -> Vec<u16> {
+        bytes
+            .chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect()
+    };
+
+    let mut map = HashMap::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let section = match &tokens[i] {
+            Token::Word(word) if word == "beginbfchar" || word == "beginbfrange" => word.clone(),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        i += 1;
+
+        while i < tokens.len() {
+            match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+                (Token::Hex(src), Some(Token::Hex(dst)), _) if section == "beginbfchar" => {
+                    map.insert(code(src), String::from_utf16_lossy(&utf16(dst)));
+                    i += 2;
+                }
+                (Token::Hex(lo), Some(Token::Hex(hi)), Some(Token::Hex(dst))) => {
+                    let (lo, hi) = (code(lo), code(hi).min(code(lo) + 0xFFFF));
+                    let base = utf16(dst);
+                    for (offset, src) in (lo..=hi).enumerate() {
+                        let mut text = base.clone();
+                        if let Some(last) = text.last_mut() {
+                            *last = last.wrapping_add(offset as u16);
+                        }
+                        map.insert(src, String::from_utf16_lossy(&text));
+                    }
+                    i += 3;
+                }
+                (Token::Hex(lo), Some(Token::Hex(_)), Some(Token::Open)) => {
+                    let mut src = code(lo);
+                    i += 3;
+                    while let Some(Token::Hex(dst)) = tokens.get(i) {
+                        map.insert(src, String::from_utf16_lossy(&utf16(dst)));
+                        src += 1;
+                        i += 1;
+                    }
+                    if tokens.get(i) == Some(&Token::Close) {
+                        i += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+    map
+}
+
+// ============================================================================
+// Content Streams
+// ============================================================================
+
+/// A run of text placed on the page, in page space.
+#[derive(Clone)]
+struct Span {
+    /// Left end of the baseline.
+    x: f32,
+    y: f32,
+    width: f32,
+    size: f32,
+    bold: bool,
+    text: String,
+}
+
+impl Span {
+    fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    /// Rough glyph extents above and below the baseline.
+    fn top(&self) -> f32 {
+        self.y + 0.8 * self.size
+    }
+
+    fn bottom(&self) -> f32 {
+        self.y - 0.25 * self.size
+    }
+}
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `m` then `n`, for PDF's row-vector convention.
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+fn translate(x: f32, y: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+fn matrix(operands: &[Object]) -> Option<Matrix> {
+    let values: Vec<f32> = operands.iter().filter_map(number).collect();
+    values.try_into().ok()
+}
+
+/// Text state that survives `BT`/`ET` (and is saved by `q`/`Q`).
+#[derive(Clone)]
+struct TextState {
+    font: Option<Vec<u8>>,
+    size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            font: None,
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+struct Interpreter<'a> {
+    doc: &'a Document,
+    spans: Vec<Span>,
+}
+
+fn page_spans(doc: &Document, page_id: ObjectId) -> Result<Vec<Span>, ConversionError> {
+    let content = doc.get_page_content(page_id).map_err(pdf_error)?;
+    let resources = inherited(doc, page_id, b"Resources")
+        .and_then(|resources| doc.dereference(&resources).ok().map(|(_, r)| r.clone()))
+        .and_then(|resources| resources.as_dict().ok().cloned())
+        .unwrap_or_default();
+
+    let mut interpreter = Interpreter {
+        doc,
+        spans: Vec::new(),
+    };
+    interpreter.run(&content, &resources, IDENTITY, 0)?;
+    Ok(interpreter.spans)
+}
+
+impl Interpreter<'_> {
+    fn fonts(&self, resources: &Dictionary) -> HashMap<Vec<u8>, Font> {
+        let Ok(fonts) = resources
+            .get_deref(b"Font", self.doc)
+            .and_then(Object::as_dict)
+        else {
+            return HashMap::new();
+        };
+        fonts
+            .iter()
+            .filter_map(|(name, font)| {
+                let (_, font) = self.doc.dereference(font).ok()?;
+                Some((name.clone(), Font::load(self.doc, font.as_dict().ok()?)))
+            })
+            .collect()
+    }
+
+    fn run(
+        &mut self,
+        content: &[u8],
+        resources: &Dictionary,
+        base: Matrix,
+        depth: usize,
+    ) -> Result<(), ConversionError> {
+        let content = Content::decode(content).map_err(pdf_error)?;
+        let fonts = self.fonts(resources);
+
+        let mut ctm = base;
+        let mut state = TextState::default();
+        let mut saved = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+
+        for op in &content.operations {
+            let operands = &op.operands;
+            match op.operator.as_str() {
+                "q" => saved.push((ctm, state.clone())),
+                "Q" => {
+                    if let Some((m, s)) = saved.pop() {
+                        ctm = m;
+                        state = s;
+                    }
+                }
+                "cm" => {
+                    if let Some(m) = matrix(operands) {
+                        ctm = multiply(&m, &ctm);
+                    }
+                }
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                }
+                "Tf" => {
+                    state.font = operands
+                        .first()
+                        .and_then(|f| f.as_name().ok())
+                        .map(<[u8]>::to_vec);
+                    state.size = operands.get(1).and_then(number).unwrap_or(0.0);
+                }
+                "Tc" => state.char_spacing = operands.first().and_then(number).unwrap_or(0.0),
+                "Tw" => state.word_spacing = operands.first().and_then(number).unwrap_or(0.0),
+                "Tz" => {
+                    state.horizontal_scale =
+                        operands.first().and_then(number).unwrap_or(100.0) / 100.0
+                }
+                "TL" => state.leading = operands.first().and_then(number).unwrap_or(0.0),
+                "Ts" => state.rise = operands.first().and_then(number).unwrap_or(0.0),
+                "Td" | "TD" => {
+                    let x = operands.first().and_then(number).unwrap_or(0.0);
+                    let y = operands.get(1).and_then(number).unwrap_or(0.0);
+                    if op.operator == "TD" {
+                        state.leading = -y;
+                    }
+                    line_matrix = multiply(&translate(x, y), &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(m) = matrix(operands) {
+                        line_matrix = m;
+                        text_matrix = m;
+                    }
+                }
+                "T*" => {
+                    line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tj" | "'" | "\"" | "TJ" => {
+                    if op.operator == "'" || op.operator == "\"" {
+                        if op.operator == "\"" {
+                            state.word_spacing = operands.first().and_then(number).unwrap_or(0.0);
+                            state.char_spacing = operands.get(1).and_then(number).unwrap_or(0.0);
+                        }
+                        line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    let Some(font) = state.font.as_ref().and_then(|name| fonts.get(name)) else {
+                        continue;
+                    };
+                    let items: Vec<&Object> = match operands.last() {
+                        Some(Object::Array(items)) if op.operator == "TJ" => items.iter().collect(),
+                        Some(item) => vec![item],
+                        None => continue,
+                    };
+                    self.show_text(&items, font, &state, &mut text_matrix, &ctm);
+                }
+                "Do" if depth < MAX_FORM_DEPTH => {
+                    let Some(name) = operands.first().and_then(|n| n.as_name().ok()) else {
+                        continue;
+                    };
+                    let form = resources
+                        .get_deref(b"XObject", self.doc)
+                        .and_then(Object::as_dict)
+                        .and_then(|xobjects| xobjects.get_deref(name, self.doc))
+                        .and_then(Object::as_stream);
+                    let Ok(form) = form else {
+                        continue;
+                    };
+                    if form.dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Form") {
+                        continue;
+                    }
+                    let form_matrix = form
+                        .dict
+                        .get(b"Matrix")
+                        .and_then(Object::as_array)
+                        .ok()
+                        .and_then(|m| matrix(m))
+                        .unwrap_or(IDENTITY);
+                    let form_resources = form
+                        .dict
+                        .get_deref(b"Resources", self.doc)
+                        .and_then(Object::as_dict)
+                        .unwrap_or(resources)
+                        .clone();
+                    let data = form.get_plain_content().map_err(pdf_error)?;
+                    self.run(
+                        &data,
+                        &form_resources,
+                        multiply(&form_matrix, &ctm),
+                        depth + 1,
+                    )?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Places the strings of a `Tj` or `TJ` as one span and moves the text
+    /// matrix past them.
+    fn show_text(
+        &mut self,
+        items: &[&Object],
+        font: &Font,
+        state: &TextState,
+        text_matrix: &mut Matrix,
+        ctm: &Matrix,
+    ) {
+        let start = multiply(&multiply(&translate(0.0, state.rise), text_matrix), ctm);
+        let mut text = String::new();
+        let mut advance = 0.0;
+
+        for item in items {
+            match item {
+                Object::String(bytes, _) => {
+                    for code in font.codes(bytes) {
+                        text.push_str(font.text(code));
+                        let mut width = font.width(code) / 1000.0 * state.size + state.char_spacing;
+                        if code == 32 && !font.two_byte {
+                            width += state.word_spacing;
+                        }
+                        advance += width * state.horizontal_scale;
+                    }
+                }
+                other => {
+                    if let Some(adjust) = number(other) {
+                        advance -= adjust / 1000.0 * state.size * state.horizontal_scale;
+                        if adjust < -TJ_SPACE && !text.ends_with(' ') {
+                            text.push(' ');
+                        }
+                    }
+                }
+            }
+        }
+
+        *text_matrix = multiply(&translate(advance, 0.0), text_matrix);
+
+        let size = state.size * start[2].hypot(start[3]);
+        if text.trim().is_empty() || size <= 0.0 {
+            return;
+        }
+        self.spans.push(Span {
+            x: start[4],
+            y: start[5],
+            width: advance * start[0].hypot(start[1]),
+            size,
+            bold: font.bold,
+            text,
+        });
+    }
+}
+
+// ============================================================================
+// Reading Order
+// ============================================================================
+
+fn median(mut values: Vec<f32>) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f32::total_cmp);
+    values[values.len() / 2]
+}
+
+/// The middle of the widest gap between the intervals, if it is wider than
+/// `min_gap`.
+fn widest_gap(mut intervals: Vec<(f32, f32)>, min_gap: f32) -> Option<f32> {
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut end = intervals.first()?.1;
+    let mut best: Option<(f32, f32)> = None;
+    for &(start, stop) in &intervals[1..] {
+        let gap = start - end;
+        if gap > min_gap && best.is_none_or(|(widest, _)| gap > widest) {
+            best = Some((gap, (start + end) / 2.0));
+        }
+        end = end.max(stop);
+    }
+    best.map(|(_, middle)| middle)
+}
+
+/// Splits a page into regions in reading order by recursive XY cuts: a
+/// clear vertical band of whitespace separates columns (left first), and a
+/// wide horizontal one separates sections (top first). Regions that cannot
+/// be cut further are read line by line.
+fn split_regions(spans: Vec<Span>) -> Vec<Vec<Span>> {
+    let mut regions = Vec::new();
+    let mut pending = vec![spans];
+
+    while let Some(spans) = pending.pop() {
+        if spans.len() < 2 {
+            if !spans.is_empty() {
+                regions.push(spans);
+            }
+            continue;
+        }
+
+        let size = median(spans.iter().map(|s| s.size).collect());
+        let columns = widest_gap(
+            spans.iter().map(|s| (s.x, s.right())).collect(),
+            COLUMN_GAP * size,
+        );
+        let (first, second): (Vec<Span>, Vec<Span>) = if let Some(x) = columns {
+            spans.into_iter().partition(|s| s.x < x)
+        } else if let Some(y) = widest_gap(
+            spans.iter().map(|s| (-s.top(), -s.bottom())).collect(),
+            SECTION_GAP * size,
+        ) {
+            spans.into_iter().partition(|s| -s.top() < y)
+        } else {
+            regions.push(spans);
+            continue;
+        };
+
+        // Popped last-in first-out, so the first part goes on top
+        pending.push(second);
+        pending.push(first);
+    }
+
+    regions
+}
+
+/// A line of text in a region.
+struct Line {
+    y: f32,
+    size: f32,
+    bold: bool,
+    text: String,
+}
+
+/// Groups spans sharing a baseline into lines, top to bottom, joining them
+/// left to right with spaces where they are apart.
+fn group_lines(mut spans: Vec<Span>) -> Vec<Line> {
+    spans.sort_by(|a, b| b.y.total_cmp(&a.y));
+
+    let mut rows: Vec<Vec<Span>> = Vec::new();
+    for span in spans {
+        match rows.last_mut() {
+            Some(row) if (row[0].y - span.y).abs() <= 0.5 * row[0].size.min(span.size) => {
+                row.push(span)
+            }
+            _ => rows.push(vec![span]),
+        }
+    }
+
+    rows.into_iter()
+        .map(|mut row| {
+            row.sort_by(|a, b| a.x.total_cmp(&b.x));
+            let mut text = String::new();
+            let mut right: Option<f32> = None;
+            for span in &row {
+                let apart = right.is_some_and(|right| span.x - right > WORD_GAP * span.size);
+                if apart && !text.ends_with(' ') && !span.text.starts_with(' ') {
+                    text.push(' ');
+                }
+                text.push_str(&span.text);
+                right = Some(span.right());
+            }
+
+            let chars = |s: &Span| s.text.chars().count() as f32;
+            let total: f32 = row.iter().map(chars).sum();
+            Line {
+                y: row[0].y,
+                size: row.iter().map(|s| s.size * chars(s)).sum::<f32>() / total.max(1.0),
+                bold: row.iter().all(|s| s.bold),
+                text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            }
+        })
+        .filter(|line| !line.text.is_empty())
+        .collect()
+}
+
+/// Splits a region's lines into paragraphs at unusually wide line spacing
+/// and at changes of font size or weight.
+fn paragraphs(lines: Vec<Line>) -> Vec<Vec<Line>> {
+    let spacing: Vec<f32> = lines.windows(2).map(|pair| pair[0].y - pair[1].y).collect();
+    let size = median(lines.iter().map(|line| line.size).collect());
+    let usual = median(spacing.clone()).min(1.5 * size);
+
+    let mut paragraphs: Vec<Vec<Line>> = Vec::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        let breaks = match (paragraphs.last().and_then(|p| p.last()), i.checked_sub(1)) {
+            (Some(previous), Some(gap)) => {
+                spacing[gap] > 1.4 * usual
+                    || (line.size - previous.size).abs() > 0.1 * previous.size
+                    || line.bold != previous.bold
+            }
+            _ => true,
+        };
+        if breaks {
+            paragraphs.push(vec![line]);
+        } else if let Some(paragraph) = paragraphs.last_mut() {
+            paragraph.push(line);
+        }
+    }
+    paragraphs
+}
+
+/// The font size most of the text is set in.
+fn body_font_size(pages: &[Vec<Vec<Line>>]) -> f32 {
+    let mut counts: HashMap<i32, usize> = HashMap::new();
+    for line in pages.iter().flatten().flatten() {
+        *counts.entry((line.size * 2.0).round() as i32).or_default() += line.text.len();
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(size, count)| (count, -size))
+        .map_or(12.0, |(size, _)| size as f32 / 2.0)
+}
+
+/// Short paragraphs set larger than the body text are headings, level 1 to
+/// 3 by size. A short bold line at body size is a level 3 heading.
+fn heading_level(lines: &[Line], body_size: f32) -> Option<u8> {
+    let text_len: usize = lines.iter().map(|line| line.text.chars().count()).sum();
+    if lines.len() > 3 || text_len > 150 {
+        return None;
+    }
+
+    let ratio = lines[0].size / body_size;
+    let bold_line = lines.len() == 1
+        && lines[0].bold
+        && ratio > 0.95
+        && text_len <= 80
+        && !lines[0].text.ends_with(['.', ',', ';', ':']);
+    if ratio >= 1.6 {
+        Some(1)
+    } else if ratio >= 1.3 {
+        Some(2)
+    } else if ratio >= 1.12 || bold_line {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+// ============================================================================
+// Output
+// ============================================================================
+
+/// Joins a paragraph's lines into running text, rejoining words hyphenated
+/// across a line break.
+fn reflow(lines: &[String]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        let hyphenated = text.ends_with('-')
+            && text[..text.len() - 1].ends_with(char::is_alphabetic)
+            && line.starts_with(char::is_lowercase);
+        if hyphenated {
+            text.pop();
+        } else if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(line);
+    }
+    text
+}
+
+/// Plain text keeping the page's line breaks, a blank line between
+/// paragraphs and a form feed between pages.
+pub(crate) fn to_text(pages: &[PageText]) -> String {
+    pages
+        .iter()
+        .map(|page| {
+            let blocks: Vec<String> = page
+                .blocks
+                .iter()
+                .map(|block| block.lines.join("\n"))
+                .collect();
+            format!("{}\n", blocks.join("\n\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\x0C")
+}
+
+/// Escapes characters that Markdown would read as formatting.
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    // Line starts that would become headings, quotes, lists or rules
+    let starts_block = escaped.starts_with(['#', '>', '-', '+', '='])
+        || escaped.split_once(". ").is_some_and(|(number, _)| {
+            !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+        });
+    if starts_block {
+        if let Some(dot) = escaped
+            .find(". ")
+            .filter(|_| escaped.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            escaped.insert(dot, '\\');
+        } else {
+            escaped.insert(0, '\\');
+        }
+    }
+    escaped
+}
+
+/// Markdown with reflowed paragraphs, `#` headings and a rule between pages.
+pub(crate) fn to_markdown(pages: &[PageText]) -> String {
+    let pages: Vec<String> = pages
+        .iter()
+        .map(|page| {
+            page.blocks
+                .iter()
+                .map(|block| {
+                    let text = markdown_escape(&reflow(&block.lines));
+                    match block.heading {
+                        Some(level) => format!("{} {}", "#".repeat(level as usize), text),
+                        None => text,
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        })
+        .collect();
+    format!("{}\n", pages.join("\n\n---\n\n"))
+}
+
+/// HTML body content: one `<section class="page">` per page holding
+/// `<h1>`–`<h3>` headings and `<p>` paragraphs.
+pub(crate) fn to_html_body(pages: &[PageText]) -> String {
+    pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let blocks: Vec<String> = page
+                .blocks
+                .iter()
+                .map(|block| {
+                    let text = html_escape(&reflow(&block.lines));
+                    match block.heading {
+                        Some(level) => format!("<h{0}>{1}</h{0}>", level, text),
+                        None => format!("<p>{}</p>", text),
+                    }
+                })
+                .collect();
+            format!(
+                "<section class=\"page\" id=\"page-{}\">\n{}\n</section>",
+                index + 1,
+                blocks.join("\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,420 @@
+use crate::pdf_export::parse_page_range;
+use crate::pdf_security::{self, check_not_encrypted};
+use crate::{
+    generate_output_path, jobs, remove_files, AtomicOutput, ConversionError, ConversionResult,
+};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// PDF Tool Options
+// ============================================================================
+
+/// Where the PDF page tools write their results. Outputs are named after the
+/// (first) input with a suffix such as `_merged` or `_page001`, so the input
+/// is never overwritten.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfToolOptions {
+    /// Defaults to the input's directory.
+    pub output_dir: Option<String>,
+    pub overwrite_existing: bool,
+}
+
+/// Attributes a page can inherit from the page tree nodes above it.
+const INHERITED_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Page trees deeper than this are treated as broken (or cyclic).
+const MAX_TREE_DEPTH: usize = 64;
+
+fn pdf_error(e: lopdf::Error) -> ConversionError {
+    ConversionError::PdfError(e.to_string())
+}
+
+// ============================================================================
+// Page Tree
+// ============================================================================
+
+fn load(path: &Path) -> Result<Document, ConversionError> {
+    let doc = Document::load(path)
+        .map_err(|e| ConversionError::PdfError(format!("{}: {}", path.display(), e)))?;
+    check_not_encrypted(&doc)?;
+    Ok(doc)
+}
+
+/// Page object ids in page order.
+fn page_ids(doc: &Document) -> Vec<ObjectId> {
+    doc.get_pages().into_values().collect()
+}
+
+/// Looks up `key` on the page, then up its chain of parents.
+pub(crate) fn inherited(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    for _ in 0..MAX_TREE_DEPTH {
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+    }
+    None
+}
+
+/// A copy of the page's dictionary with its inherited attributes filled in.
+fn resolved_page(doc: &Document, page_id: ObjectId) -> Result<Dictionary, ConversionError> {
+    let mut page = doc.get_dictionary(page_id).map_err(pdf_error)?.clone();
+    for key in INHERITED_KEYS {
+        if let Some(value) = inherited(doc, page_id, key) {
+            page.set(key, value);
+        }
+    }
+    Ok(page)
+}
+
+/// Every object reachable from `from`, except pages and page tree nodes.
+fn referenced_objects<'a>(doc: &'a Document, from: Vec<&'a Object>) -> BTreeMap<ObjectId, Object> {
+    let mut found = BTreeMap::new();
+    let mut pending = from;
+    while let Some(object) = pending.pop() {
+        match object {
+            Object::Reference(id) => {
+                if found.contains_key(id) {
+                    continue;
+                }
+                let Ok(target) = doc.get_object(*id) else {
+                    continue;
+                };
+                let is_page = target
+                    .as_dict()
+                    .is_ok_and(|dict| matches!(dict.type_name(), Ok("Page" | "Pages")));
+                if !is_page {
+                    found.insert(*id, target.clone());
+                    pending.push(target);
+                }
+            }
+            Object::Array(items) => pending.extend(items),
+            Object::Dictionary(dict) => pending.extend(dict.iter().map(|(_, value)| value)),
+            Object::Stream(stream) => pending.extend(stream.dict.iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+    found
+}
+
+/// A new document holding one page of `doc` and only the objects it uses,
+/// plus the document info. Links from the page to other pages are left
+/// dangling, which readers treat as null.
+fn single_page(doc: &Document, page_id: ObjectId) -> Result<Document, ConversionError> {
+    let mut page = resolved_page(doc, page_id)?;
+    let info = doc.trailer.get(b"Info").ok();
+
+    let mut single = Document::with_version(doc.version.clone());
+    let from = page.iter().map(|(_, value)| value).chain(info).collect();
+    single.objects = referenced_objects(doc, from);
+    // Copied objects keep their ids, so new ones go above the source's
+    single.max_id = doc.max_id;
+
+    let pages_id = single.new_object_id();
+    page.set("Parent", pages_id);
+    single.objects.insert(page_id, Object::Dictionary(page));
+    single.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![Object::Reference(page_id)],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = single.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    single.trailer.set("Root", catalog_id);
+    if let Some(info) = info {
+        single.trailer.set("Info", info.clone());
+    }
+    Ok(single)
+}
+
+/// Replaces the document's pages with `pages`, in that order, as direct
+/// children of the root page tree node. Inherited attributes are copied onto
+/// each page first so nothing is lost when leaving its old parent. Pages left
+/// out become unreferenced and are dropped by `save`.
+fn set_pages(doc: &mut Document, pages: &[ObjectId]) -> Result<(), ConversionError> {
+    let root_id = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(pdf_error)?;
+
+    // Resolve everything while the old parents are still linked
+    let mut resolved = Vec::with_capacity(pages.len());
+    for &id in pages {
+        let mut page = resolved_page(doc, id)?;
+        page.set("Parent", root_id);
+        resolved.push((id, page));
+    }
+
+    let mut kids = Vec::with_capacity(resolved.len());
+    for (id, page) in resolved {
+        doc.objects.insert(id, Object::Dictionary(page));
+        kids.push(Object::Reference(id));
+    }
+
+    let root = doc.get_dictionary_mut(root_id).map_err(pdf_error)?;
+    root.set("Count", kids.len() as i64);
+    root.set("Kids", kids);
+    Ok(())
+}
+
+/// Drops unreferenced objects and writes the document atomically.
+fn save(mut doc: Document, output_path: &Path) -> Result<(), ConversionError> {
+    jobs::check_cancelled()?;
+    doc.prune_objects();
+    doc.renumber_objects();
+    doc.compress();
+
+    let output = AtomicOutput::new(output_path);
+    doc.save(output.path())
+        .map_err(|e| ConversionError::WriteError(e.to_string()))?;
+    output.commit()
+}
+
+/// `<input stem>_<suffix>.pdf` in the output directory.
+fn suffixed_output_path(
+    input_path: &Path,
+    suffix: &str,
+    options: &PdfToolOptions,
+) -> Result<PathBuf, ConversionError> {
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or(ConversionError::InvalidPath)?;
+    generate_output_path(
+        &input_path.with_file_name(format!("{}_{}.pdf", stem, suffix)),
+        "pdf",
+        options.output_dir.as_deref(),
+        options.overwrite_existing,
+    )
+}
+
+fn input_size(input_paths: &[&Path]) -> Result<u64, ConversionError> {
+    input_paths.iter().try_fold(0, |total, path| {
+        fs::metadata(path)
+            .map(|meta| total + meta.len())
+            .map_err(|e| ConversionError::ReadError(format!("{}: {}", path.display(), e)))
+    })
+}
+
+/// Runs a tool that writes one PDF and reports it as a conversion result.
+fn run_tool(
+    input_paths: &[&Path],
+    suffix: &str,
+    options: &PdfToolOptions,
+    build: impl FnOnce() -> Result<Document, ConversionError>,
+) -> ConversionResult {
+    let result = input_size(input_paths).and_then(|original_size| {
+        let output_path = suffixed_output_path(input_paths[0], suffix, options)?;
+        save(build()?, &output_path)?;
+        let new_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        Ok(ConversionResult::success(
+            output_path.to_string_lossy().to_string(),
+            original_size,
+            new_size,
+        ))
+    });
+    result.unwrap_or_else(|e| ConversionResult::error(e.to_string()))
+}
+
+// ============================================================================
+// Operations
+// ============================================================================
+
+/// Joins the PDFs, in the order given, into `<first>_merged.pdf`. Document
+/// outlines and form fields of the inputs after the first are not carried
+/// over.
+pub fn merge_pdfs(input_paths: &[String], options: &PdfToolOptions) -> ConversionResult {
+    if input_paths.is_empty() {
+        return ConversionResult::error("No PDFs to merge".to_string());
+    }
+    let paths: Vec<&Path> = input_paths.iter().map(Path::new).collect();
+
+    run_tool(&paths, "merged", options, || {
+        let mut merged = load(paths[0])?;
+        let mut pages = page_ids(&merged);
+
+        for path in &paths[1..] {
+            jobs::check_cancelled()?;
+            let mut doc = load(path)?;
+            // Move the other document's object ids above ours before joining
+            doc.renumber_objects_with(merged.max_id + 1);
+            merged.max_id = doc.max_id;
+            if doc.version > merged.version {
+                merged.version = doc.version.clone();
+            }
+            pages.extend(page_ids(&doc));
+            merged.objects.extend(doc.objects);
+        }
+
+        set_pages(&mut merged, &pages)?;
+        Ok(merged)
+    })
+}
+
+/// Writes every page to its own `<input>_page<N>.pdf`. `output_paths` lists
+/// the files in page order.
+pub fn split_pdf(input_path: &str, options: &PdfToolOptions) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    let result = input_size(&[input_path]).and_then(|original_size| {
+        let doc = load(input_path)?;
+        let pages = page_ids(&doc);
+        if pages.is_empty() {
+            return Err(ConversionError::PdfError(
+                "The document has no pages".to_string(),
+            ));
+        }
+
+        let mut written = Vec::new();
+        for (index, &page) in pages.iter().enumerate() {
+            let saved = suffixed_output_path(input_path, &format!("page{:03}", index + 1), options)
+                .and_then(|path| {
+                    save(single_page(&doc, page)?, &path)?;
+                    Ok(path)
+                });
+            match saved {
+                Ok(path) => written.push(path.to_string_lossy().to_string()),
+                Err(e) => {
+                    remove_files(&written);
+                    return Err(e);
+                }
+            }
+        }
+
+        let new_size = written
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        let mut result = ConversionResult::success(written[0].clone(), original_size, new_size);
+        result.output_paths = written;
+        Ok(result)
+    });
+    result.unwrap_or_else(|e| ConversionResult::error(e.to_string()))
+}
+
+/// Copies the pages in `pages` (1-based, such as `1-3,7`) into
+/// `<input>_extract.pdf`, in the order listed.
+pub fn extract_pdf_pages(
+    input_path: &str,
+    pages: &str,
+    options: &PdfToolOptions,
+) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    run_tool(&[input_path], "extract", options, || {
+        let mut doc = load(input_path)?;
+        let ids = page_ids(&doc);
+        let selected: Vec<ObjectId> = parse_page_range(pages, ids.len())?
+            .into_iter()
+            .map(|index| ids[index])
+            .collect();
+        set_pages(&mut doc, &selected)?;
+        Ok(doc)
+    })
+}
+
+/// Writes `<input>_edited.pdf` without the pages in `pages`.
+pub fn delete_pdf_pages(
+    input_path: &str,
+    pages: &str,
+    options: &PdfToolOptions,
+) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    run_tool(&[input_path], "edited", options, || {
+        let mut doc = load(input_path)?;
+        let ids = page_ids(&doc);
+        let deleted: HashSet<usize> = parse_page_range(pages, ids.len())?.into_iter().collect();
+        if deleted.len() == ids.len() {
+            return Err(ConversionError::PdfError(
+                "Cannot delete every page of a document".to_string(),
+            ));
+        }
+        let kept: Vec<ObjectId> = ids
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !deleted.contains(index))
+            .map(|(_, &id)| id)
+            .collect();
+        set_pages(&mut doc, &kept)?;
+        Ok(doc)
+    })
+}
+
+/// Writes `<input>_reordered.pdf` with the pages in the order given, such as
+/// `3,1-2`. Every page must be listed.
+pub fn reorder_pdf_pages(
+    input_path: &str,
+    order: &str,
+    options: &PdfToolOptions,
+) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    run_tool(&[input_path], "reordered", options, || {
+        let mut doc = load(input_path)?;
+        let ids = page_ids(&doc);
+        let order = parse_page_range(order, ids.len())?;
+        if order.len() != ids.len() {
+            return Err(ConversionError::PdfError(format!(
+                "The new order must list each of the {} pages once",
+                ids.len()
+            )));
+        }
+        let reordered: Vec<ObjectId> = order.into_iter().map(|index| ids[index]).collect();
+        set_pages(&mut doc, &reordered)?;
+        Ok(doc)
+    })
+}
+
+/// Turns the pages in `pages` (all pages when `None`) clockwise by `degrees`,
+/// a multiple of 90, into `<input>_rotated.pdf`.
+pub fn rotate_pdf_pages(
+    input_path: &str,
+    pages: Option<&str>,
+    degrees: i32,
+    options: &PdfToolOptions,
+) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    run_tool(&[input_path], "rotated", options, || {
+        if degrees % 90 != 0 {
+            return Err(ConversionError::PdfError(format!(
+                "Pages can only be rotated by multiples of 90 degrees, not {}",
+                degrees
+            )));
+        }
+
+        let mut doc = load(input_path)?;
+        let ids = page_ids(&doc);
+        for index in parse_page_range(pages.unwrap_or("all"), ids.len())? {
+            let id = ids[index];
+            let current = inherited(&doc, id, b"Rotate")
+                .and_then(|value| value.as_i64().ok())
+                .unwrap_or(0);
+            let rotation = (current + degrees as i64).rem_euclid(360);
+            doc.get_dictionary_mut(id)
+                .map_err(pdf_error)?
+                .set("Rotate", rotation);
+        }
+        Ok(doc)
+    })
+}
+
+/// Removes the password protection from a PDF into `<input>_decrypted.pdf`.
+/// `password` may be the user or the owner password; documents that open
+/// without a password only have an owner password, and `""` unlocks them.
+pub fn decrypt_pdf(input_path: &str, password: &str, options: &PdfToolOptions) -> ConversionResult {
+    let input_path = Path::new(input_path);
+    run_tool(&[input_path], "decrypted", options, || {
+        pdf_security::decrypt(input_path, password)
+    })
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+use std::sync::Arc;
+
+/// Name of the Tauri event carrying `ConversionProgress` payloads.
+pub const PROGRESS_EVENT: &str = "conversion-progress";
+
+// ============================================================================
+// Progress Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionProgress {
+    pub job_id: String,
+    /// Completion from 0 to 100.
+    pub percent: f64,
+    /// Estimated seconds remaining, if known.
+    pub eta_seconds: Option<f64>,
+    /// Encoding speed relative to playback (2.0 = twice real time), if known.
+    pub speed: Option<f64>,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(ConversionProgress) + Send + Sync>;
+
+/// Delivers progress updates for one job to a subscriber (a Tauri event
+/// emitter in the app, a terminal printer in the CLI).
+#[derive(Clone)]
+pub struct ProgressReporter {
+    job_id: String,
+    callback: ProgressCallback,
+}
+
+impl ProgressReporter {
+    pub fn new(job_id: impl Into<String>, callback: ProgressCallback) -> Self {
+        Self {
+            job_id: job_id.into(),
+            callback,
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    pub fn report(&self, percent: f64, eta_seconds: Option<f64>, speed: Option<f64>) {
+        (self.callback)(ConversionProgress {
+            job_id: self.job_id.clone(),
+            percent: percent.clamp(0.0, 100.0),
+            eta_seconds,
+            speed,
+        });
+    }
+}
+
+// ============================================================================
+// FFmpeg Progress Parsing
+// ============================================================================
+
+/// Accumulates the `key=value` lines written by `ffmpeg -progress`. FFmpeg
+/// writes one block per update, terminated by a `progress=continue` or
+/// `progress=end` line.
+#[derive(Debug, Default)]
+struct FfmpegProgressBlock {
+    out_time_secs: Option<f64>,
+    speed: Option<f64>,
+}
+
+impl FfmpegProgressBlock {
+    fn apply(&mut self, key: &str, value: &str) {
+        match key {
+            // Despite its name, out_time_ms is in microseconds, same as out_time_us
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    self.out_time_secs = Some(us.max(0) as f64 / 1_000_000.0);
+                }
+            }
+            "speed" => {
+                self.speed = value.trim().trim_end_matches('x').parse::<f64>().ok();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads FFmpeg `-progress` output from `reader` until EOF, reporting each
+/// update against the input's total `duration` in seconds.
+pub(crate) fn read_ffmpeg_progress<R: BufRead>(
+    reader: R,
+    duration: Option<f64>,
+    reporter: &ProgressReporter,
+) {
+    let mut block = FfmpegProgressBlock::default();
+
+    for line in reader.lines().map_while(Result::ok) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        if key != "progress" {
+            block.apply(key, value);
+            continue;
+        }
+
+        if value == "end" {
+            reporter.report(100.0, Some(0.0), block.speed);
+            break;
+        }
+
+        // Without a known duration there is nothing to measure progress against
+        let (Some(total), Some(done)) = (duration.filter(|d| *d > 0.0), block.out_time_secs) else {
+            continue;
+        };
+
+        let percent = (done / total * 100.0).min(99.9);
+        let eta = block
+            .speed
+            .filter(|s| *s > 0.0)
+            .map(|s| ((total - done).max(0.0)) / s);
+        reporter.report(percent, eta, block.speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::Mutex;
+
+    fn collect(output: &str, duration: Option<f64>) -> Vec<ConversionProgress> {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&updates);
+        let reporter = ProgressReporter::new(
+            "job-1",
+            Arc::new(move |progress| sink.lock().unwrap().push(progress)),
+        );
+        read_ffmpeg_progress(Cursor::new(output), duration, &reporter);
+        let updates = updates.lock().unwrap();
+        updates.clone()
+    }
+
+    const OUTPUT: &str = "frame=10\n\
+        out_time_ms=2500000\n\
+        speed=2.5x\n\
+        progress=continue\n\
+        out_time_us=5000000\n\
+        speed=   2x\n\
+        progress=continue\n\
+        out_time_ms=10000000\n\
+        speed=4x\n\
+        progress=end\n";
+
+    #[test]
+    fn reports_percent_eta_and_speed() {
+        let updates = collect(OUTPUT, Some(10.0));
+        assert_eq!(updates.len(), 3);
+        assert!(updates.iter().all(|u| u.job_id == "job-1"));
+
+        assert_eq!(updates[0].percent, 25.0);
+        assert_eq!(updates[0].speed, Some(2.5));
+        assert_eq!(updates[0].eta_seconds, Some(3.0));
+
+        assert_eq!(updates[1].percent, 50.0);
+        assert_eq!(updates[1].speed, Some(2.0));
+        assert_eq!(updates[1].eta_seconds, Some(2.5));
+
+        // Only the end marker reports completion
+        assert_eq!(updates[2].percent, 100.0);
+        assert_eq!(updates[2].eta_seconds, Some(0.0));
+        assert_eq!(updates[2].speed, Some(4.0));
+    }
+
+    #[test]
+    fn stays_below_100_until_the_end() {
+        let updates = collect("out_time_ms=12000000\nprogress=continue\n", Some(10.0));
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].percent, 99.9);
+        // No speed, no ETA
+        assert_eq!(updates[0].eta_seconds, None);
+    }
+
+    #[test]
+    fn without_duration_only_reports_the_end() {
+        let updates = collect(OUTPUT, None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].percent, 100.0);
+    }
+}
@@ -0,0 +1,733 @@
+//! Photoshop PSD/PSB reader: layer records, channel data and the saved
+//! composite, enough to flatten a document ourselves.
+
+use crate::layers::{apply_mask, BlendMode, ByteReader, Layer, LayeredImage};
+use crate::metadata::ImageMetadata;
+use crate::ConversionError;
+use flate2::read::ZlibDecoder;
+use image::metadata::Orientation;
+use image::{Rgba, RgbaImage};
+use std::io::Read;
+
+// Colour modes from the file header
+const MODE_BITMAP: u16 = 0;
+const MODE_GRAYSCALE: u16 = 1;
+const MODE_INDEXED: u16 = 2;
+const MODE_RGB: u16 = 3;
+const MODE_CMYK: u16 = 4;
+const MODE_DUOTONE: u16 = 8;
+
+// Image resource IDs
+const RESOURCE_ICC: u16 = 1039;
+const RESOURCE_EXIF: u16 = 1058;
+const RESOURCE_XMP: u16 = 1060;
+
+/// Channel IDs in layer records
+const CHANNEL_ALPHA: i16 = -1;
+const CHANNEL_USER_MASK: i16 = -2;
+
+struct Header {
+    width: u32,
+    height: u32,
+    channels: u16,
+    depth: u16,
+    mode: u16,
+    /// PSB ("large document") files widen several length fields to 64 bits.
+    large: bool,
+    palette: Vec<u8>,
+}
+
+impl Header {
+    /// Number of channels holding colour, before any alpha or spot channels.
+    fn color_channels(&self) -> usize {
+        match self.mode {
+            MODE_RGB => 3,
+            MODE_CMYK => 4,
+            _ => 1,
+        }
+    }
+
+    fn length(&self, r: &mut ByteReader) -> Result<usize, ConversionError> {
+        if self.large {
+            Ok(r.u64()? as usize)
+        } else {
+            Ok(r.u32()? as usize)
+        }
+    }
+
+    /// Byte count of one PackBits-compressed row.
+    fn row_count(&self, r: &mut ByteReader) -> Result<usize, ConversionError> {
+        if self.large {
+            Ok(r.u32()? as usize)
+        } else {
+            Ok(r.u16()? as usize)
+        }
+    }
+}
+
+/// Parses a PSD or PSB file. Layer visibility, opacity, group visibility,
+/// layer masks and the separable blend modes are honoured; adjustment
+/// layers, layer effects and clipping masks are not.
+pub(crate) fn parse(data: &[u8]) -> Result<LayeredImage, ConversionError> {
+    let mut r = ByteReader::new(data, "PSD");
+    if r.bytes(4)? != b"8BPS" {
+        return Err(r.error("not a Photoshop file"));
+    }
+    let large = match r.u16()? {
+        1 => false,
+        2 => true,
+        v => return Err(r.error(format!("unknown version {}", v))),
+    };
+    r.skip(6)?;
+    let channels = r.u16()?;
+    let height = r.u32()?;
+    let width = r.u32()?;
+    let depth = r.u16()?;
+    let mode = r.u16()?;
+
+    if !matches!(
+        mode,
+        MODE_BITMAP | MODE_GRAYSCALE | MODE_INDEXED | MODE_RGB | MODE_CMYK | MODE_DUOTONE
+    ) {
+        return Err(ConversionError::UnsupportedFormat(format!(
+            "PSD colour mode {} (only RGB, CMYK, grayscale, indexed and bitmap are supported)",
+            mode
+        )));
+    }
+    if !matches!(depth, 1 | 8 | 16 | 32) {
+        return Err(r.error(format!("unsupported bit depth {}", depth)));
+    }
+
+    let color_data_len = r.u32()? as usize;
+    let palette = r.bytes(color_data_len)?.to_vec();
+    let header = Header {
+        width,
+        height,
+        channels,
+        depth,
+        mode,
+        large,
+        palette,
+    };
+
+    let resources_len = r.u32()? as usize;
+    let metadata = read_resources(r.bytes(resources_len)?);
+
+    let section_len = header.length(&mut r)?;
+    let section_end = r.pos_after(section_len)?;
+    let (layers, merged_alpha) = if section_len > 0 {
+        read_layer_section(&mut r, &header, section_end)?
+    } else {
+        (Vec::new(), false)
+    };
+    r.seek(section_end)?;
+
+    // Only needed without layers; a document with layers is flattened by us
+    let composite = if layers.is_empty() {
+        Some(read_composite(&mut r, &header, merged_alpha)?)
+    } else {
+        None
+    };
+
+    Ok(LayeredImage {
+        width,
+        height,
+        layers,
+        composite,
+        metadata,
+    })
+}
+
+/// Reads ICC, EXIF and XMP from the image resources section.
+fn read_resources(data: &[u8]) -> ImageMetadata {
+    let mut metadata = ImageMetadata {
+        // Photoshop stores pixels upright
+        orientation: Some(Orientation::NoTransforms),
+        ..Default::default()
+    };
+
+    let mut r = ByteReader::new(data, "PSD");
+    while let Ok(signature) = r.bytes(4) {
+        if signature != b"8BIM" {
+            break;
+        }
+        let Ok(id) = r.u16() else { break };
+        // Pascal string name, padded to an even length including the length byte
+        let Ok(name_len) = r.u8() else { break };
+        if r.skip(name_len as usize + (name_len as usize + 1) % 2)
+            .is_err()
+        {
+            break;
+        }
+        let Ok(size) = r.u32() else { break };
+        let Ok(payload) = r.bytes(size as usize) else {
+            break;
+        };
+        let _ = r.skip(size as usize % 2);
+
+        match id {
+            RESOURCE_ICC => metadata.icc = Some(payload.to_vec()),
+            RESOURCE_EXIF => {
+                let mut exif = payload.to_vec();
+                let _ = Orientation::remove_from_exif_chunk(&mut exif);
+                metadata.exif = Some(exif);
+            }
+            RESOURCE_XMP => metadata.xmp = Some(payload.to_vec()),
+            _ => {}
+        }
+    }
+
+    metadata
+}
+
+// ============================================================================
+// Layers
+// ============================================================================
+
+struct ChannelInfo {
+    id: i16,
+    length: usize,
+}
+
+struct LayerMask {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    default_color: u8,
+    disabled: bool,
+}
+
+struct LayerRecord {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    channels: Vec<ChannelInfo>,
+    blend: BlendMode,
+    opacity: u8,
+    hidden: bool,
+    name: String,
+    mask: Option<LayerMask>,
+    /// Section divider type from the `lsct` block: 1/2 open/closed group,
+    /// 3 the hidden marker below a group's layers.
+    section: u32,
+}
+
+/// Reads the layer and mask information section. 16- and 32-bit documents
+/// keep their layers in an `Lr16`/`Lr32` block after the (empty) layer info.
+fn read_layer_section(
+    r: &mut ByteReader,
+    header: &Header,
+    section_end: usize,
+) -> Result<(Vec<Layer>, bool), ConversionError> {
+    let info_len = header.length(r)?;
+    let info_end = r.pos_after(info_len)?;
+    if info_len > 0 {
+        let layers = read_layer_info(r, header)?;
+        r.seek(info_end)?;
+        return Ok(layers);
+    }
+    r.seek(info_end)?;
+
+    // Global layer mask info, then tagged blocks
+    let global_mask_len = r.u32()? as usize;
+    r.skip(global_mask_len)?;
+    while r.pos() + 12 <= section_end {
+        let signature = r.bytes(4)?;
+        if signature != b"8BIM" && signature != b"8B64" {
+            break;
+        }
+        let key = r.bytes(4)?;
+        let len = if header.large && matches!(key, b"Lr16" | b"Lr32" | b"Layr") {
+            r.u64()? as usize
+        } else {
+            r.u32()? as usize
+        };
+        let block_end = r.pos_after(len)?;
+        if matches!(key, b"Lr16" | b"Lr32" | b"Layr") {
+            return read_layer_info(r, header);
+        }
+        r.seek(block_end.min(section_end))?;
+    }
+
+    Ok((Vec::new(), false))
+}
+
+/// Reads the layer records and their channel data. Returns the layers
+/// bottom to top, and whether the composite's first extra channel is its
+/// transparency (signalled by a negative layer count).
+fn read_layer_info(
+    r: &mut ByteReader,
+    header: &Header,
+) -> Result<(Vec<Layer>, bool), ConversionError> {
+    let count = r.i16()?;
+    let merged_alpha = count < 0;
+
+    let records = (0..count.unsigned_abs())
+        .map(|_| read_layer_record(r, header))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut layers: Vec<Layer> = Vec::new();
+    // Indices into `layers` where each open group started
+    let mut groups: Vec<usize> = Vec::new();
+
+    for record in records {
+        let pixels = read_layer_pixels(r, header, &record)?;
+
+        match record.section {
+            3 => {
+                groups.push(layers.len());
+                continue;
+            }
+            1 | 2 => {
+                let Some(start) = groups.pop() else {
+                    continue;
+                };
+                let opacity = record.opacity as f32 / 255.0;
+                for layer in &mut layers[start..] {
+                    layer.visible &= !record.hidden;
+                    layer.opacity *= opacity;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some(pixels) = pixels {
+            layers.push(Layer {
+                name: record.name,
+                pixels,
+                left: record.left as i64,
+                top: record.top as i64,
+                opacity: record.opacity as f32 / 255.0,
+                visible: !record.hidden,
+                blend: record.blend,
+            });
+        }
+    }
+
+    Ok((layers, merged_alpha))
+}
+
+fn read_layer_record(r: &mut ByteReader, header: &Header) -> Result<LayerRecord, ConversionError> {
+    let top = r.i32()?;
+    let left = r.i32()?;
+    let bottom = r.i32()?;
+    let right = r.i32()?;
+
+    let channel_count = r.u16()?;
+    let channels = (0..channel_count)
+        .map(|_| {
+            Ok(ChannelInfo {
+                id: r.i16()?,
+                length: header.length(r)?,
+            })
+        })
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+
+    if r.bytes(4)? != b"8BIM" {
+        return Err(r.error("bad layer record signature"));
+    }
+    let blend = blend_mode(r.bytes(4)?);
+    let opacity = r.u8()?;
+    let _clipping = r.u8()?;
+    let flags = r.u8()?;
+    let _filler = r.u8()?;
+
+    let extra_len = r.u32()? as usize;
+    let extra_end = r.pos_after(extra_len)?;
+
+    let mask_len = r.u32()? as usize;
+    let mask_end = r.pos_after(mask_len)?;
+    let mask = if mask_len >= 18 {
+        Some(LayerMask {
+            top: r.i32()?,
+            left: r.i32()?,
+            bottom: r.i32()?,
+            right: r.i32()?,
+            default_color: r.u8()?,
+            disabled: r.u8()? & 0x02 != 0,
+        })
+    } else {
+        None
+    };
+    r.seek(mask_end)?;
+
+    let ranges_len = r.u32()? as usize;
+    r.skip(ranges_len)?;
+
+    // Pascal name padded to a multiple of four, including the length byte
+    let name_len = r.u8()? as usize;
+    let mut name = String::from_utf8_lossy(r.bytes(name_len)?).into_owned();
+    r.skip((4 - (name_len + 1) % 4) % 4)?;
+
+    let mut section = 0;
+    while r.pos() + 12 <= extra_end {
+        let signature = r.bytes(4)?;
+        if signature != b"8BIM" && signature != b"8B64" {
+            break;
+        }
+        let key = r.bytes(4)?;
+        let len = r.u32()? as usize;
+        let block = r.bytes(len.min(extra_end.saturating_sub(r.pos())))?;
+        match key {
+            b"luni" => {
+                if let Some(unicode) = unicode_name(block) {
+                    name = unicode;
+                }
+            }
+            b"lsct" if block.len() >= 4 => {
+                section = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+            }
+            _ => {}
+        }
+        let _ = r.skip(len % 2);
+    }
+    r.seek(extra_end)?;
+
+    Ok(LayerRecord {
+        top,
+        left,
+        bottom,
+        right,
+        channels,
+        blend,
+        opacity,
+        hidden: flags & 0x02 != 0,
+        name,
+        mask,
+        section,
+    })
+}
+
+/// Decodes a layer's channels into straight-alpha RGBA with its mask
+/// applied. Empty layers (groups, adjustment layers) yield `None`.
+fn read_layer_pixels(
+    r: &mut ByteReader,
+    header: &Header,
+    record: &LayerRecord,
+) -> Result<Option<RgbaImage>, ConversionError> {
+    let width = record.right.saturating_sub(record.left).max(0) as u32;
+    let height = record.bottom.saturating_sub(record.top).max(0) as u32;
+
+    let mut color: Vec<Option<Vec<u8>>> = vec![None; header.color_channels()];
+    let mut alpha = None;
+    let mut mask_data = None;
+
+    for channel in &record.channels {
+        let end = r.pos_after(channel.length)?;
+        if channel.length >= 2 {
+            let compression = r.u16()?;
+            match channel.id {
+                id @ 0.. if (id as usize) < color.len() => {
+                    color[id as usize] =
+                        Some(read_channel(r, header, compression, width, height, end)?);
+                }
+                CHANNEL_ALPHA => {
+                    alpha = Some(read_channel(r, header, compression, width, height, end)?);
+                }
+                CHANNEL_USER_MASK => {
+                    if let Some(mask) = record.mask.as_ref().filter(|m| !m.disabled) {
+                        let mask_width = mask.right.saturating_sub(mask.left).max(0) as u32;
+                        let mask_height = mask.bottom.saturating_sub(mask.top).max(0) as u32;
+                        let data =
+                            read_channel(r, header, compression, mask_width, mask_height, end)?;
+                        mask_data = Some((data, mask_width, mask_height));
+                    }
+                }
+                _ => {}
+            }
+        }
+        r.seek(end)?;
+    }
+
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+
+    let mut pixels = to_rgba(header, width, height, &color, alpha.as_deref());
+    if let (Some(mask), Some((data, mask_width, mask_height))) = (&record.mask, mask_data) {
+        let rect = (
+            (mask.left - record.left) as i64,
+            (mask.top - record.top) as i64,
+            mask_width,
+            mask_height,
+        );
+        apply_mask(&mut pixels, &data, rect, mask.default_color);
+    }
+    Ok(Some(pixels))
+}
+
+/// Reads the composite image saved after the layer section. Channels are
+/// stored one after the other, sharing a single compression marker.
+fn read_composite(
+    r: &mut ByteReader,
+    header: &Header,
+    merged_alpha: bool,
+) -> Result<RgbaImage, ConversionError> {
+    let compression = r.u16()?;
+    let (width, height) = (header.width, header.height);
+    let color_channels = header.color_channels();
+    let wanted = (color_channels + merged_alpha as usize).min(header.channels as usize);
+
+    let planes: Vec<Vec<u8>> = if compression == 1 {
+        // All row byte counts come first, then the rows of every channel
+        let counts = (0..header.channels as usize * height as usize)
+            .map(|_| header.row_count(r))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut planes = Vec::with_capacity(wanted);
+        for channel in 0..wanted {
+            let rows = &counts[channel * height as usize..(channel + 1) * height as usize];
+            planes.push(unpack_rows(r, header, rows, width)?);
+        }
+        planes
+    } else {
+        let plane_len = plane_bytes(header, width, height);
+        (0..wanted)
+            .map(|_| {
+                let raw = r.bytes(plane_len)?.to_vec();
+                Ok(to_8bit(header, &raw, width, height))
+            })
+            .collect::<Result<_, ConversionError>>()?
+    };
+
+    let color: Vec<Option<Vec<u8>>> = (0..color_channels)
+        .map(|c| planes.get(c).cloned())
+        .collect();
+    let alpha = merged_alpha.then(|| planes.get(color_channels)).flatten();
+    Ok(to_rgba(
+        header,
+        width,
+        height,
+        &color,
+        alpha.map(|a| a.as_slice()),
+    ))
+}
+
+// ============================================================================
+// Channel Data
+// ============================================================================
+
+/// Bytes in one uncompressed plane of `width` × `height` samples.
+fn plane_bytes(header: &Header, width: u32, height: u32) -> usize {
+    let row = if header.depth == 1 {
+        (width as usize).div_ceil(8)
+    } else {
+        width as usize * header.depth as usize / 8
+    };
+    row * height as usize
+}
+
+/// Reads one layer channel (after its compression marker) as 8-bit samples.
+fn read_channel(
+    r: &mut ByteReader,
+    header: &Header,
+    compression: u16,
+    width: u32,
+    height: u32,
+    end: usize,
+) -> Result<Vec<u8>, ConversionError> {
+    let plane_len = plane_bytes(header, width, height);
+    match compression {
+        0 => {
+            let raw = r.bytes(plane_len)?;
+            Ok(to_8bit(header, raw, width, height))
+        }
+        1 => {
+            let counts = (0..height)
+                .map(|_| header.row_count(r))
+                .collect::<Result<Vec<_>, _>>()?;
+            unpack_rows(r, header, &counts, width)
+        }
+        2 | 3 => {
+            let compressed = r.bytes(end.saturating_sub(r.pos()))?;
+            let mut raw = Vec::with_capacity(plane_len);
+            ZlibDecoder::new(compressed)
+                .read_to_end(&mut raw)
+                .map_err(|e| r.error(e))?;
+            raw.resize(plane_len, 0);
+            if compression == 3 {
+                undo_prediction(header, &mut raw, width)?;
+            }
+            Ok(to_8bit(header, &raw, width, height))
+        }
+        other => Err(r.error(format!("unknown compression {}", other))),
+    }
+}
+
+/// Decodes PackBits-compressed rows with the given byte counts.
+fn unpack_rows(
+    r: &mut ByteReader,
+    header: &Header,
+    counts: &[usize],
+    width: u32,
+) -> Result<Vec<u8>, ConversionError> {
+    let height = counts.len() as u32;
+    let row_len = plane_bytes(header, width, 1);
+    let mut raw = Vec::with_capacity(row_len * counts.len());
+    for &count in counts {
+        unpack_bits(r.bytes(count)?, row_len, &mut raw);
+    }
+    Ok(to_8bit(header, &raw, width, height))
+}
+
+/// Appends exactly `expected` bytes decoded from PackBits data to `out`.
+fn unpack_bits(src: &[u8], expected: usize, out: &mut Vec<u8>) {
+    let target = out.len() + expected;
+    let mut i = 0;
+    while i < src.len() && out.len() < target {
+        let n = src[i] as i8;
+        i += 1;
+        if n >= 0 {
+            let end = (i + n as usize + 1).min(src.len());
+            out.extend_from_slice(&src[i..end]);
+            i = end;
+        } else if n != -128 {
+            if let Some(&value) = src.get(i) {
+                out.extend(std::iter::repeat_n(value, 1 + n.unsigned_abs() as usize));
+            }
+            i += 1;
+        }
+    }
+    out.resize(target, 0);
+}
+
+/// Reverses the delta encoding of "ZIP with prediction" channels.
+fn undo_prediction(header: &Header, raw: &mut [u8], width: u32) -> Result<(), ConversionError> {
+    let width = width as usize;
+    match header.depth {
+        8 => {
+            for row in raw.chunks_mut(width.max(1)) {
+                for i in 1..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - 1]);
+                }
+            }
+        }
+        16 => {
+            for row in raw.chunks_mut((width * 2).max(2)) {
+                let mut previous = 0u16;
+                for sample in row.chunks_exact_mut(2) {
+                    previous = previous.wrapping_add(u16::from_be_bytes([sample[0], sample[1]]));
+                    sample.copy_from_slice(&previous.to_be_bytes());
+                }
+            }
+        }
+        _ => {
+            return Err(ConversionError::UnsupportedFormat(
+                "PSD 32-bit ZIP-predicted channels".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Converts raw samples at the document's bit depth to 8 bits per sample.
+/// 32-bit documents hold linear floats and are converted to sRGB gamma.
+fn to_8bit(header: &Header, raw: &[u8], width: u32, height: u32) -> Vec<u8> {
+    match header.depth {
+        8 => raw.to_vec(),
+        // Big-endian samples: the high byte is a good enough reduction
+        16 => raw.chunks_exact(2).map(|s| s[0]).collect(),
+        32 => raw
+            .chunks_exact(4)
+            .map(|s| {
+                let linear = f32::from_be_bytes([s[0], s[1], s[2], s[3]]).clamp(0.0, 1.0);
+                let encoded = if linear <= 0.003_130_8 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (encoded * 255.0).round() as u8
+            })
+            .collect(),
+        // Bitmap mode: one bit per pixel, rows padded to whole bytes, 1 is black
+        _ => {
+            let row_len = (width as usize).div_ceil(8);
+            let mut out = Vec::with_capacity(width as usize * height as usize);
+            for row in raw.chunks(row_len.max(1)).take(height as usize) {
+                for x in 0..width as usize {
+                    let bit = row.get(x / 8).map_or(0, |b| (b >> (7 - x % 8)) & 1);
+                    out.push(if bit == 1 { 0 } else { 255 });
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Assembles 8-bit channel planes into RGBA according to the colour mode.
+/// Missing channels read as zero, a missing alpha as opaque.
+fn to_rgba(
+    header: &Header,
+    width: u32,
+    height: u32,
+    color: &[Option<Vec<u8>>],
+    alpha: Option<&[u8]>,
+) -> RgbaImage {
+    let sample = |channel: usize, i: usize| -> u8 {
+        color
+            .get(channel)
+            .and_then(|c| c.as_ref())
+            .and_then(|c| c.get(i).copied())
+            .unwrap_or(0)
+    };
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let i = y as usize * width as usize + x as usize;
+        let a = alpha.and_then(|a| a.get(i).copied()).unwrap_or(255);
+        let [r, g, b] = match header.mode {
+            MODE_RGB => [sample(0, i), sample(1, i), sample(2, i)],
+            // Stored inverted: 255 means no ink
+            MODE_CMYK => {
+                let k = sample(3, i) as u32;
+                [0, 1, 2].map(|c| (sample(c, i) as u32 * k / 255) as u8)
+            }
+            MODE_INDEXED => {
+                let index = sample(0, i) as usize;
+                let entry = |plane: usize| header.palette.get(plane * 256 + index).copied();
+                match (entry(0), entry(1), entry(2)) {
+                    (Some(r), Some(g), Some(b)) => [r, g, b],
+                    _ => [0, 0, 0],
+                }
+            }
+            _ => {
+                let v = sample(0, i);
+                [v, v, v]
+            }
+        };
+        Rgba([r, g, b, a])
+    })
+}
+
+fn unicode_name(block: &[u8]) -> Option<String> {
+    let len = u32::from_be_bytes(block.get(..4)?.try_into().ok()?) as usize;
+    let units: Vec<u16> = block
+        .get(4..4 + len * 2)?
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+fn blend_mode(key: &[u8]) -> BlendMode {
+    match key {
+        b"mul " => BlendMode::Multiply,
+        b"scrn" => BlendMode::Screen,
+        b"over" => BlendMode::Overlay,
+        b"dark" => BlendMode::Darken,
+        b"lite" => BlendMode::Lighten,
+        b"div " => BlendMode::ColorDodge,
+        b"idiv" => BlendMode::ColorBurn,
+        b"lbrn" => BlendMode::LinearBurn,
+        b"hLit" => BlendMode::HardLight,
+        b"sLit" => BlendMode::SoftLight,
+        b"diff" => BlendMode::Difference,
+        b"smud" => BlendMode::Exclusion,
+        b"lddg" => BlendMode::Add,
+        b"fsub" => BlendMode::Subtract,
+        b"fdiv" => BlendMode::Divide,
+        _ => BlendMode::Normal,
+    }
+}
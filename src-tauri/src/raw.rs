@@ -0,0 +1,409 @@
+use crate::metadata::{exif_from_raw, ImageMetadata};
+use crate::ConversionError;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// ============================================================================
+// RAW Options
+// ============================================================================
+
+/// How a camera RAW file is turned into pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RawMode {
+    /// Develop the sensor data: demosaic, white balance, colour matrix and
+    /// gamma. Builds without the `raw` feature use the preview instead.
+    #[default]
+    Develop,
+    /// Use the JPEG preview the camera embedded in the file. Much faster, but
+    /// it carries the camera's own processing and may be smaller than the
+    /// sensor resolution.
+    Preview,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RawOptions {
+    pub mode: RawMode,
+    /// Exposure compensation in stops, applied while developing.
+    pub exposure: f32,
+}
+
+/// Decodes a camera RAW file according to `options`.
+pub(crate) fn load_raw(path: &Path, options: &RawOptions) -> Result<DynamicImage, ConversionError> {
+    match options.mode {
+        #[cfg(feature = "raw")]
+        RawMode::Develop => develop::develop_raw(path, options.exposure),
+        #[cfg(not(feature = "raw"))]
+        RawMode::Develop => load_preview(path),
+        RawMode::Preview => load_preview(path),
+    }
+}
+
+/// Reads the shooting data and orientation of a TIFF-based RAW file. Neither
+/// the sensor data nor the embedded previews are rotated, so the orientation
+/// is left for the transform step to apply.
+pub(crate) fn read_raw_metadata(path: &Path) -> ImageMetadata {
+    let Ok(data) = fs::read(path) else {
+        return ImageMetadata::default();
+    };
+
+    let orientation = Tiff::parse(&data)
+        .and_then(|tiff| {
+            let (entries, _) = tiff.entries(tiff.first_ifd)?;
+            let entry = entries.iter().find(|e| e.tag == TAG_ORIENTATION)?;
+            tiff.values(entry).first().copied()
+        })
+        .and_then(|value| Orientation::from_exif(value as u8));
+
+    ImageMetadata {
+        exif: exif_from_raw(data),
+        orientation,
+        ..Default::default()
+    }
+}
+
+// ============================================================================
+// Embedded Previews
+// ============================================================================
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014a;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+
+/// Limits on IFD traversal, so corrupt files with looping offsets terminate.
+const MAX_IFDS: usize = 64;
+const MAX_SUB_IFD_DEPTH: usize = 4;
+
+/// Decodes the largest JPEG preview embedded in a TIFF-based RAW file.
+fn load_preview(path: &Path) -> Result<DynamicImage, ConversionError> {
+    let data = fs::read(path).map_err(|e| ConversionError::DecodeError(e.to_string()))?;
+    let tiff = Tiff::parse(&data).ok_or_else(|| {
+        ConversionError::DecodeError("RAW file is not TIFF-based, no preview found".to_string())
+    })?;
+
+    let mut previews = Vec::new();
+    let mut visited = Vec::new();
+    tiff.collect_previews(tiff.first_ifd, 0, &mut visited, &mut previews);
+
+    // Compressed sensor data also starts with a JPEG marker (lossless JPEG),
+    // but the `image` decoder rejects it, so the largest decodable stream wins
+    previews.sort_by_key(|&(_, len)| std::cmp::Reverse(len));
+    previews
+        .into_iter()
+        .filter_map(|(offset, len)| data.get(offset..offset.checked_add(len)?))
+        .filter(|jpeg| jpeg.starts_with(&[0xFF, 0xD8]))
+        .find_map(|jpeg| image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).ok())
+        .ok_or_else(|| {
+            ConversionError::DecodeError("RAW file has no embedded JPEG preview".to_string())
+        })
+}
+
+struct IfdEntry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// Position of the entry's value/offset field in the file.
+    value_pos: usize,
+}
+
+/// Minimal reader for the TIFF structure shared by most RAW formats.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+    first_ifd: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let mut tiff = Self {
+            data,
+            little_endian,
+            first_ifd: 0,
+        };
+        tiff.first_ifd = tiff.u32(4)? as usize;
+        Some(tiff)
+    }
+
+    fn u16(&self, pos: usize) -> Option<u16> {
+        let bytes = [*self.data.get(pos)?, *self.data.get(pos + 1)?];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, pos: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Reads the entries of the IFD at `pos` and the offset of the next IFD.
+    fn entries(&self, pos: usize) -> Option<(Vec<IfdEntry>, usize)> {
+        let count = self.u16(pos)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = pos + 2 + i * 12;
+            entries.push(IfdEntry {
+                tag: self.u16(entry)?,
+                kind: self.u16(entry + 2)?,
+                count: self.u32(entry + 4)?,
+                value_pos: entry + 8,
+            });
+        }
+        let next = self.u32(pos + 2 + count * 12)? as usize;
+        Some((entries, next))
+    }
+
+    /// Integer values of a SHORT, LONG or IFD entry. Other types yield nothing.
+    fn values(&self, entry: &IfdEntry) -> Vec<u32> {
+        let size = match entry.kind {
+            3 => 2,
+            4 | 13 => 4,
+            _ => return Vec::new(),
+        };
+        let count = entry.count as usize;
+        let start = if count.saturating_mul(size) <= 4 {
+            entry.value_pos
+        } else {
+            match self.u32(entry.value_pos) {
+                Some(offset) => offset as usize,
+                None => return Vec::new(),
+            }
+        };
+        if start.saturating_add(count.saturating_mul(size)) > self.data.len() {
+            return Vec::new();
+        }
+
+        (0..count)
+            .filter_map(|i| match size {
+                2 => self.u16(start + i * 2).map(u32::from),
+                _ => self.u32(start + i * 4),
+            })
+            .collect()
+    }
+
+    /// Walks the IFD chain starting at `pos`, and the SubIFDs of each IFD,
+    /// collecting `(offset, length)` of every JPEG stream they reference.
+    fn collect_previews(
+        &self,
+        mut pos: usize,
+        depth: usize,
+        visited: &mut Vec<usize>,
+        previews: &mut Vec<(usize, usize)>,
+    ) {
+        while pos != 0 && visited.len() < MAX_IFDS && !visited.contains(&pos) {
+            visited.push(pos);
+            let Some((entries, next)) = self.entries(pos) else {
+                return;
+            };
+            let value = |tag: u16| {
+                entries
+                    .iter()
+                    .find(|e| e.tag == tag)
+                    .map(|e| self.values(e))
+                    .unwrap_or_default()
+            };
+
+            // Thumbnails and previews referenced the JPEG way (ARW, NEF, IFD1)
+            if let ([offset], [len]) = (&value(TAG_JPEG_OFFSET)[..], &value(TAG_JPEG_LENGTH)[..]) {
+                previews.push((*offset as usize, *len as usize));
+            }
+
+            // JPEG-compressed single-strip images (CR2 IFD0, DNG previews)
+            if matches!(value(TAG_COMPRESSION)[..], [6 | 7]) {
+                if let ([offset], [len]) = (
+                    &value(TAG_STRIP_OFFSETS)[..],
+                    &value(TAG_STRIP_BYTE_COUNTS)[..],
+                ) {
+                    previews.push((*offset as usize, *len as usize));
+                }
+            }
+
+            if depth < MAX_SUB_IFD_DEPTH {
+                for sub_ifd in value(TAG_SUB_IFDS) {
+                    self.collect_previews(sub_ifd as usize, depth + 1, visited, previews);
+                }
+            }
+
+            pos = next;
+        }
+    }
+}
+
+// ============================================================================
+// RAW Development
+// ============================================================================
+
+#[cfg(feature = "raw")]
+mod develop {
+    use crate::ConversionError;
+    use image::{DynamicImage, ImageBuffer, Rgb};
+    use rawloader::{RawImage, RawImageData};
+    use std::path::Path;
+
+    /// Linear XYZ (D65) to linear sRGB.
+    const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+        [3.240_454_2, -1.537_138_5, -0.498_531_4],
+        [-0.969_266, 1.876_010_8, 0.041_556],
+        [0.055_643_4, -0.204_025_9, 1.057_225_2],
+    ];
+
+    /// Develops the sensor data of a RAW file into a 16-bit sRGB image:
+    /// black/white level scaling, the camera's as-shot white balance, bilinear
+    /// demosaicing, the camera colour matrix and the sRGB transfer curve.
+    pub(crate) fn develop_raw(path: &Path, exposure: f32) -> Result<DynamicImage, ConversionError> {
+        let raw = rawloader::decode_file(path)
+            .map_err(|e| ConversionError::DecodeError(format!("RAW: {}", e)))?;
+
+        let samples: Vec<f32> = match &raw.data {
+            RawImageData::Integer(data) => data.iter().map(|&v| v as f32).collect(),
+            RawImageData::Float(data) => data.clone(),
+        };
+        if raw.width == 0 || raw.height == 0 || samples.len() < raw.width * raw.height * raw.cpp {
+            return Err(ConversionError::DecodeError(
+                "RAW: sensor data is truncated".to_string(),
+            ));
+        }
+
+        let wb = white_balance(&raw);
+        let matrix = camera_to_srgb(&raw);
+        let gain = 2f32.powf(exposure);
+
+        // Crops are top, right, bottom, left
+        let [top, right, bottom, left] = raw.crops;
+        let out_width = raw.width.saturating_sub(left + right).max(1);
+        let out_height = raw.height.saturating_sub(top + bottom).max(1);
+
+        // Black-level subtracted, white-balanced value of one sample
+        let level = |index: usize, color: usize| {
+            let black = raw.blacklevels[color] as f32;
+            let white = (raw.whitelevels[color] as f32).max(black + 1.0);
+            ((samples[index] - black) / (white - black)).max(0.0) * wb[color]
+        };
+
+        let mut data = Vec::with_capacity(out_width * out_height * 3);
+        for row in top..top + out_height {
+            for col in left..left + out_width {
+                let camera = if raw.cpp >= 3 {
+                    let base = (row * raw.width + col) * raw.cpp;
+                    let green = level(base + 1, 1);
+                    [level(base, 0), green, level(base + 2, 2), green]
+                } else {
+                    demosaic_pixel(&raw, row, col, &level)
+                };
+
+                for out in matrix {
+                    let linear = (out[0] * camera[0]
+                        + out[1] * camera[1]
+                        + out[2] * camera[2]
+                        + out[3] * camera[3])
+                        * gain;
+                    data.push((srgb_gamma(linear.clamp(0.0, 1.0)) * 65535.0).round() as u16);
+                }
+            }
+        }
+
+        ImageBuffer::<Rgb<u16>, _>::from_raw(out_width as u32, out_height as u32, data)
+            .map(DynamicImage::ImageRgb16)
+            .ok_or_else(|| ConversionError::DecodeError("RAW: image size mismatch".to_string()))
+    }
+
+    /// Bilinear demosaic of one photosite: its own colour is kept and the
+    /// others are averaged from the surrounding 3×3 neighbourhood. This works
+    /// for both Bayer and X-Trans layouts.
+    fn demosaic_pixel(
+        raw: &RawImage,
+        row: usize,
+        col: usize,
+        level: &impl Fn(usize, usize) -> f32,
+    ) -> [f32; 4] {
+        let mut sum = [0.0f32; 4];
+        let mut count = [0u32; 4];
+        for r in row.saturating_sub(1)..(row + 2).min(raw.height) {
+            for c in col.saturating_sub(1)..(col + 2).min(raw.width) {
+                let color = raw.cfa.color_at(r, c).min(3);
+                sum[color] += level(r * raw.width + c, color);
+                count[color] += 1;
+            }
+        }
+
+        let own = raw.cfa.color_at(row, col).min(3);
+        let mut camera = [0.0f32; 4];
+        for (color, value) in camera.iter_mut().enumerate() {
+            *value = if color == own {
+                level(row * raw.width + col, color)
+            } else if count[color] > 0 {
+                sum[color] / count[color] as f32
+            } else {
+                0.0
+            };
+        }
+        // Three-colour sensors have no fourth channel; mirror green into it
+        if count[3] == 0 {
+            camera[3] = camera[1];
+        }
+        camera
+    }
+
+    /// As-shot white balance multipliers, normalised to green. Missing or
+    /// unreadable coefficients fall back to no correction.
+    fn white_balance(raw: &RawImage) -> [f32; 4] {
+        let coeffs = raw.wb_coeffs;
+        let usable = |v: f32| v.is_finite() && v > 0.0;
+        if !usable(coeffs[0]) || !usable(coeffs[1]) || !usable(coeffs[2]) {
+            return [1.0; 4];
+        }
+        let green = coeffs[1];
+        let fourth = if usable(coeffs[3]) { coeffs[3] } else { green };
+        [coeffs[0] / green, 1.0, coeffs[2] / green, fourth / green]
+    }
+
+    /// Camera RGB(E) to linear sRGB, via XYZ. Cameras without a known colour
+    /// matrix fall back to treating the sensor channels as sRGB.
+    fn camera_to_srgb(raw: &RawImage) -> [[f32; 4]; 3] {
+        let cam_to_xyz = raw.cam_to_xyz_normalized();
+        let known = cam_to_xyz.iter().flatten().all(|v| v.is_finite())
+            && cam_to_xyz.iter().flatten().any(|v| *v != 0.0);
+        if !known {
+            return [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.5, 0.0, 0.5],
+                [0.0, 0.0, 1.0, 0.0],
+            ];
+        }
+
+        let mut matrix = [[0.0f32; 4]; 3];
+        for (out, srgb_row) in matrix.iter_mut().zip(XYZ_TO_SRGB) {
+            for (channel, value) in out.iter_mut().enumerate() {
+                *value = (0..3).map(|k| srgb_row[k] * cam_to_xyz[k][channel]).sum();
+            }
+        }
+        matrix
+    }
+
+    fn srgb_gamma(linear: f32) -> f32 {
+        if linear <= 0.003_130_8 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{ConversionError, ConversionOptions, PdfPageOptions};
+use image::{DynamicImage, RgbaImage};
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use usvg::fontdb;
+
+// ============================================================================
+// SVG Options
+// ============================================================================
+
+/// How SVG files are rasterised.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SvgOptions {
+    /// Render width in pixels. With `height` as well, the drawing is scaled
+    /// to fit inside both, keeping its aspect ratio. For PDF output this sets
+    /// the page size, at 96 pixels per inch.
+    pub width: Option<u32>,
+    /// Render height in pixels.
+    pub height: Option<u32>,
+    /// Render resolution when no size is given. The SVG's own size is taken
+    /// to be at 96 DPI, so 192 renders at twice its size. Vector PDFs have
+    /// no resolution and ignore it.
+    pub dpi: Option<f32>,
+    /// Background colour as `#rgb`, `#rrggbb` or `#rrggbbaa`. Defaults to
+    /// transparent, or white for formats without an alpha channel (JPEG,
+    /// PPM/PGM/PBM, HDR). Setting it rasterises PDF output.
+    pub background: Option<String>,
+    /// Extra directories to load fonts from, on top of the system fonts.
+    pub font_dirs: Vec<String>,
+    /// Directory that relative `<image>` hrefs are resolved against.
+    /// Defaults to the SVG's own directory.
+    pub resources_dir: Option<String>,
+}
+
+/// Resolution SVG user units are defined at.
+const SVG_DPI: f32 = 96.0;
+
+/// Output formats that drop the alpha channel, so transparent areas would
+/// turn black.
+fn is_opaque_format(extension: &str) -> bool {
+    matches!(
+        extension,
+        "jpg" | "jpeg" | "jfif" | "ppm" | "pgm" | "pbm" | "hdr"
+    )
+}
+
+/// Parses `#rgb`, `#rrggbb` or `#rrggbbaa`.
+fn parse_color(value: &str) -> Result<Color, ConversionError> {
+    let invalid = || {
+        ConversionError::SvgError(format!(
+            "Invalid background colour '{}', expected #rrggbb or #rrggbbaa",
+            value
+        ))
+    };
+
+    let hex = value.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let digits: Vec<u8> = match hex.len() {
+        3 => hex
+            .chars()
+            .map(|c| u8::from_str_radix(&c.to_string(), 16).map(|v| v * 17))
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?,
+        _ => return Err(invalid()),
+    };
+
+    Ok(Color::from_rgba8(
+        digits[0],
+        digits[1],
+        digits[2],
+        digits.get(3).copied().unwrap_or(255),
+    ))
+}
+
+/// System fonts are scanned once per process; batches render many SVGs.
+fn system_fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+/// Scale from the SVG's own size to the requested render size.
+fn render_scale(size: usvg::Size, options: &SvgOptions) -> f32 {
+    let width_scale = options.width.map(|w| w as f32 / size.width());
+    let height_scale = options.height.map(|h| h as f32 / size.height());
+    match (width_scale, height_scale) {
+        (Some(w), Some(h)) => w.min(h),
+        (Some(s), None) | (None, Some(s)) => s,
+        (None, None) => options.dpi.map_or(1.0, |dpi| dpi / SVG_DPI),
+    }
+}
+
+// ============================================================================
+// Rendering
+// ============================================================================
+
+/// Parses an SVG with the fonts and resources directory from `options`.
+fn parse_svg(path: &Path, options: &SvgOptions) -> Result<usvg::Tree, ConversionError> {
+    let svg_data = fs::read(path).map_err(|e| ConversionError::ReadError(e.to_string()))?;
+
+    let mut usvg_options = usvg::Options {
+        resources_dir: match &options.resources_dir {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => path.parent().map(Path::to_path_buf),
+        },
+        fontdb: system_fonts(),
+        ..usvg::Options::default()
+    };
+    for dir in &options.font_dirs {
+        usvg_options.fontdb_mut().load_fonts_dir(dir);
+    }
+
+    usvg::Tree::from_data(&svg_data, &usvg_options)
+        .map_err(|e| ConversionError::SvgError(e.to_string()))
+}
+
+/// Rasterises an SVG according to `options`. `output_extension` picks the
+/// default background.
+pub(crate) fn load_svg(
+    path: &Path,
+    options: &SvgOptions,
+    output_extension: &str,
+) -> Result<DynamicImage, ConversionError> {
+    let background = match &options.background {
+        Some(value) => Some(parse_color(value)?),
+        None if is_opaque_format(output_extension) => Some(Color::WHITE),
+        None => None,
+    };
+
+    let tree = parse_svg(path, options)?;
+    let original_size = tree.size();
+    let scale = render_scale(original_size, options);
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(ConversionError::SvgError(
+            "Render size must be greater than zero".to_string(),
+        ));
+    }
+
+    let scaled_width = ((original_size.width() * scale).round() as u32).max(1);
+    let scaled_height = ((original_size.height() * scale).round() as u32).max(1);
+
+    let mut pixmap = Pixmap::new(scaled_width, scaled_height)
+        .ok_or_else(|| ConversionError::SvgError("Failed to create pixmap".to_string()))?;
+    if let Some(color) = background {
+        pixmap.fill(color);
+    }
+
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny-skia stores premultiplied alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    let rgba_image = RgbaImage::from_raw(scaled_width, scaled_height, pixels)
+        .ok_or_else(|| ConversionError::SvgError("Failed to create image from SVG".to_string()))?;
+
+    Ok(DynamicImage::ImageRgba8(rgba_image))
+}
+
+// ============================================================================
+// Vector PDF
+// ============================================================================
+
+/// Whether the SVG can go to PDF as vectors. Pixel edits, backgrounds and
+/// page layouts only exist on the raster path.
+pub(crate) fn can_convert_to_pdf(options: &ConversionOptions) -> bool {
+    options.svg.background.is_none()
+        && !options.transform.has_edits()
+        && options.pdf_page == PdfPageOptions::default()
+}
+
+/// Converts an SVG to a one-page PDF, keeping paths as vectors and text as
+/// selectable text with the fonts embedded. The page is the SVG's size (or
+/// `options.width`/`height`) at 96 pixels per inch; images inside the SVG
+/// are embedded as they are.
+pub(crate) fn svg_to_pdf(
+    path: &Path,
+    options: &SvgOptions,
+    output_path: &Path,
+) -> Result<(), ConversionError> {
+    let tree = parse_svg(path, options)?;
+    let scale = render_scale(
+        tree.size(),
+        &SvgOptions {
+            dpi: None,
+            ..options.clone()
+        },
+    );
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(ConversionError::SvgError(
+            "Page size must be greater than zero".to_string(),
+        ));
+    }
+
+    // A lower DPI maps each SVG unit to more of the page
+    let pdf = svg2pdf::to_pdf(
+        &tree,
+        svg2pdf::ConversionOptions::default(),
+        svg2pdf::PageOptions {
+            dpi: SVG_DPI / scale,
+        },
+    )
+    .map_err(|e| ConversionError::PdfError(e.to_string()))?;
+
+    fs::write(output_path, pdf).map_err(|e| ConversionError::WriteError(e.to_string()))
+}
@@ -0,0 +1,126 @@
+use image::{ColorType, DynamicImage, RgbaImage};
+use std::borrow::Cow;
+
+// ============================================================================
+// High Dynamic Range
+// ============================================================================
+
+/// Share of pixels that map below full white. The brightest 0.1% (specular
+/// highlights, light sources) clip, so a single hot pixel cannot darken the
+/// rest of the image.
+const WHITE_PERCENTILE: f32 = 0.999;
+
+/// Largest finite half-float value; OpenEXR files may hold infinities.
+const MAX_RADIANCE: f32 = 65504.0;
+
+/// Whether the image holds floating-point, scene-linear samples, as decoded
+/// from Radiance HDR and OpenEXR files.
+pub(crate) fn is_hdr(img: &DynamicImage) -> bool {
+    matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+/// Tone maps a floating-point image to 8-bit sRGB for formats that cannot
+/// hold values above white. Uses extended Reinhard on luminance, with the
+/// white point at the 99.9th percentile, so images already within 0..1 are
+/// only gamma encoded. Other images are returned unchanged.
+pub(crate) fn to_sdr(img: &DynamicImage) -> Cow<'_, DynamicImage> {
+    if !is_hdr(img) {
+        return Cow::Borrowed(img);
+    }
+
+    let linear = img.to_rgba32f();
+    let luminances: Vec<f32> = linear
+        .pixels()
+        .map(|p| luminance(sanitize(p[0]), sanitize(p[1]), sanitize(p[2])))
+        .collect();
+    let white = white_point(luminances.clone());
+
+    let mut mapped = RgbaImage::new(linear.width(), linear.height());
+    for ((src, dst), l) in linear.pixels().zip(mapped.pixels_mut()).zip(luminances) {
+        let scale = if l > 0.0 { reinhard(l, white) / l } else { 0.0 };
+        dst.0 = [
+            encode_srgb(sanitize(src[0]) * scale),
+            encode_srgb(sanitize(src[1]) * scale),
+            encode_srgb(sanitize(src[2]) * scale),
+            (src[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+        ];
+    }
+
+    let mapped = DynamicImage::ImageRgba8(mapped);
+    Cow::Owned(if img.color().has_alpha() {
+        mapped
+    } else {
+        DynamicImage::ImageRgb8(mapped.into_rgb8())
+    })
+}
+
+/// Converts an image to floating-point linear light for HDR formats,
+/// decoding the sRGB transfer curve of 8- and 16-bit sources. Images that
+/// are already floating point are returned unchanged.
+pub(crate) fn to_linear(img: &DynamicImage) -> Cow<'_, DynamicImage> {
+    if is_hdr(img) {
+        return Cow::Borrowed(img);
+    }
+
+    let mut linear = img.to_rgba32f();
+    for pixel in linear.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = decode_srgb(*c);
+        }
+    }
+
+    let linear = DynamicImage::ImageRgba32F(linear);
+    Cow::Owned(if img.color().has_alpha() {
+        linear
+    } else {
+        DynamicImage::ImageRgb32F(linear.into_rgb32f())
+    })
+}
+
+/// Rec. 709 luminance of linear RGB.
+fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Drops negative and NaN samples and caps infinities.
+fn sanitize(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, MAX_RADIANCE)
+    }
+}
+
+/// Luminance that maps to full white, never below 1 so SDR content keeps
+/// its brightness.
+fn white_point(mut luminances: Vec<f32>) -> f32 {
+    if luminances.is_empty() {
+        return 1.0;
+    }
+    let index = ((luminances.len() - 1) as f32 * WHITE_PERCENTILE) as usize;
+    let (_, white, _) = luminances.select_nth_unstable_by(index, f32::total_cmp);
+    white.max(1.0)
+}
+
+/// Extended Reinhard: compresses highlights while mapping `white` to 1.
+fn reinhard(l: f32, white: f32) -> f32 {
+    l * (1.0 + l / (white * white)) / (1.0 + l)
+}
+
+fn encode_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+fn decode_srgb(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,225 @@
+use crate::ConversionError;
+use image::imageops::{self, FilterType};
+use image::metadata::Orientation;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Transform Types
+// ============================================================================
+
+/// How an image is fitted into an exact `width` × `height` box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    /// Scale to fit inside the box, keeping the aspect ratio. The output may be
+    /// smaller than the box in one dimension.
+    #[default]
+    Fit,
+    /// Scale to cover the box, keeping the aspect ratio, and crop the overflow.
+    Fill,
+    /// Scale to fit inside the box and pad the rest with `pad_color`.
+    Pad,
+    /// Stretch to the box, ignoring the aspect ratio.
+    Stretch,
+}
+
+/// Resampling filter used when resizing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResampleFilter> for FilterType {
+    fn from(filter: ResampleFilter) -> Self {
+        match filter {
+            ResampleFilter::Nearest => FilterType::Nearest,
+            ResampleFilter::Triangle => FilterType::Triangle,
+            ResampleFilter::CatmullRom => FilterType::CatmullRom,
+            ResampleFilter::Gaussian => FilterType::Gaussian,
+            ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// A rectangle in pixels, measured from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Edits applied to an image between decoding and encoding, in this order:
+/// EXIF auto-orient, crop, rotate, flip, exact resize, then the max bounds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageTransform {
+    /// Rotate/flip the pixels according to the EXIF orientation tag, so the
+    /// output looks right even where the tag is dropped.
+    pub auto_orient: bool,
+    /// Region to keep, in the (auto-oriented) source's pixel coordinates.
+    pub crop: Option<CropRect>,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub rotate: u16,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// Exact output width. With only one of `width`/`height` set, the other
+    /// follows the aspect ratio.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub resize_mode: ResizeMode,
+    /// Fill colour (RGBA) for `ResizeMode::Pad`.
+    pub pad_color: [u8; 4],
+    /// Downscale (never upscale) to fit within these bounds.
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub filter: ResampleFilter,
+}
+
+impl Default for ImageTransform {
+    fn default() -> Self {
+        Self {
+            auto_orient: true,
+            crop: None,
+            rotate: 0,
+            flip_horizontal: false,
+            flip_vertical: false,
+            width: None,
+            height: None,
+            resize_mode: ResizeMode::default(),
+            // Transparent white, so formats without alpha pad with white
+            pad_color: [255, 255, 255, 0],
+            max_width: None,
+            max_height: None,
+            filter: ResampleFilter::default(),
+        }
+    }
+}
+
+// ============================================================================
+// Applying Transforms
+// ============================================================================
+
+impl ImageTransform {
+    /// Whether any edit besides EXIF auto-orient is set.
+    pub(crate) fn has_edits(&self) -> bool {
+        self.crop.is_some()
+            || self.rotate != 0
+            || self.flip_horizontal
+            || self.flip_vertical
+            || self.width.is_some()
+            || self.height.is_some()
+            || self.max_width.is_some()
+            || self.max_height.is_some()
+    }
+
+    /// Applies the transform to `img`. `orientation` is the source's EXIF
+    /// orientation, used when `auto_orient` is set.
+    pub fn apply(
+        &self,
+        mut img: DynamicImage,
+        orientation: Orientation,
+    ) -> Result<DynamicImage, ConversionError> {
+        if self.auto_orient {
+            img.apply_orientation(orientation);
+        }
+
+        if let Some(crop) = self.crop {
+            let (width, height) = img.dimensions();
+            let fits = crop.width > 0
+                && crop.height > 0
+                && crop.x.checked_add(crop.width).is_some_and(|r| r <= width)
+                && crop.y.checked_add(crop.height).is_some_and(|b| b <= height);
+            if !fits {
+                return Err(ConversionError::InvalidTransform(format!(
+                    "crop {}x{}+{}+{} is outside the {}x{} image",
+                    crop.width, crop.height, crop.x, crop.y, width, height
+                )));
+            }
+            img = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        }
+
+        img = match self.rotate % 360 {
+            0 => img,
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            other => {
+                return Err(ConversionError::InvalidTransform(format!(
+                    "rotation must be a multiple of 90 degrees, got {}",
+                    other
+                )))
+            }
+        };
+
+        if self.flip_horizontal {
+            img = img.fliph();
+        }
+        if self.flip_vertical {
+            img = img.flipv();
+        }
+
+        img = self.resize_exact(img)?;
+
+        // Downscale only; images already within bounds are left alone
+        let (width, height) = img.dimensions();
+        let max_width = self.max_width.unwrap_or(u32::MAX);
+        let max_height = self.max_height.unwrap_or(u32::MAX);
+        if max_width == 0 || max_height == 0 {
+            return Err(ConversionError::InvalidTransform(
+                "max width and height must be greater than zero".to_string(),
+            ));
+        }
+        if width > max_width || height > max_height {
+            img = img.resize(max_width.min(width), max_height.min(height), self.filter.into());
+        }
+
+        Ok(img)
+    }
+
+    fn resize_exact(&self, img: DynamicImage) -> Result<DynamicImage, ConversionError> {
+        let (src_width, src_height) = img.dimensions();
+        let filter: FilterType = self.filter.into();
+
+        // Fill in a missing dimension from the aspect ratio
+        let (width, height) = match (self.width, self.height) {
+            (None, None) => return Ok(img),
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, scale_dimension(src_height, w, src_width)),
+            (None, Some(h)) => (scale_dimension(src_width, h, src_height), h),
+        };
+        if width == 0 || height == 0 {
+            return Err(ConversionError::InvalidTransform(
+                "width and height must be greater than zero".to_string(),
+            ));
+        }
+
+        Ok(match self.resize_mode {
+            ResizeMode::Fit => img.resize(width, height, filter),
+            ResizeMode::Fill => img.resize_to_fill(width, height, filter),
+            ResizeMode::Stretch => img.resize_exact(width, height, filter),
+            ResizeMode::Pad => {
+                let fitted = img.resize(width, height, filter).to_rgba8();
+                let mut canvas = RgbaImage::from_pixel(width, height, Rgba(self.pad_color));
+                let x = (width - fitted.width()) / 2;
+                let y = (height - fitted.height()) / 2;
+                imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+                DynamicImage::ImageRgba8(canvas)
+            }
+        })
+    }
+}
+
+/// Scales `length` by `target / reference`, rounding and never returning zero.
+fn scale_dimension(length: u32, target: u32, reference: u32) -> u32 {
+    let scaled = (length as f64 * target as f64 / reference.max(1) as f64).round();
+    (scaled as u32).max(1)
+}
@@ -0,0 +1,578 @@
+//! GIMP XCF reader: layers, their properties and tiled pixel data.
+
+use crate::layers::{apply_mask, BlendMode, ByteReader, Layer, LayeredImage};
+use crate::metadata::ImageMetadata;
+use crate::ConversionError;
+use flate2::read::ZlibDecoder;
+use image::metadata::Orientation;
+use image::{Rgba, RgbaImage};
+use std::io::Read;
+
+// Property types
+const PROP_END: u32 = 0;
+const PROP_COLORMAP: u32 = 1;
+const PROP_OPACITY: u32 = 6;
+const PROP_MODE: u32 = 7;
+const PROP_VISIBLE: u32 = 8;
+const PROP_APPLY_MASK: u32 = 11;
+const PROP_OFFSETS: u32 = 15;
+const PROP_COMPRESSION: u32 = 17;
+const PROP_PARASITES: u32 = 21;
+const PROP_GROUP_ITEM: u32 = 29;
+const PROP_ITEM_PATH: u32 = 30;
+const PROP_FLOAT_OPACITY: u32 = 33;
+
+// Tile compression
+const COMPRESS_NONE: u8 = 0;
+const COMPRESS_RLE: u8 = 1;
+const COMPRESS_ZLIB: u8 = 2;
+
+const TILE_SIZE: u32 = 64;
+
+/// Sample encoding of the image's pixels.
+#[derive(Clone, Copy)]
+struct Precision {
+    bytes: usize,
+    float: bool,
+    /// Linear light rather than sRGB gamma.
+    linear: bool,
+}
+
+impl Precision {
+    const U8_GAMMA: Self = Self {
+        bytes: 1,
+        float: false,
+        linear: false,
+    };
+
+    fn from_file(value: u32, version: u32) -> Option<Self> {
+        let integer = |bytes, linear| {
+            Some(Self {
+                bytes,
+                float: false,
+                linear,
+            })
+        };
+        let float = |linear| {
+            Some(Self {
+                bytes: 4,
+                float: true,
+                linear,
+            })
+        };
+
+        if version == 4 {
+            return match value {
+                0 => integer(1, false),
+                1 => integer(2, true),
+                2 => integer(4, true),
+                4 => float(true),
+                _ => None,
+            };
+        }
+        match value {
+            100 => integer(1, true),
+            150 => integer(1, false),
+            200 => integer(2, true),
+            250 => integer(2, false),
+            300 => integer(4, true),
+            350 => integer(4, false),
+            // Versions 5 and 6 numbered float after half without a gap
+            500 if version < 7 => float(true),
+            550 if version < 7 => float(false),
+            600 if version >= 7 => float(true),
+            650 if version >= 7 => float(false),
+            _ => None,
+        }
+    }
+
+    /// Converts one big-endian sample to 8 bits, encoding linear colour
+    /// samples to sRGB gamma.
+    fn to_u8(self, sample: &[u8], color: bool) -> u8 {
+        let value = match (self.bytes, self.float) {
+            (1, _) => sample[0] as f32 / 255.0,
+            (2, false) => u16::from_be_bytes([sample[0], sample[1]]) as f32 / 65535.0,
+            (4, true) => f32::from_be_bytes([sample[0], sample[1], sample[2], sample[3]]),
+            _ => {
+                u32::from_be_bytes([sample[0], sample[1], sample[2], sample[3]]) as f32
+                    / u32::MAX as f32
+            }
+        };
+        let value = value.clamp(0.0, 1.0);
+        if self.linear && color {
+            encode_gamma(value)
+        } else {
+            (value * 255.0).round() as u8
+        }
+    }
+}
+
+fn encode_gamma(linear: f32) -> u8 {
+    let encoded = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+struct Document {
+    version: u32,
+    precision: Precision,
+    compression: u8,
+    colormap: Vec<u8>,
+}
+
+impl Document {
+    /// Offsets are 64-bit from version 11 on.
+    fn pointer(&self, r: &mut ByteReader) -> Result<usize, ConversionError> {
+        if self.version >= 11 {
+            Ok(r.u64()? as usize)
+        } else {
+            Ok(r.u32()? as usize)
+        }
+    }
+}
+
+/// A parsed layer with the group information needed to resolve visibility.
+struct XcfLayer {
+    layer: Layer,
+    /// Position in the layer tree: indices from the top level down.
+    path: Vec<u32>,
+    group: bool,
+}
+
+/// Parses an XCF file. Layer and group visibility, opacity, layer masks and
+/// the separable blend modes are honoured; layer effects (GEGL filters) and
+/// non-separable modes such as hue or colour are not.
+pub(crate) fn parse(data: &[u8]) -> Result<LayeredImage, ConversionError> {
+    let mut r = ByteReader::new(data, "XCF");
+    if r.bytes(9)? != b"gimp xcf " {
+        return Err(r.error("not a GIMP file"));
+    }
+    let version = match r.bytes(4)? {
+        b"file" => 0,
+        [b'v', digits @ ..] => std::str::from_utf8(digits)
+            .ok()
+            .and_then(|d| d.parse().ok())
+            .ok_or_else(|| r.error("bad version"))?,
+        _ => return Err(r.error("bad version")),
+    };
+    r.skip(1)?;
+
+    let width = r.u32()?;
+    let height = r.u32()?;
+    let _base_type = r.u32()?;
+    let precision = if version >= 4 {
+        let value = r.u32()?;
+        Precision::from_file(value, version)
+            .ok_or_else(|| ConversionError::UnsupportedFormat(format!("XCF precision {}", value)))?
+    } else {
+        Precision::U8_GAMMA
+    };
+
+    let mut doc = Document {
+        version,
+        precision,
+        compression: COMPRESS_RLE,
+        colormap: Vec::new(),
+    };
+    let mut metadata = ImageMetadata {
+        orientation: Some(Orientation::NoTransforms),
+        ..Default::default()
+    };
+
+    for (kind, payload) in read_props(&mut r)? {
+        let mut p = ByteReader::new(payload, "XCF");
+        match kind {
+            PROP_COMPRESSION => doc.compression = p.u8()?,
+            PROP_COLORMAP => {
+                let count = p.u32()? as usize;
+                doc.colormap = p.bytes(count * 3)?.to_vec();
+            }
+            PROP_PARASITES => {
+                while let Ok(name) = read_string(&mut p) {
+                    let _flags = p.u32()?;
+                    let size = p.u32()? as usize;
+                    let value = p.bytes(size)?;
+                    if name == "icc-profile" {
+                        metadata.icc = Some(value.to_vec());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut pointers = Vec::new();
+    loop {
+        match doc.pointer(&mut r)? {
+            0 => break,
+            pointer => pointers.push(pointer),
+        }
+    }
+
+    // Top-level items carry no path; their index counts only top-level items
+    let mut top_level = 0;
+    let mut layers = Vec::with_capacity(pointers.len());
+    for pointer in pointers {
+        r.seek(pointer)?;
+        let mut layer = read_layer(&mut r, &doc)?;
+        if layer.path.is_empty() {
+            layer.path.push(top_level);
+            top_level += 1;
+        }
+        layers.push(layer);
+    }
+
+    Ok(LayeredImage {
+        width,
+        height,
+        layers: resolve_groups(layers),
+        composite: None,
+        metadata,
+    })
+}
+
+/// Applies group visibility and opacity to their children, drops the group
+/// items themselves and returns the layers bottom to top (XCF lists them
+/// top to bottom).
+fn resolve_groups(layers: Vec<XcfLayer>) -> Vec<Layer> {
+    let groups: Vec<(Vec<u32>, bool, f32)> = layers
+        .iter()
+        .filter(|l| l.group)
+        .map(|l| (l.path.clone(), l.layer.visible, l.layer.opacity))
+        .collect();
+
+    let mut resolved: Vec<Layer> = layers
+        .into_iter()
+        .filter(|l| !l.group)
+        .map(|mut item| {
+            for (path, visible, opacity) in &groups {
+                if item.path.len() > path.len() && item.path.starts_with(path) {
+                    item.layer.visible &= visible;
+                    item.layer.opacity *= opacity;
+                }
+            }
+            item.layer
+        })
+        .collect();
+    resolved.reverse();
+    resolved
+}
+
+fn read_layer(r: &mut ByteReader, doc: &Document) -> Result<XcfLayer, ConversionError> {
+    let width = r.u32()?;
+    let height = r.u32()?;
+    let kind = r.u32()?;
+    let name = read_string(r)?;
+
+    let mut layer = Layer {
+        name,
+        pixels: RgbaImage::new(0, 0),
+        left: 0,
+        top: 0,
+        opacity: 1.0,
+        visible: true,
+        blend: BlendMode::Normal,
+    };
+    let mut path = Vec::new();
+    let mut group = false;
+    let mut apply_layer_mask = true;
+
+    for (prop, payload) in read_props(r)? {
+        let mut p = ByteReader::new(payload, "XCF");
+        match prop {
+            PROP_OPACITY => layer.opacity = p.u32()?.min(255) as f32 / 255.0,
+            PROP_FLOAT_OPACITY => layer.opacity = f32::from_bits(p.u32()?).clamp(0.0, 1.0),
+            PROP_VISIBLE => layer.visible = p.u32()? != 0,
+            PROP_MODE => layer.blend = blend_mode(p.u32()?),
+            PROP_OFFSETS => {
+                layer.left = p.i32()? as i64;
+                layer.top = p.i32()? as i64;
+            }
+            PROP_APPLY_MASK => apply_layer_mask = p.u32()? != 0,
+            PROP_GROUP_ITEM => group = true,
+            PROP_ITEM_PATH => {
+                while let Ok(index) = p.u32() {
+                    path.push(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let hierarchy = doc.pointer(r)?;
+    let mask = doc.pointer(r)?;
+
+    // A group's own pixels are just a cached projection of its children
+    if !group && width > 0 && height > 0 {
+        let (channels, indexed) = match kind {
+            0 => (3, false),
+            1 => (4, false),
+            2 => (1, false),
+            3 => (2, false),
+            4 => (1, true),
+            5 => (2, true),
+            other => return Err(r.error(format!("unknown layer type {}", other))),
+        };
+        let raw = read_hierarchy(r, doc, hierarchy, (width, height), channels)?;
+        layer.pixels = to_rgba(doc, &raw, width, height, channels, indexed);
+
+        if mask != 0 && apply_layer_mask {
+            r.seek(mask)?;
+            let mask_width = r.u32()?;
+            let mask_height = r.u32()?;
+            let _name = read_string(r)?;
+            read_props(r)?;
+            let mask_hierarchy = doc.pointer(r)?;
+            let raw = read_hierarchy(r, doc, mask_hierarchy, (mask_width, mask_height), 1)?;
+            let bytes = doc.precision.bytes;
+            let values: Vec<u8> = raw
+                .chunks_exact(bytes)
+                .map(|s| doc.precision.to_u8(s, false))
+                .collect();
+            apply_mask(
+                &mut layer.pixels,
+                &values,
+                (0, 0, mask_width, mask_height),
+                255,
+            );
+        }
+    }
+
+    Ok(XcfLayer { layer, path, group })
+}
+
+/// Reads a property list up to `PROP_END` as `(type, payload)` pairs.
+fn read_props<'a>(r: &mut ByteReader<'a>) -> Result<Vec<(u32, &'a [u8])>, ConversionError> {
+    let mut props = Vec::new();
+    loop {
+        let kind = r.u32()?;
+        let len = r.u32()? as usize;
+        if kind == PROP_END {
+            return Ok(props);
+        }
+        props.push((kind, r.bytes(len)?));
+    }
+}
+
+/// Reads a length-prefixed, NUL-terminated string.
+fn read_string(r: &mut ByteReader) -> Result<String, ConversionError> {
+    let len = r.u32()? as usize;
+    let bytes = r.bytes(len)?;
+    let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    Ok(String::from_utf8_lossy(text).into_owned())
+}
+
+// ============================================================================
+// Tiles
+// ============================================================================
+
+/// Reads the full-resolution level of a hierarchy into interleaved pixel
+/// bytes (in file byte order). The hierarchy must match the `width` x
+/// `height` layer or mask it belongs to, with `channels` samples per pixel.
+fn read_hierarchy(
+    r: &mut ByteReader,
+    doc: &Document,
+    pointer: usize,
+    (width, height): (u32, u32),
+    channels: usize,
+) -> Result<Vec<u8>, ConversionError> {
+    r.seek(pointer)?;
+    if (r.u32()?, r.u32()?) != (width, height) {
+        return Err(r.error("pixel data does not match the layer size"));
+    }
+    let bpp = r.u32()? as usize;
+    if bpp != channels * doc.precision.bytes {
+        return Err(r.error(format!("unexpected {} bytes per pixel", bpp)));
+    }
+    let level = doc.pointer(r)?;
+
+    r.seek(level)?;
+    let _level_width = r.u32()?;
+    let _level_height = r.u32()?;
+    let mut tiles = Vec::new();
+    loop {
+        match doc.pointer(r)? {
+            0 => break,
+            tile => tiles.push(tile),
+        }
+    }
+
+    let columns = width.div_ceil(TILE_SIZE);
+    let tile_count = (columns as usize)
+        .checked_mul(height.div_ceil(TILE_SIZE) as usize)
+        .filter(|&count| count <= tiles.len())
+        .ok_or_else(|| r.error("missing tiles"))?;
+
+    let too_large = || r.error("layer too large");
+    let row_bytes = (width as usize).checked_mul(bpp).ok_or_else(too_large)?;
+    let len = row_bytes
+        .checked_mul(height as usize)
+        .ok_or_else(too_large)?;
+    let mut out = Vec::new();
+    out.try_reserve_exact(len).map_err(|_| too_large())?;
+    out.resize(len, 0);
+    for (index, &tile) in tiles.iter().enumerate().take(tile_count) {
+        let tx = index as u32 % columns * TILE_SIZE;
+        let ty = index as u32 / columns * TILE_SIZE;
+        let tile_width = TILE_SIZE.min(width - tx) as usize;
+        let tile_height = TILE_SIZE.min(height - ty) as usize;
+
+        r.seek(tile)?;
+        let pixels =
+            decode_tile(doc, r.rest(), tile_width * tile_height, bpp).map_err(|e| r.error(e))?;
+
+        for (y, row) in pixels.chunks_exact(tile_width * bpp).enumerate() {
+            let start = (ty as usize + y) * row_bytes + tx as usize * bpp;
+            out[start..start + row.len()].copy_from_slice(row);
+        }
+    }
+    Ok(out)
+}
+
+/// Decodes one tile of `pixels` pixels with `bpp` bytes each.
+fn decode_tile(doc: &Document, src: &[u8], pixels: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    let len = pixels * bpp;
+    match doc.compression {
+        COMPRESS_NONE => src
+            .get(..len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| "truncated tile".to_string()),
+        COMPRESS_RLE => decode_rle(src, pixels, bpp),
+        COMPRESS_ZLIB => {
+            let mut out = vec![0u8; len];
+            ZlibDecoder::new(src)
+                .read_exact(&mut out)
+                .map_err(|e| e.to_string())?;
+            Ok(out)
+        }
+        other => Err(format!("unsupported tile compression {}", other)),
+    }
+}
+
+/// XCF run-length encoding: each byte of the pixel is a separate stream of
+/// `pixels` bytes, made of literal and repeated runs.
+fn decode_rle(src: &[u8], pixels: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    let truncated = || "truncated RLE tile".to_string();
+    let mut out = vec![0u8; pixels * bpp];
+    let mut pos = 0;
+
+    for channel in 0..bpp {
+        let mut i = 0;
+        while i < pixels {
+            let op = *src.get(pos).ok_or_else(truncated)?;
+            pos += 1;
+            let long_count = |pos: usize| -> Result<usize, String> {
+                let p = *src.get(pos).ok_or_else(truncated)? as usize;
+                let q = *src.get(pos + 1).ok_or_else(truncated)? as usize;
+                Ok(p * 256 + q)
+            };
+
+            if op >= 128 {
+                // Literal run
+                let count = if op == 128 {
+                    pos += 2;
+                    long_count(pos - 2)?
+                } else {
+                    256 - op as usize
+                };
+                let values = src.get(pos..pos + count).ok_or_else(truncated)?;
+                if i + count > pixels {
+                    return Err("RLE run overflows the tile".to_string());
+                }
+                for (k, &value) in values.iter().enumerate() {
+                    out[(i + k) * bpp + channel] = value;
+                }
+                pos += count;
+                i += count;
+            } else {
+                // Repeated value
+                let count = if op == 127 {
+                    pos += 2;
+                    long_count(pos - 2)?
+                } else {
+                    op as usize + 1
+                };
+                let value = *src.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                if i + count > pixels {
+                    return Err("RLE run overflows the tile".to_string());
+                }
+                for k in i..i + count {
+                    out[k * bpp + channel] = value;
+                }
+                i += count;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Converts interleaved layer pixels to 8-bit RGBA.
+fn to_rgba(
+    doc: &Document,
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    indexed: bool,
+) -> RgbaImage {
+    // Indexed layers always use one byte per channel
+    let bytes = if indexed { 1 } else { doc.precision.bytes };
+    let pixel_bytes = channels * bytes;
+    let precision = doc.precision;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let start = (y as usize * width as usize + x as usize) * pixel_bytes;
+        let Some(pixel) = raw.get(start..start + pixel_bytes) else {
+            return Rgba([0, 0, 0, 0]);
+        };
+        let sample =
+            |c: usize, color: bool| precision.to_u8(&pixel[c * bytes..(c + 1) * bytes], color);
+
+        match (channels, indexed) {
+            (_, true) => {
+                let index = pixel[0] as usize * 3;
+                let color = doc.colormap.get(index..index + 3).unwrap_or(&[0, 0, 0]);
+                let alpha = if channels == 2 { pixel[1] } else { 255 };
+                Rgba([color[0], color[1], color[2], alpha])
+            }
+            (1 | 2, false) => {
+                let v = sample(0, true);
+                let alpha = if channels == 2 { sample(1, false) } else { 255 };
+                Rgba([v, v, v, alpha])
+            }
+            _ => {
+                let alpha = if channels == 4 { sample(3, false) } else { 255 };
+                Rgba([sample(0, true), sample(1, true), sample(2, true), alpha])
+            }
+        }
+    })
+}
+
+/// Maps GIMP layer modes, both the legacy (pre-2.10) and current numbering.
+fn blend_mode(mode: u32) -> BlendMode {
+    match mode {
+        3 | 30 => BlendMode::Multiply,
+        4 | 31 => BlendMode::Screen,
+        // Legacy mode 5 is GIMP's old overlay, which behaved like soft light
+        5 | 19 | 45 => BlendMode::SoftLight,
+        23 => BlendMode::Overlay,
+        6 | 32 => BlendMode::Difference,
+        7 | 33 => BlendMode::Add,
+        8 | 34 => BlendMode::Subtract,
+        9 | 35 => BlendMode::Darken,
+        10 | 36 => BlendMode::Lighten,
+        15 | 41 => BlendMode::Divide,
+        16 | 42 => BlendMode::ColorDodge,
+        17 | 43 => BlendMode::ColorBurn,
+        18 | 44 => BlendMode::HardLight,
+        20 | 46 => BlendMode::GrainExtract,
+        21 | 47 => BlendMode::GrainMerge,
+        52 => BlendMode::Exclusion,
+        53 => BlendMode::LinearBurn,
+        _ => BlendMode::Normal,
+    }
+}
@@ -0,0 +1,98 @@
+// Cancelling a conversion while its external tool is still running. This lives
+// in its own test binary because it puts a fake `ffmpeg` first on PATH.
+#![cfg(unix)]
+
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Passes the availability check and fails the duration probe, then writes part
+// of the output and hangs like a long encode, recording its pid once it does
+const FAKE_FFMPEG: &str = r#"#!/bin/sh
+[ "$1" = "-version" ] && exit 0
+for last; do :; done
+[ "$last" = "$2" ] && exit 1
+printf partial > "$last"
+echo $$ > "$FAKE_FFMPEG_PID"
+exec sleep 30
+"#;
+
+fn setup_test_dir(test_name: &str) -> PathBuf {
+    let test_dir = std::env::temp_dir()
+        .join("fileflip_tests")
+        .join(test_name)
+        .join(format!("{}", std::process::id()));
+    let _ = fs::remove_dir_all(&test_dir);
+    fs::create_dir_all(&test_dir).expect("Failed to create test directory");
+    test_dir
+}
+
+#[test]
+fn test_cancel_kills_running_tool() {
+    let test_dir = setup_test_dir("cancel_running_tool");
+    let bin_dir = test_dir.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let ffmpeg = bin_dir.join("ffmpeg");
+    fs::write(&ffmpeg, FAKE_FFMPEG).unwrap();
+    fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let pid_file = test_dir.join("ffmpeg.pid");
+    let path = format!("{}:{}", bin_dir.display(), std::env::var("PATH").unwrap_or_default());
+    std::env::set_var("PATH", path);
+    std::env::set_var("FAKE_FFMPEG_PID", &pid_file);
+
+    let work_dir = test_dir.join("work");
+    fs::create_dir_all(&work_dir).unwrap();
+    let input = work_dir.join("song.wav");
+    fs::write(&input, "not really audio").unwrap();
+
+    let registry = Arc::new(fileflip_lib::JobRegistry::default());
+    let job = registry.start(Some("long".to_string()));
+
+    // Cancel from another thread once the tool is running
+    let canceller = {
+        let registry = registry.clone();
+        let pid_file = pid_file.clone();
+        thread::spawn(move || {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !pid_file.exists() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(20));
+            }
+            assert!(registry.cancel("long"), "Job was not running");
+        })
+    };
+
+    let started = Instant::now();
+    let reporter = fileflip_lib::ProgressReporter::new("long", Arc::new(|_| {}));
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "mp3".to_string(),
+        ..Default::default()
+    };
+    let result = job.run(|| {
+        fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, Some(&reporter))
+    });
+    registry.finish(&job);
+    canceller.join().unwrap();
+
+    assert!(!result.success, "Cancelled conversion reported success");
+    assert_eq!(result.error.as_deref(), Some("Conversion cancelled"));
+    assert!(started.elapsed() < Duration::from_secs(20), "Waited for the tool to finish");
+
+    // The killed tool was waited on, so no zombie is left behind
+    let pid = fs::read_to_string(&pid_file).unwrap().trim().to_string();
+    let ps = Command::new("ps").args(["-o", "stat=", "-p", &pid]).output().unwrap();
+    assert!(
+        String::from_utf8_lossy(&ps.stdout).trim().is_empty(),
+        "ffmpeg (pid {}) was not reaped",
+        pid
+    );
+
+    // Neither the partial output nor any temporary file is left next to the input
+    let left: Vec<OsString> = fs::read_dir(&work_dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(left, vec![OsString::from("song.wav")]);
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const CLI: &str = env!("CARGO_BIN_EXE_fileflip-cli");
+
+fn setup_test_dir(test_name: &str) -> PathBuf {
+    let test_dir = std::env::temp_dir()
+        .join("fileflip_cli_tests")
+        .join(test_name)
+        .join(format!("{}", std::process::id()));
+    let _ = fs::remove_dir_all(&test_dir);
+    fs::create_dir_all(&test_dir).expect("Failed to create test directory");
+    test_dir
+}
+
+fn run_cli(args: &[&str]) -> Output {
+    Command::new(CLI).args(args).output().expect("Failed to run fileflip-cli")
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_cli_convert_expands_globs() {
+    let test_dir = setup_test_dir("globs");
+    fs::write(test_dir.join("one.txt"), "First file").unwrap();
+    fs::write(test_dir.join("two.txt"), "Second file").unwrap();
+    fs::write(test_dir.join("skip.md"), "# Not matched").unwrap();
+    let out_dir = test_dir.join("out");
+
+    let pattern = path_arg(&test_dir.join("*.txt"));
+    let output = run_cli(&["convert", "--to", "html", &pattern, "-o", &path_arg(&out_dir)]);
+
+    assert_eq!(output.status.code(), Some(0), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(out_dir.join("one.html").exists());
+    assert!(out_dir.join("two.html").exists());
+    assert!(!out_dir.join("skip.html").exists());
+}
+
+#[test]
+fn test_cli_convert_json_output() {
+    let test_dir = setup_test_dir("json");
+    let input = test_dir.join("notes.md");
+    fs::write(&input, "# Title\n\nBody").unwrap();
+
+    let output = run_cli(&["convert", "--to", "html", &path_arg(&input), "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout is not JSON");
+    let results = results.as_array().expect("expected a JSON array");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["success"], true);
+    let output_path = results[0]["output_path"].as_str().unwrap();
+    assert!(Path::new(output_path).exists(), "Reported output missing: {}", output_path);
+}
+
+#[test]
+fn test_cli_exit_codes() {
+    let test_dir = setup_test_dir("exit_codes");
+    let good = test_dir.join("good.txt");
+    fs::write(&good, "Hello").unwrap();
+    let missing = test_dir.join("missing.txt");
+
+    // Any failed conversion fails the run, and is reported in the JSON
+    let output = run_cli(&["convert", "--to", "html", &path_arg(&good), &path_arg(&missing), "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(results[0]["success"], true);
+    assert_eq!(results[1]["success"], false);
+
+    // A pattern matching nothing is a usage error
+    let pattern = path_arg(&test_dir.join("*.docx"));
+    assert_eq!(run_cli(&["convert", "--to", "pdf", &pattern]).status.code(), Some(2));
+
+    // So are missing arguments and out-of-range values
+    assert_eq!(run_cli(&["convert", &path_arg(&good)]).status.code(), Some(2));
+    assert_eq!(
+        run_cli(&["convert", "--to", "jpg", "--quality", "0", &path_arg(&good)]).status.code(),
+        Some(2)
+    );
+
+    assert_eq!(run_cli(&["supports", "png", "jpg"]).status.code(), Some(0));
+    assert_eq!(run_cli(&["supports", "txt", "mp3"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_cli_formats_json() {
+    let output = run_cli(&["formats", ".png", "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let formats: Vec<String> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(formats.contains(&"jpg".to_string()), "{:?}", formats);
+    assert!(!formats.contains(&"png".to_string()), "{:?}", formats);
+
+    assert_eq!(run_cli(&["formats", "nope"]).status.code(), Some(1));
+}
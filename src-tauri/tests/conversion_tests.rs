@@ -114,3 +114,2400 @@ fn test_rtf_to_txt() {
     let content = fs::read_to_string(&output).unwrap();
     assert!(content.contains("Hello") || content.contains("World"), "RTF not extracted properly: {}", content);
 }
+
+fn conversion_options(format: &str) -> fileflip_lib::ConversionOptions {
+    fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_batch_conversion_preserves_order() {
+    let test_dir = setup_test_dir("batch_order");
+    let mut inputs = Vec::new();
+    for i in 0..6 {
+        let input = test_dir.join(format!("doc{}.txt", i));
+        fs::write(&input, format!("Document number {}", i)).unwrap();
+        inputs.push(input.to_string_lossy().to_string());
+    }
+    // A missing file in the middle should fail without affecting the others
+    inputs.insert(3, test_dir.join("missing.txt").to_string_lossy().to_string());
+
+    let options = fileflip_lib::BatchOptions {
+        conversion: conversion_options("html"),
+        max_cpu_workers: Some(3),
+        max_process_workers: Some(1),
+    };
+
+    let batch = fileflip_lib::convert_batch(&inputs, &options);
+
+    assert_eq!(batch.results.len(), inputs.len());
+    assert_eq!(batch.summary.total, 7);
+    assert_eq!(batch.summary.succeeded, 6);
+    assert_eq!(batch.summary.failed, 1);
+    assert!(!batch.results[3].success, "Missing input should fail");
+
+    for (input, result) in inputs.iter().zip(&batch.results) {
+        if let Some(output) = &result.output_path {
+            let expected_stem = std::path::Path::new(input).file_stem().unwrap();
+            assert_eq!(std::path::Path::new(output).file_stem().unwrap(), expected_stem,
+                       "Results out of order");
+        }
+    }
+}
+
+#[test]
+fn test_batch_conversion_same_stem_does_not_collide() {
+    let test_dir = setup_test_dir("batch_same_stem");
+    let txt = test_dir.join("notes.txt");
+    let md = test_dir.join("notes.md");
+    fs::write(&txt, "From text").unwrap();
+    fs::write(&md, "# From markdown").unwrap();
+
+    let inputs = vec![
+        txt.to_string_lossy().to_string(),
+        md.to_string_lossy().to_string(),
+    ];
+    let options = fileflip_lib::BatchOptions {
+        conversion: conversion_options("html"),
+        max_cpu_workers: None,
+        max_process_workers: None,
+    };
+
+    let batch = fileflip_lib::convert_batch(&inputs, &options);
+
+    assert_eq!(batch.summary.succeeded, 2, "Batch failed: {:?}", batch.results);
+    assert_ne!(batch.results[0].output_path, batch.results[1].output_path,
+               "Both inputs were written to the same output file");
+}
+
+#[test]
+fn test_cancelled_job_removes_output() {
+    let test_dir = setup_test_dir("cancelled_job");
+    let input = test_dir.join("test.txt");
+    let output = test_dir.join("test.html");
+    fs::write(&input, "Never converted").unwrap();
+
+    let registry = fileflip_lib::JobRegistry::default();
+    let job = registry.start(None);
+    assert!(registry.cancel(job.id()), "Running job should be cancellable");
+    assert!(!registry.cancel("no-such-job"), "Unknown job ids should be rejected");
+
+    let result = job.run(|| {
+        fileflip_lib::convert_file(
+            input.to_string_lossy().to_string(),
+            "html".to_string(),
+            90,
+            None,
+            false,
+            false,
+            None,
+        )
+    });
+    registry.finish(&job);
+
+    assert!(!result.success, "Cancelled conversion reported success");
+    assert_eq!(result.error.as_deref(), Some("Conversion cancelled"));
+    assert!(!output.exists(), "Partial output was not cleaned up");
+}
+
+#[test]
+fn test_cancelled_batch_reports_every_file() {
+    let test_dir = setup_test_dir("cancelled_batch");
+    let inputs: Vec<String> = (0..4)
+        .map(|i| {
+            let input = test_dir.join(format!("doc{}.txt", i));
+            fs::write(&input, "Never converted").unwrap();
+            input.to_string_lossy().to_string()
+        })
+        .collect();
+
+    let registry = fileflip_lib::JobRegistry::default();
+    let job = registry.start(Some("batch".to_string()));
+    registry.cancel("batch");
+
+    let options = fileflip_lib::BatchOptions {
+        conversion: conversion_options("html"),
+        max_cpu_workers: Some(2),
+        max_process_workers: None,
+    };
+    let batch = job.run(|| fileflip_lib::convert_batch(&inputs, &options));
+
+    assert_eq!(batch.results.len(), 4);
+    assert_eq!(batch.summary.failed, 4);
+    assert!(batch.results.iter().all(|r| r.error.as_deref() == Some("Conversion cancelled")));
+}
+
+#[test]
+fn test_failed_overwrite_keeps_existing_output() {
+    let test_dir = setup_test_dir("atomic_overwrite");
+    let input = test_dir.join("photo.png");
+    let existing = test_dir.join("photo.jpg");
+    fs::write(&input, b"not really a png").unwrap();
+    fs::write(&existing, b"previous good copy").unwrap();
+
+    let result = fileflip_lib::convert_file(
+        input.to_string_lossy().to_string(),
+        "jpg".to_string(),
+        90,
+        None,
+        false,
+        true,
+        None,
+    );
+
+    assert!(!result.success, "Corrupt input should fail to convert");
+    assert_eq!(fs::read(&existing).unwrap(), b"previous good copy",
+               "Failed conversion replaced the existing output");
+}
+
+#[test]
+fn test_successful_conversion_leaves_no_temp_files() {
+    let test_dir = setup_test_dir("atomic_success");
+    let input = test_dir.join("notes.txt");
+    fs::write(&input, "Hello").unwrap();
+
+    let result = fileflip_lib::convert_file(
+        input.to_string_lossy().to_string(),
+        "html".to_string(),
+        90,
+        None,
+        false,
+        false,
+        None,
+    );
+
+    assert!(result.success, "Conversion failed: {:?}", result.error);
+    let mut names: Vec<String> = fs::read_dir(&test_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["notes.html", "notes.txt"]);
+}
+
+// Writes a small JPEG whose EXIF holds a camera make, a GPS position and a body serial number
+fn write_jpeg_with_private_exif(path: &std::path::Path) {
+    use exif::{Field, In, Rational, Tag, Value};
+    use image::ImageEncoder;
+
+    let fields = [
+        Field { tag: Tag::Make, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"Acme".to_vec()]) },
+        Field { tag: Tag::BodySerialNumber, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"SN12345".to_vec()]) },
+        Field { tag: Tag::GPSLatitudeRef, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"N".to_vec()]) },
+        Field {
+            tag: Tag::GPSLatitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![Rational { num: 51, denom: 1 }, Rational { num: 30, denom: 1 }, Rational { num: 0, denom: 1 }]),
+        },
+    ];
+    let mut writer = exif::experimental::Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut exif_data = std::io::Cursor::new(Vec::new());
+    writer.write(&mut exif_data, false).unwrap();
+
+    let img = image::RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50]));
+    let mut file = fs::File::create(path).unwrap();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, 90);
+    encoder.set_exif_metadata(exif_data.into_inner()).unwrap();
+    encoder.write_image(img.as_raw(), 8, 8, image::ExtendedColorType::Rgb8).unwrap();
+}
+
+// EXIF tags present in an image file, or none if it carries no EXIF
+fn exif_tags(path: &str) -> Vec<exif::Tag> {
+    use image::ImageDecoder;
+
+    let mut decoder = image::ImageReader::open(path)
+        .unwrap()
+        .with_guessed_format()
+        .unwrap()
+        .into_decoder()
+        .unwrap();
+    match decoder.exif_metadata().unwrap() {
+        Some(raw) => exif::Reader::new()
+            .read_raw(raw)
+            .unwrap()
+            .fields()
+            .map(|f| f.tag)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn convert_image(input: &std::path::Path, format: &str, preserve: bool, strip_private: bool) -> String {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        preserve_metadata: preserve,
+        strip_private_metadata: strip_private,
+        overwrite_existing: true,
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Conversion failed: {:?}", result.error);
+    result.output_path.unwrap()
+}
+
+#[test]
+fn test_image_metadata_stripped_by_default() {
+    let test_dir = setup_test_dir("metadata_strip");
+    let input = test_dir.join("photo.jpg");
+    write_jpeg_with_private_exif(&input);
+
+    for format in ["jpg", "png", "webp"] {
+        let output = convert_image(&input, format, false, false);
+        assert!(exif_tags(&output).is_empty(), "EXIF left in stripped {} output", format);
+    }
+}
+
+#[test]
+fn test_image_metadata_preserved() {
+    let test_dir = setup_test_dir("metadata_preserve");
+    let input = test_dir.join("photo.jpg");
+    write_jpeg_with_private_exif(&input);
+
+    let output = convert_image(&input, "png", true, false);
+    let tags = exif_tags(&output);
+    assert!(tags.contains(&exif::Tag::Make), "Make not preserved: {:?}", tags);
+    assert!(tags.contains(&exif::Tag::GPSLatitude), "GPS not preserved: {:?}", tags);
+}
+
+const TEST_ICC: &[u8] = b"not a real ICC profile, but carried over byte for byte";
+const TEST_XMP: &[u8] = b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF/></x:xmpmeta>";
+
+// Writes the JPEG from `write_jpeg_with_private_exif`, plus an ICC profile and an XMP packet
+fn write_jpeg_with_all_metadata(path: &std::path::Path) {
+    use image::{ImageDecoder, ImageEncoder};
+
+    write_jpeg_with_private_exif(path);
+    let mut decoder = image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(fs::read(path).unwrap())).unwrap();
+    let exif_data = decoder.exif_metadata().unwrap().unwrap();
+
+    // The TIFF decoder caps tag sizes relative to the image size, so this one is larger
+    let img = image::RgbImage::from_pixel(32, 32, image::Rgb([200, 100, 50]));
+    let mut data = Vec::new();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 90);
+    encoder.set_exif_metadata(exif_data).unwrap();
+    encoder.set_icc_profile(TEST_ICC.to_vec()).unwrap();
+    encoder.write_image(img.as_raw(), 32, 32, image::ExtendedColorType::Rgb8).unwrap();
+
+    // XMP goes in its own APP1 segment right after the start-of-image marker
+    let mut segment = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+    segment.extend_from_slice(TEST_XMP);
+    let mut xmp = vec![0xFF, 0xE1];
+    xmp.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+    xmp.extend_from_slice(&segment);
+    data.splice(2..2, xmp);
+    fs::write(path, data).unwrap();
+}
+
+// EXIF tags found by reading the file as a TIFF or HEIF container
+fn container_exif_tags(path: &str) -> Vec<exif::Tag> {
+    let mut reader = std::io::BufReader::new(fs::File::open(path).unwrap());
+    match exif::Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => exif.fields().map(|f| f.tag).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn test_image_metadata_preserved_in_tiff() {
+    use image::ImageDecoder;
+
+    let test_dir = setup_test_dir("metadata_tiff");
+    let input = test_dir.join("photo.jpg");
+    write_jpeg_with_all_metadata(&input);
+
+    let output = convert_image(&input, "tiff", true, false);
+    let tags = container_exif_tags(&output);
+    assert!(tags.contains(&exif::Tag::Make), "Make not preserved: {:?}", tags);
+    assert!(tags.contains(&exif::Tag::GPSLatitude), "GPS not preserved: {:?}", tags);
+
+    let mut decoder = image::ImageReader::open(&output).unwrap().with_guessed_format().unwrap().into_decoder().unwrap();
+    assert_eq!(decoder.icc_profile().unwrap().as_deref(), Some(TEST_ICC));
+    assert_eq!(decoder.xmp_metadata().unwrap().as_deref(), Some(TEST_XMP));
+    assert_eq!(image::open(&output).unwrap().to_rgb8().get_pixel(4, 4).0[0] / 10, 20, "Pixels not kept");
+
+    // Stripping keeps only the colour profile
+    let output = convert_image(&input, "tiff", false, false);
+    assert!(!container_exif_tags(&output).contains(&exif::Tag::Make), "EXIF left in stripped TIFF");
+    let data = fs::read(&output).unwrap();
+    assert!(contains_bytes(&data, TEST_ICC) && !contains_bytes(&data, TEST_XMP));
+}
+
+#[test]
+fn test_image_metadata_preserved_in_avif() {
+    let test_dir = setup_test_dir("metadata_avif");
+    let input = test_dir.join("photo.jpg");
+    write_jpeg_with_all_metadata(&input);
+
+    let output = convert_image(&input, "avif", true, true);
+    let tags = container_exif_tags(&output);
+    assert!(tags.contains(&exif::Tag::Make), "Make not preserved: {:?}", tags);
+    assert!(!tags.contains(&exif::Tag::GPSLatitude), "GPS left in: {:?}", tags);
+
+    let data = fs::read(&output).unwrap();
+    assert!(contains_bytes(&data, TEST_ICC), "ICC profile not preserved");
+    // XMP is dropped along with the private EXIF fields, but kept otherwise
+    assert!(!contains_bytes(&data, TEST_XMP), "XMP left in");
+    let output = convert_image(&input, "avif", true, false);
+    assert!(contains_bytes(&fs::read(&output).unwrap(), TEST_XMP), "XMP not preserved");
+}
+
+#[test]
+fn test_image_metadata_preserved_without_private_fields() {
+    let test_dir = setup_test_dir("metadata_strip_private");
+    let input = test_dir.join("photo.jpg");
+    write_jpeg_with_private_exif(&input);
+
+    for format in ["jpg", "png", "webp"] {
+        let output = convert_image(&input, format, true, true);
+        let tags = exif_tags(&output);
+        assert!(tags.contains(&exif::Tag::Make), "Make not preserved in {}: {:?}", format, tags);
+        assert!(
+            !tags.iter().any(|t| t.context() == exif::Context::Gps),
+            "GPS left in {}: {:?}",
+            format,
+            tags
+        );
+        assert!(!tags.contains(&exif::Tag::BodySerialNumber), "Serial left in {}", format);
+    }
+}
+
+#[test]
+fn test_document_title_and_author_preserved() {
+    let test_dir = setup_test_dir("metadata_document");
+    let input = test_dir.join("notes.md");
+    fs::write(&input, "---\ntitle: Quarterly Report\nauthor: Jane Doe\n---\n\nBody text.").unwrap();
+
+    let output = test_dir.join("kept.html");
+    fileflip_lib::convert_document_with_metadata(&input, &output, "md", "html", true).unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    assert!(content.contains("<title>Quarterly Report</title>"), "Title not kept: {}", content);
+    assert!(content.contains("<meta name=\"author\" content=\"Jane Doe\">"), "Author not kept: {}", content);
+    assert!(!content.contains("title:"), "Front matter rendered as content: {}", content);
+
+    let output = test_dir.join("stripped.html");
+    fileflip_lib::convert_document_with_metadata(&input, &output, "md", "html", false).unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    assert!(content.contains("<title>notes</title>"), "Title not reset: {}", content);
+    assert!(!content.contains("Jane Doe"), "Author leaked: {}", content);
+}
+
+fn test_file(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_files").join(name)
+}
+
+fn convert_with_transform(
+    test_dir: &std::path::Path,
+    input: &str,
+    format: &str,
+    transform: fileflip_lib::ImageTransform,
+) -> fileflip_lib::ConversionResult {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        output_dir: Some(test_dir.to_string_lossy().to_string()),
+        overwrite_existing: true,
+        transform,
+        ..Default::default()
+    };
+    fileflip_lib::convert_file_with_options(&test_file(input).to_string_lossy(), &options, None)
+}
+
+fn output_dimensions(result: &fileflip_lib::ConversionResult) -> (u32, u32) {
+    assert!(result.success, "Conversion failed: {:?}", result.error);
+    image::image_dimensions(result.output_path.as_ref().unwrap()).unwrap()
+}
+
+#[test]
+fn test_transform_max_bounds_keep_aspect_ratio() {
+    let test_dir = setup_test_dir("transform_max_bounds");
+
+    // 200x150 source
+    let transform = fileflip_lib::ImageTransform {
+        max_width: Some(100),
+        ..Default::default()
+    };
+    let result = convert_with_transform(&test_dir, "test_image.jpg", "png", transform);
+    assert_eq!(output_dimensions(&result), (100, 75));
+
+    // Bounds larger than the image never upscale
+    let transform = fileflip_lib::ImageTransform {
+        max_width: Some(1000),
+        max_height: Some(1000),
+        ..Default::default()
+    };
+    let result = convert_with_transform(&test_dir, "test_image.bmp", "png", transform);
+    assert_eq!(output_dimensions(&result), (80, 80));
+}
+
+#[test]
+fn test_transform_exact_size_modes() {
+    let test_dir = setup_test_dir("transform_exact_size");
+
+    for (mode, expected) in [
+        (fileflip_lib::ResizeMode::Fit, (64, 48)),
+        (fileflip_lib::ResizeMode::Fill, (64, 64)),
+        (fileflip_lib::ResizeMode::Pad, (64, 64)),
+        (fileflip_lib::ResizeMode::Stretch, (64, 64)),
+    ] {
+        let transform = fileflip_lib::ImageTransform {
+            width: Some(64),
+            height: Some(64),
+            resize_mode: mode,
+            filter: fileflip_lib::ResampleFilter::Triangle,
+            ..Default::default()
+        };
+        let result = convert_with_transform(&test_dir, "test_image.jpg", "png", transform);
+        assert_eq!(output_dimensions(&result), expected, "Wrong size for {:?}", mode);
+
+        if mode == fileflip_lib::ResizeMode::Pad {
+            let img = image::open(result.output_path.unwrap()).unwrap().to_rgba8();
+            assert_eq!(img.get_pixel(0, 0)[3], 0, "Padding should be transparent");
+            assert_eq!(img.get_pixel(32, 32)[3], 255, "Image content should be opaque");
+        }
+    }
+}
+
+#[test]
+fn test_transform_crop_rotate_flip() {
+    let test_dir = setup_test_dir("transform_crop_rotate");
+
+    let transform = fileflip_lib::ImageTransform {
+        crop: Some(fileflip_lib::CropRect { x: 10, y: 10, width: 120, height: 60 }),
+        rotate: 90,
+        flip_horizontal: true,
+        ..Default::default()
+    };
+    let result = convert_with_transform(&test_dir, "test_image.jpg", "png", transform);
+    assert_eq!(output_dimensions(&result), (60, 120));
+
+    // Out-of-bounds crops and odd rotations are rejected
+    let transform = fileflip_lib::ImageTransform {
+        crop: Some(fileflip_lib::CropRect { x: 50, y: 50, width: 100, height: 100 }),
+        ..Default::default()
+    };
+    let result = convert_with_transform(&test_dir, "test_image.png", "jpg", transform);
+    assert!(!result.success, "Crop outside the image should fail");
+
+    let transform = fileflip_lib::ImageTransform {
+        rotate: 45,
+        ..Default::default()
+    };
+    let result = convert_with_transform(&test_dir, "test_image.png", "jpg", transform);
+    assert!(!result.success, "45 degree rotation should fail");
+}
+
+#[test]
+fn test_transform_auto_orient() {
+    use exif::{Field, In, Tag, Value};
+    use image::ImageEncoder;
+
+    let test_dir = setup_test_dir("transform_auto_orient");
+    let input = test_dir.join("rotated.jpg");
+
+    // 8x4 pixels tagged "rotate 90 degrees clockwise to display"
+    let orientation = Field { tag: Tag::Orientation, ifd_num: In::PRIMARY, value: Value::Short(vec![6]) };
+    let mut writer = exif::experimental::Writer::new();
+    writer.push_field(&orientation);
+    let mut exif_data = std::io::Cursor::new(Vec::new());
+    writer.write(&mut exif_data, false).unwrap();
+
+    let img = image::RgbImage::from_pixel(8, 4, image::Rgb([10, 20, 30]));
+    let mut file = fs::File::create(&input).unwrap();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, 90);
+    encoder.set_exif_metadata(exif_data.into_inner()).unwrap();
+    encoder.write_image(img.as_raw(), 8, 4, image::ExtendedColorType::Rgb8).unwrap();
+    drop(file);
+
+    let output = convert_image(&input, "jpg", true, false);
+    assert_eq!(image::image_dimensions(&output).unwrap(), (4, 8));
+
+    // The preserved EXIF must no longer ask viewers to rotate again
+    let mut decoder = image::ImageReader::open(&output)
+        .unwrap()
+        .with_guessed_format()
+        .unwrap()
+        .into_decoder()
+        .unwrap();
+    assert_eq!(
+        image::ImageDecoder::orientation(&mut decoder).unwrap(),
+        image::metadata::Orientation::NoTransforms
+    );
+}
+
+fn convert_to_webp(test_dir: &std::path::Path, input: &std::path::Path, quality: u8, lossless: bool) -> String {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "webp".to_string(),
+        quality,
+        output_dir: Some(test_dir.to_string_lossy().to_string()),
+        lossless,
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "WebP conversion failed: {:?}", result.error);
+    result.output_path.unwrap()
+}
+
+#[test]
+fn test_webp_lossy_honours_quality() {
+    let test_dir = setup_test_dir("webp_lossy");
+    let input = test_file("test_image.jpg");
+
+    let low = convert_to_webp(&test_dir, &input, 30, false);
+    let high = convert_to_webp(&test_dir, &input, 95, false);
+    let lossless = convert_to_webp(&test_dir, &input, 90, true);
+
+    let size = |p: &str| fs::metadata(p).unwrap().len();
+    assert!(size(&low) < size(&high), "Lower quality should produce a smaller file");
+    assert!(size(&high) < size(&lossless), "Lossy WebP should be smaller than lossless");
+    assert_eq!(image::image_dimensions(&low).unwrap(), (200, 150));
+}
+
+#[test]
+fn test_webp_keeps_alpha() {
+    let test_dir = setup_test_dir("webp_alpha");
+    let input = test_dir.join("transparent.png");
+
+    let mut img = image::RgbaImage::from_pixel(32, 32, image::Rgba([0, 128, 255, 255]));
+    for x in 0..16 {
+        for y in 0..32 {
+            img.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+        }
+    }
+    img.save(&input).unwrap();
+
+    for lossless in [false, true] {
+        let output = convert_to_webp(&test_dir, &input, 80, lossless);
+        let decoded = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(4, 4)[3], 0, "Transparent area lost (lossless: {})", lossless);
+        assert_eq!(decoded.get_pixel(28, 28)[3], 255, "Opaque area lost (lossless: {})", lossless);
+        if lossless {
+            assert_eq!(decoded, img, "Lossless WebP should round-trip exactly");
+        }
+    }
+}
+
+fn convert_to(input: &std::path::Path, format: &str) -> String {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Conversion to {} failed: {:?}", format, result.error);
+    result.output_path.unwrap()
+}
+
+#[test]
+fn test_netpbm_tga_qoi_round_trip() {
+    let test_dir = setup_test_dir("extra_formats");
+    let input = test_dir.join("source.png");
+    let img = image::RgbaImage::from_fn(24, 16, |x, y| {
+        image::Rgba([(x * 10) as u8, (y * 15) as u8, 200, if x < 12 { 255 } else { 96 }])
+    });
+    img.save(&input).unwrap();
+
+    for format in ["pam", "tga", "qoi"] {
+        let output = convert_to(&input, format);
+        assert!(output.ends_with(&format!(".{}", format)));
+        let decoded = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(decoded, img, "{} should keep RGBA exactly", format.to_uppercase());
+    }
+
+    let ppm = image::open(convert_to(&input, "ppm")).unwrap();
+    assert_eq!(ppm.color(), image::ColorType::Rgb8);
+    assert_eq!(ppm.to_rgb8(), image::DynamicImage::ImageRgba8(img.clone()).to_rgb8());
+
+    let pgm = image::open(convert_to(&input, "pgm")).unwrap();
+    assert_eq!(pgm.color(), image::ColorType::L8);
+
+    // PBM is thresholded to pure black and white
+    let pbm = image::open(convert_to(&input, "pbm")).unwrap().to_luma8();
+    assert_eq!(pbm.dimensions(), (24, 16));
+    assert!(pbm.pixels().all(|p| p[0] == 0 || p[0] == 255));
+
+    // Netpbm and JFIF files are accepted as input
+    let jfif = test_dir.join("photo.jfif");
+    fs::copy(test_file("test_image.jpg"), &jfif).unwrap();
+    let from_jfif = convert_to(&jfif, "ppm");
+    let from_ppm = convert_to(std::path::Path::new(&from_jfif), "png");
+    assert_eq!(image::image_dimensions(&from_ppm).unwrap(), (200, 150));
+    assert!(fileflip_lib::is_conversion_supported("jfif".to_string(), "qoi".to_string()));
+}
+
+#[test]
+fn test_hdr_is_tone_mapped_for_8bit_formats() {
+    let test_dir = setup_test_dir("hdr_tone_mapping");
+    let input = test_dir.join("scene.hdr");
+
+    // Linear radiance from 0 to ~16, well past SDR white at 1.0
+    let img = image::Rgb32FImage::from_fn(64, 1, |x, _| {
+        let v = x as f32 / 4.0;
+        image::Rgb([v, v, v])
+    });
+    image::DynamicImage::ImageRgb32F(img).save(&input).unwrap();
+
+    let png = image::open(convert_to(&input, "png")).unwrap().to_luma8();
+    let row: Vec<u8> = png.pixels().map(|p| p[0]).collect();
+    assert_eq!(row[0], 0);
+    assert!(row.windows(2).all(|w| w[0] <= w[1]), "Tone mapping should be monotonic: {:?}", row);
+    // Clamping would turn everything from 1.0 up into white
+    assert!(row[4] > 100 && row[4] < 230, "1.0 should map below white, got {}", row[4]);
+    assert!(row.iter().filter(|&&v| v == 255).count() < 16);
+
+    // HDR to EXR keeps the linear values
+    let exr = image::open(convert_to(&input, "exr")).unwrap().to_rgb32f();
+    assert!((exr.get_pixel(40, 0)[0] - 10.0).abs() < 0.1);
+
+    // 8-bit sources are linearised, so mid-grey is ~0.22 in linear light
+    let grey = test_dir.join("grey.png");
+    image::RgbImage::from_pixel(4, 4, image::Rgb([128, 128, 128])).save(&grey).unwrap();
+    let linear = image::open(convert_to(&grey, "exr")).unwrap().to_rgb32f();
+    assert!((linear.get_pixel(0, 0)[0] - 0.2158).abs() < 0.01);
+}
+
+/// Reads the directory of an ICO file: (width, height) of every entry.
+fn ico_entries(path: &str) -> Vec<(u32, u32)> {
+    let data = fs::read(path).unwrap();
+    let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+    (0..count)
+        .map(|i| {
+            let entry = &data[6 + i * 16..];
+            let size = |b: u8| if b == 0 { 256 } else { b as u32 };
+            (size(entry[0]), size(entry[1]))
+        })
+        .collect()
+}
+
+#[test]
+fn test_ico_packs_sizes_and_pads() {
+    let test_dir = setup_test_dir("ico_sizes");
+    let input = test_dir.join("wide.png");
+    image::RgbaImage::from_pixel(200, 100, image::Rgba([255, 0, 0, 255])).save(&input).unwrap();
+
+    let output = convert_to(&input, "ico");
+    assert_eq!(
+        ico_entries(&output),
+        vec![(16, 16), (24, 24), (32, 32), (48, 48), (64, 64), (128, 128), (256, 256)]
+    );
+
+    // The largest frame keeps the 2:1 aspect ratio on a transparent square
+    let largest = image::open(&output).unwrap().to_rgba8();
+    assert_eq!(largest.dimensions(), (256, 256));
+    assert_eq!(largest.get_pixel(128, 10)[3], 0);
+    assert_eq!(*largest.get_pixel(128, 128), image::Rgba([255, 0, 0, 255]));
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "ico".to_string(),
+        overwrite_existing: true,
+        icon: fileflip_lib::IconOptions { sizes: vec![48, 16, 300] },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(!result.success, "256 is the largest ICO size");
+
+    let options = fileflip_lib::ConversionOptions {
+        icon: fileflip_lib::IconOptions { sizes: vec![48, 16] },
+        ..options
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "ICO failed: {:?}", result.error);
+    assert_eq!(ico_entries(result.output_path.as_ref().unwrap()), vec![(16, 16), (48, 48)]);
+}
+
+#[test]
+fn test_svg_to_icns() {
+    let test_dir = setup_test_dir("svg_icns");
+    let input = test_dir.join("logo.svg");
+    fs::write(
+        &input,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16"><circle cx="8" cy="8" r="8" fill="blue"/></svg>"#,
+    )
+    .unwrap();
+
+    let output = convert_to(&input, "icns");
+    assert!(output.ends_with(".icns"));
+
+    let data = fs::read(&output).unwrap();
+    assert_eq!(&data[..4], b"icns");
+    assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize, data.len());
+    let mut types = Vec::new();
+    let mut pos = 8;
+    while pos < data.len() {
+        types.push(String::from_utf8_lossy(&data[pos..pos + 4]).to_string());
+        pos += u32::from_be_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+    }
+    for kind in ["icp4", "icp5", "ic07", "ic08", "ic09", "ic10"] {
+        assert!(types.iter().any(|t| t == kind), "missing {} in {:?}", kind, types);
+    }
+
+    // The SVG is rendered at 1024 px rather than scaled up from 16 px, so the
+    // circle's edge is a thin anti-aliased ring
+    let png = image::open(convert_to(std::path::Path::new(&output), "png")).unwrap().to_rgba8();
+    assert_eq!(png.dimensions(), (1024, 1024));
+    let partial = (0..1024).filter(|&x| (1..255).contains(&png.get_pixel(x, 512)[3])).count();
+    assert!(partial <= 4, "{} blurred edge pixels", partial);
+}
+
+fn convert_svg(input: &std::path::Path, format: &str, svg: fileflip_lib::SvgOptions) -> image::RgbaImage {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        svg,
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "SVG to {} failed: {:?}", format, result.error);
+    image::open(result.output_path.unwrap()).unwrap().to_rgba8()
+}
+
+#[test]
+fn test_svg_render_size_and_background() {
+    let test_dir = setup_test_dir("svg_render");
+    let input = test_dir.join("shape.svg");
+    fs::write(
+        &input,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20"><rect x="10" y="0" width="20" height="20" fill="red"/></svg>"#,
+    )
+    .unwrap();
+
+    // Transparent areas become white in JPEG rather than black
+    let jpg = convert_svg(&input, "jpg", Default::default());
+    assert_eq!(jpg.dimensions(), (40, 20));
+    assert!(jpg.get_pixel(2, 10).0[..3].iter().all(|&c| c > 245), "{:?}", jpg.get_pixel(2, 10));
+
+    // PNG keeps transparency unless a background is given
+    let png = convert_svg(&input, "png", Default::default());
+    assert_eq!(png.get_pixel(2, 10)[3], 0);
+    assert_eq!(png.get_pixel(20, 10).0, [255, 0, 0, 255]);
+
+    let png = convert_svg(
+        &input,
+        "png",
+        fileflip_lib::SvgOptions {
+            width: Some(80),
+            background: Some("#00f".to_string()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(png.dimensions(), (80, 40));
+    assert_eq!(png.get_pixel(4, 20).0, [0, 0, 255, 255]);
+
+    // Width and height together fit inside the box
+    let png = convert_svg(
+        &input,
+        "png",
+        fileflip_lib::SvgOptions {
+            width: Some(100),
+            height: Some(30),
+            ..Default::default()
+        },
+    );
+    assert_eq!(png.dimensions(), (60, 30));
+
+    let png = convert_svg(
+        &input,
+        "png",
+        fileflip_lib::SvgOptions {
+            dpi: Some(192.0),
+            ..Default::default()
+        },
+    );
+    assert_eq!(png.dimensions(), (80, 40));
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        svg: fileflip_lib::SvgOptions {
+            background: Some("blue".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(!result.success, "Invalid colour should be rejected");
+}
+
+#[test]
+fn test_svg_resolves_relative_images() {
+    let test_dir = setup_test_dir("svg_resources");
+    let assets = test_dir.join("assets");
+    fs::create_dir_all(&assets).unwrap();
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 255, 0, 255]))
+        .save(assets.join("dot.png"))
+        .unwrap();
+
+    let input = test_dir.join("page.svg");
+    fs::write(
+        &input,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="8" height="8"><image href="assets/dot.png" width="8" height="8"/></svg>"#,
+    )
+    .unwrap();
+
+    // Relative to the SVG's own directory by default
+    let png = convert_svg(&input, "png", Default::default());
+    assert_eq!(png.get_pixel(4, 4).0, [0, 255, 0, 255]);
+
+    // Or to the given resources directory
+    let moved = test_dir.join("elsewhere");
+    fs::create_dir_all(&moved).unwrap();
+    fs::copy(&input, moved.join("page.svg")).unwrap();
+    let png = convert_svg(
+        &moved.join("page.svg"),
+        "png",
+        fileflip_lib::SvgOptions {
+            resources_dir: Some(test_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(png.get_pixel(4, 4).0, [0, 255, 0, 255]);
+}
+
+/// Counts the image XObjects in a PDF.
+fn pdf_image_count(doc: &lopdf::Document) -> usize {
+    doc.objects
+        .values()
+        .filter(|object| {
+            object
+                .as_stream()
+                .map(|stream| matches!(stream.dict.get(b"Subtype").and_then(|s| s.as_name()), Ok(b"Image")))
+                .unwrap_or(false)
+        })
+        .count()
+}
+
+#[test]
+fn test_svg_to_vector_pdf() {
+    let test_dir = setup_test_dir("svg_pdf");
+    let input = test_dir.join("diagram.svg");
+    fs::write(
+        &input,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200"><rect x="10" y="10" width="380" height="180" fill="none" stroke="black"/><circle cx="200" cy="100" r="50" fill="red"/></svg>"#,
+    )
+    .unwrap();
+
+    let doc = lopdf::Document::load(convert_to(&input, "pdf")).unwrap();
+    assert_eq!(doc.get_pages().len(), 1);
+    assert_eq!(pdf_image_count(&doc), 0, "SVG should stay vector");
+
+    // 400x200 px at 96 DPI is 300x150 pt
+    assert_eq!(pdf_media_box(&doc, 1), vec![0.0, 0.0, 300.0, 150.0]);
+
+    // Pixel edits need the raster path
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "pdf".to_string(),
+        transform: fileflip_lib::ImageTransform {
+            rotate: 90,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Rotated SVG to PDF failed: {:?}", result.error);
+    let doc = lopdf::Document::load(result.output_path.unwrap()).unwrap();
+    assert_eq!(pdf_name(pdf_page_image(&doc, 1), b"Subtype"), "Image");
+}
+
+fn pdf_media_box(doc: &lopdf::Document, page: u32) -> Vec<f32> {
+    let page = doc.get_dictionary(*doc.get_pages().get(&page).unwrap()).unwrap();
+    page.get(b"MediaBox")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_float().unwrap())
+        .collect()
+}
+
+/// The image XObject drawn on a page.
+fn pdf_page_image(doc: &lopdf::Document, page: u32) -> &lopdf::Stream {
+    let page = doc.get_dictionary(*doc.get_pages().get(&page).unwrap()).unwrap();
+    let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+    let xobjects = resources.get(b"XObject").unwrap().as_dict().unwrap();
+    let (_, image) = xobjects.iter().next().unwrap();
+    doc.get_object(image.as_reference().unwrap()).unwrap().as_stream().unwrap()
+}
+
+fn pdf_page_image_size(doc: &lopdf::Document, page: u32) -> (i64, i64) {
+    let image = pdf_page_image(doc, page);
+    (
+        image.dict.get(b"Width").unwrap().as_i64().unwrap(),
+        image.dict.get(b"Height").unwrap().as_i64().unwrap(),
+    )
+}
+
+fn inflate(data: &[u8]) -> Vec<u8> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    flate2::read::ZlibDecoder::new(data).read_to_end(&mut out).unwrap();
+    out
+}
+
+fn pdf_name<'a>(stream: &'a lopdf::Stream, key: &[u8]) -> &'a str {
+    std::str::from_utf8(stream.dict.get(key).unwrap().as_name().unwrap()).unwrap()
+}
+
+#[test]
+fn test_image_to_pdf_embedding() {
+    let test_dir = setup_test_dir("pdf_embedding");
+
+    // Lossless sources are stored losslessly, with alpha as a soft mask
+    let png = test_dir.join("screenshot.png");
+    let img = image::RgbaImage::from_fn(16, 8, |x, y| image::Rgba([(x * 16) as u8, (y * 32) as u8, 7, if x < 8 { 255 } else { 0 }]));
+    img.save(&png).unwrap();
+    let doc = lopdf::Document::load(convert_to(&png, "pdf")).unwrap();
+    let image = pdf_page_image(&doc, 1);
+    assert_eq!(pdf_name(image, b"Filter"), "FlateDecode");
+    assert_eq!(pdf_name(image, b"ColorSpace"), "DeviceRGB");
+    let rgb: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+    assert_eq!(inflate(&image.content), rgb);
+    let mask = doc.get_object(image.dict.get(b"SMask").unwrap().as_reference().unwrap()).unwrap().as_stream().unwrap();
+    let alpha: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+    assert_eq!(inflate(&mask.content), alpha);
+
+    let gray = test_dir.join("lineart.png");
+    image::GrayImage::from_fn(8, 8, |x, _| image::Luma([(x * 30) as u8])).save(&gray).unwrap();
+    let doc = lopdf::Document::load(convert_to(&gray, "pdf")).unwrap();
+    let image = pdf_page_image(&doc, 1);
+    assert_eq!(pdf_name(image, b"ColorSpace"), "DeviceGray");
+    assert!(image.dict.get(b"SMask").is_err());
+
+    // JPEG bytes are copied as they are
+    let jpg = test_dir.join("photo.jpg");
+    image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([(x * 4) as u8, (y * 5) as u8, 90])).save(&jpg).unwrap();
+    let doc = lopdf::Document::load(convert_to(&jpg, "pdf")).unwrap();
+    let image = pdf_page_image(&doc, 1);
+    assert_eq!(pdf_name(image, b"Filter"), "DCTDecode");
+    assert_eq!(image.content, fs::read(&jpg).unwrap());
+
+    // Edited JPEGs are re-encoded at the requested quality
+    let jpeg_size = |quality: u8| {
+        let options = fileflip_lib::ConversionOptions {
+            output_format: "pdf".to_string(),
+            quality,
+            overwrite_existing: true,
+            transform: fileflip_lib::ImageTransform {
+                flip_horizontal: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = fileflip_lib::convert_file_with_options(&jpg.to_string_lossy(), &options, None);
+        assert!(result.success, "{:?}", result.error);
+        let doc = lopdf::Document::load(result.output_path.unwrap()).unwrap();
+        let image = pdf_page_image(&doc, 1);
+        assert_eq!(pdf_name(image, b"Filter"), "DCTDecode");
+        image.content.len()
+    };
+    assert!(jpeg_size(20) < jpeg_size(95));
+}
+
+#[test]
+fn test_images_to_pdf_pages() {
+    let test_dir = setup_test_dir("images_to_pdf");
+    let sizes = [(300, 200), (100, 400), (96, 96)];
+    let inputs: Vec<String> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &(w, h))| {
+            let path = test_dir.join(format!("scan{}.jpg", i + 1));
+            image::RgbImage::from_pixel(w, h, image::Rgb([200, 200, 200])).save(&path).unwrap();
+            path.to_string_lossy().to_string()
+        })
+        .collect();
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "pdf".to_string(),
+        pdf_page: fileflip_lib::PdfPageOptions {
+            size: fileflip_lib::PageSize::A4,
+            margin_mm: 10.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::images_to_pdf(&inputs, &options);
+    assert!(result.success, "Combining images failed: {:?}", result.error);
+    let output = result.output_path.unwrap();
+    assert!(output.ends_with("scan1.pdf"), "{}", output);
+
+    let doc = lopdf::Document::load(&output).unwrap();
+    assert_eq!(doc.get_pages().len(), 3);
+    for (page, &size) in (1..=3).zip(&sizes) {
+        assert_eq!(pdf_page_image_size(&doc, page), (size.0 as i64, size.1 as i64), "page {}", page);
+    }
+    // Auto orientation turns the page for the wide image only
+    assert_eq!(pdf_media_box(&doc, 1), vec![0.0, 0.0, 841.89, 595.28]);
+    assert_eq!(pdf_media_box(&doc, 2), vec![0.0, 0.0, 595.28, 841.89]);
+
+    // Fit-to-image pages are the image's size at 96 DPI plus the margins
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "pdf".to_string(),
+        pdf_page: fileflip_lib::PdfPageOptions {
+            margin_mm: 25.4,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::images_to_pdf(&inputs[2..], &options);
+    assert!(result.success, "{:?}", result.error);
+    let doc = lopdf::Document::load(result.output_path.unwrap()).unwrap();
+    assert_eq!(pdf_media_box(&doc, 1), vec![0.0, 0.0, 216.0, 216.0]);
+
+    let text = test_dir.join("notes.txt");
+    fs::write(&text, "not an image").unwrap();
+    let result = fileflip_lib::images_to_pdf(&[inputs[0].clone(), text.to_string_lossy().to_string()], &options);
+    assert!(!result.success);
+    assert!(fs::read_dir(&test_dir).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().starts_with('.')));
+}
+
+const FRAME_COLORS: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+
+/// GIF palettes are quantised, so colours can be off by a level or two.
+fn assert_frame_color(actual: image::Rgba<u8>, expected: [u8; 4], what: &str) {
+    let close = actual.0.iter().zip(expected).all(|(&a, e)| a.abs_diff(e) <= 2);
+    assert!(close, "{}: expected {:?}, got {:?}", what, expected, actual.0);
+}
+
+/// Writes a three-frame GIF: solid red, green and blue, shown for 100, 200
+/// and 300 ms.
+fn write_animated_gif(path: &std::path::Path) {
+    let file = fs::File::create(path).unwrap();
+    let mut encoder = image::codecs::gif::GifEncoder::new(file);
+    encoder.set_repeat(image::codecs::gif::Repeat::Infinite).unwrap();
+    for (i, color) in FRAME_COLORS.iter().enumerate() {
+        let frame = image::Frame::from_parts(
+            image::RgbaImage::from_pixel(20, 10, image::Rgba(*color)),
+            0,
+            0,
+            image::Delay::from_numer_denom_ms(100 * (i as u32 + 1), 1),
+        );
+        encoder.encode_frame(frame).unwrap();
+    }
+}
+
+fn decode_frames(path: &str) -> Vec<(image::RgbaImage, u32)> {
+    use image::AnimationDecoder;
+    let reader = std::io::BufReader::new(fs::File::open(path).unwrap());
+    let frames = match std::path::Path::new(path).extension().unwrap().to_str().unwrap() {
+        "gif" => image::codecs::gif::GifDecoder::new(reader).unwrap().into_frames(),
+        "webp" => image::codecs::webp::WebPDecoder::new(reader).unwrap().into_frames(),
+        _ => image::codecs::png::PngDecoder::new(reader).unwrap().apng().unwrap().into_frames(),
+    };
+    frames
+        .collect_frames()
+        .unwrap()
+        .into_iter()
+        .map(|f| {
+            let (numer, denom) = f.delay().numer_denom_ms();
+            (f.into_buffer(), numer / denom)
+        })
+        .collect()
+}
+
+#[test]
+fn test_animated_gif_keeps_frames() {
+    let test_dir = setup_test_dir("animated_gif");
+    let input = test_dir.join("clip.gif");
+    write_animated_gif(&input);
+
+    for format in ["webp", "png", "gif"] {
+        let options = fileflip_lib::ConversionOptions {
+            output_format: format.to_string(),
+            output_dir: Some(test_dir.join(format).to_string_lossy().to_string()),
+            lossless: true,
+            ..Default::default()
+        };
+        let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+        assert!(result.success, "GIF to {} failed: {:?}", format, result.error);
+
+        let frames = decode_frames(result.output_path.as_ref().unwrap());
+        assert_eq!(frames.len(), 3, "{} lost frames", format);
+        for (i, (frame, delay)) in frames.iter().enumerate() {
+            assert_eq!(frame.dimensions(), (20, 10));
+            assert_frame_color(*frame.get_pixel(5, 5), FRAME_COLORS[i], &format!("{} frame {}", format, i));
+            // WebP stores no end time, so its last frame gets the average delay
+            if !(format == "webp" && i == 2) {
+                assert_eq!(*delay, 100 * (i as u32 + 1), "{} frame {} delay", format, i);
+            }
+        }
+    }
+
+    // Formats that cannot animate still get the first frame
+    let bmp = image::open(convert_to(&input, "bmp")).unwrap().to_rgba8();
+    assert_frame_color(*bmp.get_pixel(5, 5), FRAME_COLORS[0], "BMP");
+}
+
+#[test]
+fn test_extract_animation_frames() {
+    let test_dir = setup_test_dir("extract_frames");
+    let input = test_dir.join("clip.gif");
+    write_animated_gif(&input);
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        animation: fileflip_lib::AnimationOptions {
+            extract_frames: true,
+            ..Default::default()
+        },
+        transform: fileflip_lib::ImageTransform {
+            width: Some(10),
+            height: Some(5),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Frame extraction failed: {:?}", result.error);
+
+    assert_eq!(result.output_paths.len(), 4);
+    assert_eq!(decode_frames(&result.output_paths[0]).len(), 3);
+    for (i, path) in result.output_paths[1..].iter().enumerate() {
+        assert!(path.ends_with(&format!("clip_frame{:03}.png", i + 1)), "{}", path);
+        let frame = image::open(path).unwrap().to_rgba8();
+        assert_eq!(frame.dimensions(), (10, 5));
+        assert_frame_color(*frame.get_pixel(5, 2), FRAME_COLORS[i], path);
+    }
+}
+
+fn write_avif(path: &std::path::Path) {
+    let img = image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255]));
+    img.save(path).unwrap();
+}
+
+#[cfg(feature = "avif-decode")]
+#[test]
+fn test_avif_input() {
+    let test_dir = setup_test_dir("avif_input");
+    let input = test_dir.join("photo.avif");
+    write_avif(&input);
+
+    for format in ["png", "jpg", "pdf"] {
+        let options = fileflip_lib::ConversionOptions {
+            output_format: format.to_string(),
+            ..Default::default()
+        };
+        let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+        assert!(result.success, "AVIF to {} failed: {:?}", format, result.error);
+    }
+    assert!(fileflip_lib::get_supported_formats("avif".to_string()).contains(&"png".to_string()));
+}
+
+#[cfg(not(feature = "avif-decode"))]
+#[test]
+fn test_avif_input_requires_feature() {
+    let test_dir = setup_test_dir("avif_input_disabled");
+    let input = test_dir.join("photo.avif");
+    write_avif(&input);
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(!result.success);
+    assert!(result.error.unwrap().contains("AVIF"));
+    assert!(!fileflip_lib::is_conversion_supported("avif".to_string(), "png".to_string()));
+    assert!(fileflip_lib::get_supported_formats("avif".to_string()).is_empty());
+}
+
+#[cfg(feature = "pdfium")]
+#[test]
+fn test_pdf_to_image_in_process() {
+    let test_dir = setup_test_dir("pdf_render");
+    let input = test_dir.join("letter.txt");
+    fs::write(&input, "Dear reader,\n\nThis page is rendered without external tools.").unwrap();
+    let pdf = convert_to(&input, "pdf");
+
+    // Quality 90 renders at 300 DPI: A4 is 2480 x 3508 pixels
+    let png = image::open(convert_to(std::path::Path::new(&pdf), "png")).unwrap().to_rgb8();
+    assert_eq!(png.dimensions(), (2480, 3508));
+    assert!(png.pixels().any(|p| p[0] < 64), "No text was rendered");
+    assert_eq!(png.get_pixel(5, 5).0, [255, 255, 255]);
+}
+
+/// A three-page PDF (text_to_pdf fits about 65 lines on a page).
+fn write_three_page_pdf(test_dir: &std::path::Path) -> std::path::PathBuf {
+    let input = test_dir.join("report.txt");
+    let text: Vec<String> = (1..=150).map(|n| format!("Line {}", n)).collect();
+    fs::write(&input, text.join("\n")).unwrap();
+    let pdf = std::path::PathBuf::from(convert_to(&input, "pdf"));
+    assert_eq!(lopdf::Document::load(&pdf).unwrap().get_pages().len(), 3);
+    pdf
+}
+
+fn export_pdf_pages(pdf: &std::path::Path, pages: Option<&str>, zip: bool) -> fileflip_lib::ConversionResult {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        pdf_export: fileflip_lib::PdfExportOptions {
+            pages: pages.map(str::to_string),
+            zip,
+        },
+        ..Default::default()
+    };
+    fileflip_lib::convert_file_with_options(&pdf.to_string_lossy(), &options, None)
+}
+
+#[test]
+fn test_pdf_page_range_errors() {
+    let test_dir = setup_test_dir("pdf_page_range");
+    let pdf = write_three_page_pdf(&test_dir);
+
+    for (pages, message) in [
+        ("4", "page 4 does not exist"),
+        ("0-2", "page 0 does not exist"),
+        ("3-1", "3-1 runs backwards"),
+        ("1,x", "'x' is not a page number"),
+        ("-2", "'' is not a page number"),
+    ] {
+        let result = export_pdf_pages(&pdf, Some(pages), false);
+        assert!(!result.success, "Range {} should be rejected", pages);
+        assert!(result.error.as_deref().unwrap().contains(message), "{}: {:?}", pages, result.error);
+    }
+
+    let mut files: Vec<String> = fs::read_dir(&test_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["report.pdf", "report.txt"]);
+}
+
+#[cfg(feature = "pdfium")]
+#[test]
+fn test_pdf_export_pages() {
+    let test_dir = setup_test_dir("pdf_export_pages");
+    let pdf = write_three_page_pdf(&test_dir);
+
+    let result = export_pdf_pages(&pdf, Some("3,1-2"), false);
+    assert!(result.success, "Export failed: {:?}", result.error);
+    let names: Vec<String> = result
+        .output_paths
+        .iter()
+        .map(|p| std::path::Path::new(p).file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, ["report_page003.png", "report_page001.png", "report_page002.png"]);
+    assert_eq!(result.output_path.as_deref(), Some(result.output_paths[0].as_str()));
+    for path in &result.output_paths {
+        assert_eq!(image::image_dimensions(path).unwrap(), (2480, 3508));
+    }
+
+    let result = export_pdf_pages(&pdf, None, true);
+    assert!(result.success, "ZIP export failed: {:?}", result.error);
+    assert_eq!(result.output_paths.len(), 1);
+    let zip_path = result.output_path.unwrap();
+    assert!(zip_path.ends_with("report.zip"));
+    let mut archive = zip::ZipArchive::new(fs::File::open(&zip_path).unwrap()).unwrap();
+    let mut entries: Vec<String> = archive.file_names().map(str::to_string).collect();
+    entries.sort();
+    assert_eq!(entries, ["report_page001.png", "report_page002.png", "report_page003.png"]);
+    let mut page = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("report_page002.png").unwrap(), &mut page).unwrap();
+    assert!(image::load_from_memory(&page).is_ok());
+
+    // A page named twice is exported once, where it first appears
+    fs::remove_file(&zip_path).unwrap();
+    let result = export_pdf_pages(&pdf, Some("2,1-3,2"), true);
+    assert!(result.success, "ZIP export with a repeated page failed: {:?}", result.error);
+    let archive = zip::ZipArchive::new(fs::File::open(result.output_path.unwrap()).unwrap()).unwrap();
+    let entries: Vec<&str> = (0..archive.len()).map(|i| archive.name_for_index(i).unwrap()).collect();
+    assert_eq!(entries, ["report_page002.png", "report_page001.png", "report_page003.png"]);
+
+    // The scratch directory the pages were rendered into is gone
+    assert!(fs::read_dir(&test_dir).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().starts_with('.')));
+}
+
+/// The first line of text on each page of a PDF made by text_to_pdf.
+fn pdf_first_lines(path: &str) -> Vec<String> {
+    let doc = lopdf::Document::load(path).unwrap();
+    doc.get_pages()
+        .values()
+        .map(|&id| {
+            let content = lopdf::content::Content::decode(&doc.get_page_content(id).unwrap()).unwrap();
+            let text = content.operations.iter().find(|op| op.operator == "Tj").unwrap();
+            String::from_utf8_lossy(text.operands[0].as_str().unwrap()).to_string()
+        })
+        .collect()
+}
+
+fn pdf_rotations(path: &str) -> Vec<i64> {
+    let doc = lopdf::Document::load(path).unwrap();
+    doc.get_pages()
+        .values()
+        .map(|&id| doc.get_dictionary(id).unwrap().get(b"Rotate").unwrap().as_i64().unwrap())
+        .collect()
+}
+
+fn file_name(path: &str) -> String {
+    std::path::Path::new(path).file_name().unwrap().to_string_lossy().to_string()
+}
+
+#[test]
+fn test_pdf_merge_and_split() {
+    let test_dir = setup_test_dir("pdf_merge_split");
+    let report = write_three_page_pdf(&test_dir);
+    let report = report.to_string_lossy().to_string();
+    let notes = test_dir.join("notes.txt");
+    fs::write(&notes, "Note").unwrap();
+    let notes = convert_to(&notes, "pdf");
+    let options = fileflip_lib::PdfToolOptions::default();
+
+    let merged = fileflip_lib::merge_pdfs(&[report.clone(), notes.clone(), report.clone()], &options);
+    assert!(merged.success, "Merge failed: {:?}", merged.error);
+    let merged_path = merged.output_path.unwrap();
+    assert_eq!(file_name(&merged_path), "report_merged.pdf");
+    assert_eq!(
+        pdf_first_lines(&merged_path),
+        ["Line 1", "Line 66", "Line 131", "Note", "Line 1", "Line 66", "Line 131"]
+    );
+    let input_sizes = 2 * fs::metadata(&report).unwrap().len() + fs::metadata(&notes).unwrap().len();
+    assert_eq!(merged.original_size, Some(input_sizes));
+
+    let split = fileflip_lib::split_pdf(&report, &options);
+    assert!(split.success, "Split failed: {:?}", split.error);
+    let names: Vec<String> = split.output_paths.iter().map(|p| file_name(p)).collect();
+    assert_eq!(names, ["report_page001.pdf", "report_page002.pdf", "report_page003.pdf"]);
+    for (path, first_line) in split.output_paths.iter().zip(["Line 1", "Line 66", "Line 131"]) {
+        assert_eq!(pdf_first_lines(path), [first_line]);
+        // Other pages' content is pruned, not just unlinked
+        assert!(fs::metadata(path).unwrap().len() < fs::metadata(&report).unwrap().len());
+    }
+
+    let missing = fileflip_lib::merge_pdfs(&[report.clone(), test_dir.join("missing.pdf").to_string_lossy().to_string()], &options);
+    assert!(!missing.success);
+    assert!(!test_dir.join("report_merged_1.pdf").exists());
+}
+
+#[test]
+fn test_pdf_page_edits() {
+    let test_dir = setup_test_dir("pdf_page_edits");
+    let report = write_three_page_pdf(&test_dir).to_string_lossy().to_string();
+    let options = fileflip_lib::PdfToolOptions::default();
+    let output = |result: fileflip_lib::ConversionResult| {
+        assert!(result.success, "PDF edit failed: {:?}", result.error);
+        result.output_path.unwrap()
+    };
+    let error = |result: fileflip_lib::ConversionResult| {
+        assert!(!result.success);
+        result.error.unwrap()
+    };
+
+    let extracted = output(fileflip_lib::extract_pdf_pages(&report, "3,1", &options));
+    assert_eq!(file_name(&extracted), "report_extract.pdf");
+    assert_eq!(pdf_first_lines(&extracted), ["Line 131", "Line 1"]);
+    let repeated = output(fileflip_lib::extract_pdf_pages(&report, "2,2", &options));
+    assert_eq!(pdf_first_lines(&repeated), ["Line 66"]);
+
+    let deleted = output(fileflip_lib::delete_pdf_pages(&report, "2", &options));
+    assert_eq!(pdf_first_lines(&deleted), ["Line 1", "Line 131"]);
+    assert!(error(fileflip_lib::delete_pdf_pages(&report, "1-", &options)).contains("every page"));
+
+    let reordered = output(fileflip_lib::reorder_pdf_pages(&report, "3,1-2", &options));
+    assert_eq!(pdf_first_lines(&reordered), ["Line 131", "Line 1", "Line 66"]);
+    assert!(error(fileflip_lib::reorder_pdf_pages(&report, "1,2", &options)).contains("each of the 3 pages"));
+    assert!(error(fileflip_lib::reorder_pdf_pages(&report, "1,2,2", &options)).contains("each of the 3 pages"));
+
+    let rotated = output(fileflip_lib::rotate_pdf_pages(&report, Some("2"), 90, &options));
+    assert_eq!(pdf_rotations(&rotated), [0, 90, 0]);
+    let turned_back = output(fileflip_lib::rotate_pdf_pages(&rotated, None, -90, &options));
+    assert_eq!(pdf_rotations(&turned_back), [270, 0, 270]);
+    assert!(error(fileflip_lib::rotate_pdf_pages(&report, None, 45, &options)).contains("multiples of 90"));
+    assert!(error(fileflip_lib::extract_pdf_pages(&report, "4", &options)).contains("page 4 does not exist"));
+
+    // The source is never modified
+    assert_eq!(pdf_first_lines(&report), ["Line 1", "Line 66", "Line 131"]);
+}
+
+/// A two-page PDF: a large title over two columns, with the right column
+/// drawn first, then a bold heading and a paragraph on page 2.
+fn write_column_pdf(path: &std::path::Path) {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    let mut doc = lopdf::Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font = |doc: &mut lopdf::Document, name: &str| {
+        doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => name,
+            "Encoding" => "WinAnsiEncoding",
+        })
+    };
+    let regular = font(&mut doc, "Helvetica");
+    let bold = font(&mut doc, "Helvetica-Bold");
+    let resources = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => regular, "F2" => bold },
+    });
+
+    let text = |font: &str, size: i64, x: i64, y: i64, shown: Object| {
+        vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![font.into(), size.into()]),
+            Operation::new("Td", vec![x.into(), y.into()]),
+            Operation::new(if shown.as_array().is_ok() { "TJ" } else { "Tj" }, vec![shown]),
+            Operation::new("ET", vec![]),
+        ]
+    };
+    let string = |s: &str| Object::string_literal(s);
+    let pages = [
+        [
+            text("F1", 11, 320, 650, string("Right column first line.")),
+            text("F1", 11, 320, 636, string("right column second.")),
+            text("F2", 24, 72, 720, string("Annual Report")),
+            text("F1", 11, 72, 650, string("Left column opens the")),
+            text("F1", 11, 72, 636, string("story and contin-")),
+            text("F1", 11, 72, 622, string("ues here.")),
+        ]
+        .concat(),
+        [
+            text("F2", 11, 72, 720, string("Summary")),
+            text("F1", 11, 72, 700, Object::Array(vec![string("Second"), (-250).into(), string("page *body* text.")])),
+        ]
+        .concat(),
+    ];
+
+    let mut kids = Vec::new();
+    for operations in pages {
+        let content = Content { operations }.encode().unwrap();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+        kids.push(Object::Reference(doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => resources,
+            "Contents" => content_id,
+        })));
+    }
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Count" => 2, "Kids" => kids }),
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    let info_id = doc.add_object(dictionary! {
+        "Title" => string("Annual Report 2025"),
+        "Author" => string("Jo Smith"),
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.trailer.set("Info", info_id);
+    doc.save(path).unwrap();
+}
+
+#[test]
+fn test_pdf_text_extraction() {
+    let test_dir = setup_test_dir("pdf_text");
+    let pdf = test_dir.join("annual.pdf");
+    write_column_pdf(&pdf);
+
+    let text = fs::read_to_string(convert_to(&pdf, "txt")).unwrap();
+    assert_eq!(
+        text,
+        "Annual Report\n\nLeft column opens the\nstory and contin-\nues here.\n\n\
+         Right column first line.\nright column second.\n\x0CSummary\n\nSecond page *body* text.\n"
+    );
+
+    let markdown = fs::read_to_string(convert_to(&pdf, "md")).unwrap();
+    assert_eq!(
+        markdown,
+        "# Annual Report\n\nLeft column opens the story and continues here.\n\n\
+         Right column first line. right column second.\n\n---\n\n\
+         ### Summary\n\nSecond page \\*body\\* text.\n"
+    );
+
+    let options = fileflip_lib::ConversionOptions {
+        preserve_metadata: true,
+        ..conversion_options("html")
+    };
+    let result = fileflip_lib::convert_file_with_options(&pdf.to_string_lossy(), &options, None);
+    assert!(result.success, "PDF to HTML failed: {:?}", result.error);
+    let html = fs::read_to_string(result.output_path.unwrap()).unwrap();
+    assert!(html.contains("<title>Annual Report 2025</title>"));
+    assert!(html.contains("<meta name=\"author\" content=\"Jo Smith\">"));
+    assert!(html.contains("<section class=\"page\" id=\"page-1\">\n<h1>Annual Report</h1>\n<p>Left column"));
+    assert!(html.contains("<section class=\"page\" id=\"page-2\">\n<h3>Summary</h3>"));
+
+    // Text written by FileFlip itself comes back line for line
+    let report = write_three_page_pdf(&test_dir);
+    let text = fs::read_to_string(convert_to(&report, "txt")).unwrap();
+    let pages: Vec<&str> = text.split('\x0C').collect();
+    assert_eq!(pages.len(), 3);
+    assert!(pages[1].starts_with("Line 66\nLine 67\n"));
+    assert_eq!(text.split(['\n', '\x0C']).filter(|line| line.starts_with("Line ")).count(), 150);
+}
+
+/// Bytes that do not compress, from a fixed seed.
+fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect()
+}
+
+/// A one-page PDF with a 1200 px image shown 2 inches wide (600 DPI), a
+/// JPEG shown at 100 DPI, the same font file embedded twice, and an object
+/// nothing refers to.
+fn write_bloated_pdf(path: &std::path::Path) {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+    use std::io::Write;
+
+    let mut doc = lopdf::Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+
+    let mut deflated = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+    deflated.write_all(&noise(1200 * 1200 * 3, 7)).unwrap();
+    let sharp = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject", "Subtype" => "Image", "Width" => 1200, "Height" => 1200,
+            "ColorSpace" => "DeviceRGB", "BitsPerComponent" => 8, "Filter" => "FlateDecode",
+        },
+        deflated.finish().unwrap(),
+    ));
+
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(400, 200, |x, y| image::Rgb([x as u8, y as u8, 128])))
+        .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .unwrap();
+    let photo = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject", "Subtype" => "Image", "Width" => 400, "Height" => 200,
+            "ColorSpace" => "DeviceRGB", "BitsPerComponent" => 8, "Filter" => "DCTDecode",
+        },
+        jpeg,
+    ));
+
+    let font_file = noise(20_000, 11);
+    let mut font = |name: &str| {
+        let file = doc.add_object(Stream::new(dictionary! { "Length1" => 20_000 }, font_file.clone()));
+        let descriptor = doc.add_object(dictionary! {
+            "Type" => "FontDescriptor", "FontName" => "Sans", "Flags" => 32, "FontFile2" => file,
+        });
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font", "Subtype" => "TrueType", "BaseFont" => "Sans", "FontDescriptor" => descriptor,
+        });
+        (name.to_string(), Object::Reference(font))
+    };
+    let fonts = lopdf::Dictionary::from_iter([font("F1"), font("F2")]);
+    doc.add_object(Stream::new(dictionary! { "Type" => "Junk" }, noise(10_000, 13)));
+
+    let draw = |name: &str, width: i64, height: i64, y: i64| {
+        vec![
+            Operation::new("q", vec![]),
+            Operation::new("cm", vec![width.into(), 0.into(), 0.into(), height.into(), 72.into(), y.into()]),
+            Operation::new("Do", vec![Object::Name(name.as_bytes().to_vec())]),
+            Operation::new("Q", vec![]),
+        ]
+    };
+    let operations = [draw("Im1", 144, 144, 500), draw("Im2", 288, 144, 200)].concat();
+    let content = doc.add_object(Stream::new(dictionary! {}, Content { operations }.encode().unwrap()));
+    let page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Resources" => dictionary! {
+            "XObject" => dictionary! { "Im1" => sharp, "Im2" => photo },
+            "Font" => fonts,
+        },
+        "Contents" => content,
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Count" => 1, "Kids" => vec![page.into()] }),
+    );
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog);
+    doc.save(path).unwrap();
+}
+
+fn optimize_pdf(pdf: &std::path::Path, image_dpi: u32) -> (fileflip_lib::ConversionResult, lopdf::Document) {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "pdf".to_string(),
+        pdf_optimize: fileflip_lib::PdfOptimizeOptions { image_dpi },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&pdf.to_string_lossy(), &options, None);
+    assert!(result.success, "PDF optimisation failed: {:?}", result.error);
+    let doc = lopdf::Document::load(result.output_path.as_ref().unwrap()).unwrap();
+    (result, doc)
+}
+
+/// The page's resource `name` in `category`, such as an image or font.
+fn page_resource(doc: &lopdf::Document, category: &[u8], name: &[u8]) -> lopdf::ObjectId {
+    let page = doc.get_dictionary(doc.page_iter().next().unwrap()).unwrap();
+    let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+    resources.get(category).unwrap().as_dict().unwrap().get(name).unwrap().as_reference().unwrap()
+}
+
+fn image_size(doc: &lopdf::Document, name: &[u8]) -> (i64, i64) {
+    let image = &doc.get_object(page_resource(doc, b"XObject", name)).unwrap().as_stream().unwrap().dict;
+    (image.get(b"Width").unwrap().as_i64().unwrap(), image.get(b"Height").unwrap().as_i64().unwrap())
+}
+
+#[test]
+fn test_pdf_optimize() {
+    let test_dir = setup_test_dir("pdf_optimize");
+    let pdf = test_dir.join("scan.pdf");
+    write_bloated_pdf(&pdf);
+
+    let (result, doc) = optimize_pdf(&pdf, 150);
+    assert_eq!(file_name(result.output_path.as_ref().unwrap()), "scan_1.pdf");
+    let (original_size, new_size) = (result.original_size.unwrap(), result.new_size.unwrap());
+    assert_eq!(original_size, fs::metadata(&pdf).unwrap().len());
+    assert!(new_size < original_size / 4, "{} -> {}", original_size, new_size);
+
+    // Only the image shown above 150 DPI is downsampled
+    assert_eq!(image_size(&doc, b"Im1"), (300, 300));
+    assert_eq!(image_size(&doc, b"Im2"), (400, 200));
+    // The duplicate font is stored once and the unused object is gone
+    assert_eq!(page_resource(&doc, b"Font", b"F1"), page_resource(&doc, b"Font", b"F2"));
+    assert!(!doc.objects.values().any(|object| {
+        object.as_stream().is_ok_and(|s| s.dict.get(b"Type").and_then(|t| t.as_name()).ok() == Some(b"Junk"))
+    }));
+
+    let (_, kept) = optimize_pdf(&pdf, 0);
+    assert_eq!(image_size(&kept, b"Im1"), (1200, 1200));
+
+    // Text PDFs come through intact, and never grow
+    let report = write_three_page_pdf(&test_dir);
+    let (result, _) = optimize_pdf(&report, 150);
+    assert!(result.new_size.unwrap() <= result.original_size.unwrap());
+    assert_eq!(pdf_first_lines(result.output_path.as_ref().unwrap()), ["Line 1", "Line 66", "Line 131"]);
+}
+
+fn encrypted_options(format: &str, user_password: &str) -> fileflip_lib::ConversionOptions {
+    fileflip_lib::ConversionOptions {
+        output_format: format.to_string(),
+        pdf_encryption: fileflip_lib::PdfEncryptionOptions {
+            user_password: Some(user_password.to_string()),
+            owner_password: Some("owner secret".to_string()),
+            allow_copy: false,
+            allow_modify: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_pdf_encryption() {
+    let test_dir = setup_test_dir("pdf_encryption");
+    let input = test_dir.join("contract.txt");
+    fs::write(&input, "Confidential terms").unwrap();
+
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &encrypted_options("pdf", "open sesame"), None);
+    assert!(result.success, "Encrypted PDF failed: {:?}", result.error);
+    let contract = result.output_path.unwrap();
+    let doc = lopdf::Document::load(&contract).unwrap();
+    let encrypt = doc.get_encrypted().unwrap();
+    assert_eq!(encrypt.get(b"V").unwrap().as_i64().unwrap(), 5);
+    assert_eq!(encrypt.get(b"R").unwrap().as_i64().unwrap(), 6);
+    let permissions = encrypt.get(b"P").unwrap().as_i64().unwrap();
+    assert_eq!(permissions & (1 << 2), 1 << 2, "printing is allowed");
+    assert_eq!(permissions & (1 << 3 | 1 << 4), 0, "copying and editing are not");
+
+    // Nothing can be read out of it until it is decrypted
+    assert!(!fs::read(&contract).unwrap().windows(12).any(|bytes| bytes == b"Confidential"));
+    let text = fileflip_lib::convert_file_with_options(&contract, &encrypted_options("txt", ""), None);
+    assert!(text.error.unwrap().contains("password protected"));
+
+    let options = fileflip_lib::PdfToolOptions::default();
+    let wrong = fileflip_lib::decrypt_pdf(&contract, "open says me", &options);
+    assert!(wrong.error.unwrap().ends_with("The password is incorrect"));
+    assert!(!test_dir.join("contract_decrypted.pdf").exists());
+
+    for password in ["open sesame", "owner secret"] {
+        let decrypted = fileflip_lib::decrypt_pdf(&contract, password, &options);
+        assert!(decrypted.success, "Decrypt failed: {:?}", decrypted.error);
+        let path = decrypted.output_path.unwrap();
+        assert!(!lopdf::Document::load(&path).unwrap().is_encrypted());
+        assert_eq!(pdf_first_lines(&path), ["Confidential terms"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    let plain = fileflip_lib::decrypt_pdf(&write_three_page_pdf(&test_dir).to_string_lossy(), "", &options);
+    assert!(plain.error.unwrap().contains("not password protected"));
+
+    // Combined images are protected too
+    let scan = test_dir.join("scan.png");
+    image::RgbImage::from_pixel(40, 30, image::Rgb([200, 30, 30])).save(&scan).unwrap();
+    let combined = fileflip_lib::images_to_pdf(&[scan.to_string_lossy().to_string()], &encrypted_options("pdf", "scan"));
+    assert!(combined.success, "Encrypted image PDF failed: {:?}", combined.error);
+    let combined = combined.output_path.unwrap();
+    assert!(lopdf::Document::load(&combined).unwrap().is_encrypted());
+    let decrypted = fileflip_lib::decrypt_pdf(&combined, "scan", &options);
+    assert!(decrypted.success, "Decrypt failed: {:?}", decrypted.error);
+    let doc = lopdf::Document::load(decrypted.output_path.unwrap()).unwrap();
+    assert_eq!(image_size(&doc, b"Im0"), (40, 30));
+}
+
+#[test]
+fn test_pdf_password_input() {
+    let test_dir = setup_test_dir("pdf_password_input");
+    let input = test_dir.join("terms.md");
+    fs::write(&input, "Confidential terms").unwrap();
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &encrypted_options("pdf", "open sesame"), None);
+    let terms = result.output_path.unwrap();
+    let with_password = |format: &str, password: &str| fileflip_lib::ConversionOptions {
+        pdf_password: Some(password.to_string()),
+        ..conversion_options(format)
+    };
+
+    let wrong = fileflip_lib::convert_file_with_options(&terms, &with_password("txt", "open says me"), None);
+    assert!(wrong.error.unwrap().ends_with("The password is incorrect"));
+
+    // Outputs are named and placed as if converted from the protected file
+    let text = fileflip_lib::convert_file_with_options(&terms, &with_password("txt", "open sesame"), None);
+    assert!(text.success, "PDF to text failed: {:?}", text.error);
+    assert_eq!(text.output_path.unwrap(), test_dir.join("terms.txt").to_string_lossy());
+    assert!(fs::read_to_string(test_dir.join("terms.txt")).unwrap().contains("Confidential terms"));
+    assert_eq!(text.original_size, Some(fs::metadata(&terms).unwrap().len()));
+
+    // An empty output directory means beside the input, as for other conversions
+    let options = fileflip_lib::ConversionOptions {
+        output_dir: Some(String::new()),
+        ..with_password("html", "open sesame")
+    };
+    let html = fileflip_lib::convert_file_with_options(&terms, &options, None);
+    assert!(html.success, "PDF to HTML failed: {:?}", html.error);
+    assert_eq!(html.output_path.unwrap(), test_dir.join("terms.html").to_string_lossy());
+
+    let optimized = fileflip_lib::convert_file_with_options(&terms, &with_password("pdf", "owner secret"), None);
+    assert!(optimized.success, "PDF optimise failed: {:?}", optimized.error);
+    let optimized = optimized.output_path.unwrap();
+    assert!(!lopdf::Document::load(&optimized).unwrap().is_encrypted());
+    assert_eq!(pdf_first_lines(&optimized), ["Confidential terms"]);
+
+    // The password is ignored for PDFs without protection
+    let plain = write_three_page_pdf(&test_dir);
+    let result = fileflip_lib::convert_file_with_options(&plain.to_string_lossy(), &with_password("txt", "unused"), None);
+    assert!(result.success, "Plain PDF failed: {:?}", result.error);
+
+    let leftovers: Vec<_> = fs::read_dir(&test_dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with('.'))
+        .collect();
+    assert!(leftovers.is_empty(), "Scratch files left behind: {:?}", leftovers);
+}
+
+#[cfg(not(feature = "heif"))]
+#[test]
+fn test_heic_input_requires_feature() {
+    let test_dir = setup_test_dir("heic_input_disabled");
+    let input = test_dir.join("photo.heic");
+    fs::write(&input, b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").unwrap();
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "jpg".to_string(),
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(!result.success);
+    assert!(result.error.unwrap().contains("HEIC"));
+    assert!(!fileflip_lib::is_conversion_supported("heic".to_string(), "jpg".to_string()));
+    assert!(fileflip_lib::get_supported_formats("heif".to_string()).is_empty());
+}
+
+/// Writes a HEIC holding two top-level images, like a burst: a 64x48 primary
+/// photo followed by a 32x32 frame.
+#[cfg(feature = "heif")]
+fn write_heic(path: &std::path::Path) {
+    use libheif_rs::{Channel, ColorSpace, CompressionFormat, EncoderQuality, HeifContext, Image, LibHeif, RgbChroma};
+
+    let lib = LibHeif::new();
+    let mut ctx = HeifContext::new().unwrap();
+    let mut encoder = lib.encoder_for_format(CompressionFormat::Hevc).unwrap();
+    encoder.set_quality(EncoderQuality::Lossy(90)).unwrap();
+
+    for (width, height) in [(64, 48), (32, 32)] {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb)).unwrap();
+        image.create_plane(Channel::Interleaved, width, height, 8).unwrap();
+        let plane = image.planes_mut().interleaved.unwrap();
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let pixel = y * plane.stride + x * 3;
+                plane.data[pixel..pixel + 3].copy_from_slice(&[(x * 4) as u8, (y * 4) as u8, 128]);
+            }
+        }
+        ctx.encode_image(&image, &mut encoder, None).unwrap();
+    }
+
+    ctx.write_to_file(path.to_str().unwrap()).unwrap();
+}
+
+#[cfg(feature = "heif")]
+#[test]
+fn test_heic_input() {
+    let test_dir = setup_test_dir("heic_input");
+    let input = test_dir.join("photo.heic");
+    write_heic(&input);
+
+    let convert = |format: &str, heif: fileflip_lib::HeifOptions| {
+        let options = fileflip_lib::ConversionOptions {
+            output_format: format.to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            heif,
+            ..Default::default()
+        };
+        fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None)
+    };
+
+    for format in ["png", "jpg", "pdf"] {
+        let result = convert(format, Default::default());
+        assert!(result.success, "HEIC to {} failed: {:?}", format, result.error);
+    }
+
+    // The primary image by default, other top-level images by index
+    let primary = convert("png", Default::default());
+    assert_eq!(image::image_dimensions(primary.output_path.unwrap()).unwrap(), (64, 48));
+    let second = convert("png", fileflip_lib::HeifOptions { image_index: Some(1), ..Default::default() });
+    assert!(second.success, "Second HEIC image failed: {:?}", second.error);
+    assert_eq!(image::image_dimensions(second.output_path.unwrap()).unwrap(), (32, 32));
+
+    let missing = convert("png", fileflip_lib::HeifOptions { image_index: Some(5), ..Default::default() });
+    assert!(!missing.success);
+    assert!(missing.error.unwrap().contains("HEIF image 5"));
+
+    assert!(fileflip_lib::get_supported_formats("heic".to_string()).contains(&"jpg".to_string()));
+}
+
+/// Writes a minimal TIFF-based RAW container holding a small IFD0 thumbnail
+/// and a larger JPEG preview in a SubIFD, tagged with orientation 6 (90° CW).
+fn write_raw_with_previews(path: &std::path::Path) {
+    fn entry(out: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32) {
+        out.extend_from_slice(&tag.to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    let jpeg = |width: u32, height: u32| {
+        let mut data = Vec::new();
+        image::RgbImage::from_pixel(width, height, image::Rgb([200, 80, 40]))
+            .write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Jpeg)
+            .unwrap();
+        data
+    };
+    let make = b"FileFlip\0";
+    let thumbnail = jpeg(16, 12);
+    let preview = jpeg(64, 48);
+
+    // Header, IFD0 (5 entries), SubIFD (3 entries), then the data blocks
+    let sub_ifd = 8 + 2 + 5 * 12 + 4;
+    let make_offset = sub_ifd + 2 + 3 * 12 + 4;
+    let thumbnail_offset = make_offset + make.len() as u32;
+    let preview_offset = thumbnail_offset + thumbnail.len() as u32;
+
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    tiff.extend_from_slice(&5u16.to_le_bytes());
+    entry(&mut tiff, 0x010F, 2, make.len() as u32, make_offset);
+    entry(&mut tiff, 0x0112, 3, 1, 6);
+    entry(&mut tiff, 0x014A, 4, 1, sub_ifd);
+    entry(&mut tiff, 0x0201, 4, 1, thumbnail_offset);
+    entry(&mut tiff, 0x0202, 4, 1, thumbnail.len() as u32);
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(&3u16.to_le_bytes());
+    entry(&mut tiff, 0x0103, 3, 1, 7);
+    entry(&mut tiff, 0x0111, 4, 1, preview_offset);
+    entry(&mut tiff, 0x0117, 4, 1, preview.len() as u32);
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(make);
+    tiff.extend_from_slice(&thumbnail);
+    tiff.extend_from_slice(&preview);
+
+    fs::write(path, tiff).unwrap();
+}
+
+#[test]
+fn test_raw_embedded_preview() {
+    let test_dir = setup_test_dir("raw_preview");
+    let input = test_dir.join("photo.dng");
+    write_raw_with_previews(&input);
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        preserve_metadata: true,
+        raw: fileflip_lib::RawOptions {
+            mode: fileflip_lib::RawMode::Preview,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "RAW preview conversion failed: {:?}", result.error);
+
+    // The largest preview wins and is turned upright
+    assert_eq!(output_dimensions(&result), (48, 64));
+    let tags = exif_tags(result.output_path.as_deref().unwrap());
+    assert!(tags.contains(&exif::Tag::Make), "Camera make not carried over: {:?}", tags);
+    assert!(!tags.contains(&exif::Tag::JPEGInterchangeFormat));
+
+    let pdf = fileflip_lib::ConversionOptions {
+        output_format: "pdf".to_string(),
+        ..options
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &pdf, None);
+    assert!(result.success, "RAW to PDF failed: {:?}", result.error);
+}
+
+/// Writes an uncompressed 16-bit DNG from a 32x32 RGGB sensor whose left half
+/// saw a red scene and right half a blue one.
+#[cfg(feature = "raw")]
+fn write_synthetic_dng(path: &std::path::Path) {
+    const SIZE: u32 = 32;
+    let (bright, dark) = (1500u16, 150u16);
+
+    let mut sensor = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            // RGGB: red photosites on even rows and columns, blue on odd ones
+            let color = (y % 2) + (x % 2);
+            let lit = if x < SIZE / 2 { color == 0 } else { color == 2 };
+            sensor.extend_from_slice(&(if lit { bright } else { dark }).to_le_bytes());
+        }
+    }
+    let make = b"FileFlip\0";
+    let model = b"Synthetic\0";
+
+    // (tag, type, count, value or offset), in ascending tag order
+    let ifd_len = 2 + 18 * 12 + 4;
+    let make_offset = 8 + ifd_len;
+    let model_offset = make_offset + make.len() as u32;
+    let sensor_offset = model_offset + model.len() as u32;
+    let entries: [(u16, u16, u32, u32); 18] = [
+        (0x00FE, 4, 1, 0),                                    // NewSubFileType: main image
+        (0x0100, 4, 1, SIZE),                                 // ImageWidth
+        (0x0101, 4, 1, SIZE),                                 // ImageLength
+        (0x0102, 3, 1, 16),                                   // BitsPerSample
+        (0x0103, 3, 1, 1),                                    // Compression: none
+        (0x0106, 3, 1, 32803),                                // PhotometricInterpretation: CFA
+        (0x010F, 2, make.len() as u32, make_offset),          // Make
+        (0x0110, 2, model.len() as u32, model_offset),        // Model
+        (0x0111, 4, 1, sensor_offset),                        // StripOffsets
+        (0x0115, 3, 1, 1),                                    // SamplesPerPixel
+        (0x0116, 4, 1, SIZE),                                 // RowsPerStrip
+        (0x0117, 4, 1, sensor.len() as u32),                  // StripByteCounts
+        (0x828D, 3, 2, u32::from_le_bytes([2, 0, 2, 0])),     // CFARepeatPatternDim
+        (0x828E, 1, 4, u32::from_le_bytes([0, 1, 1, 2])),     // CFAPattern: RGGB
+        (0xC612, 1, 4, u32::from_le_bytes([1, 4, 0, 0])),     // DNGVersion
+        (0xC613, 1, 4, u32::from_le_bytes([1, 1, 0, 0])),     // DNGBackwardVersion
+        (0xC61A, 3, 1, 0),                                    // BlackLevel
+        (0xC61D, 3, 1, 4095),                                 // WhiteLevel
+    ];
+
+    let mut dng = b"II*\0".to_vec();
+    dng.extend_from_slice(&8u32.to_le_bytes());
+    dng.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, kind, count, value) in entries {
+        dng.extend_from_slice(&tag.to_le_bytes());
+        dng.extend_from_slice(&kind.to_le_bytes());
+        dng.extend_from_slice(&count.to_le_bytes());
+        dng.extend_from_slice(&value.to_le_bytes());
+    }
+    dng.extend_from_slice(&0u32.to_le_bytes());
+    dng.extend_from_slice(make);
+    dng.extend_from_slice(model);
+    dng.extend_from_slice(&sensor);
+
+    fs::write(path, dng).unwrap();
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn test_raw_develop() {
+    let test_dir = setup_test_dir("raw_develop");
+    let input = test_dir.join("sensor.dng");
+    write_synthetic_dng(&input);
+
+    let develop = |exposure: f32| {
+        let options = fileflip_lib::ConversionOptions {
+            output_format: "png".to_string(),
+            output_dir: Some(test_dir.to_string_lossy().to_string()),
+            preserve_metadata: true,
+            raw: fileflip_lib::RawOptions {
+                mode: fileflip_lib::RawMode::Develop,
+                exposure,
+            },
+            ..Default::default()
+        };
+        let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+        assert!(result.success, "RAW development failed: {:?}", result.error);
+        result.output_path.unwrap()
+    };
+
+    // Developed at full sensor resolution, with the colours demosaiced
+    let output = develop(0.0);
+    let img = image::open(&output).unwrap().to_rgb8();
+    assert_eq!(img.dimensions(), (32, 32));
+    let (red, blue) = (img.get_pixel(8, 16), img.get_pixel(24, 16));
+    assert!(red[0] > red[2] && red[0] > red[1], "Left half is not red: {:?}", red);
+    assert!(blue[2] > blue[0] && blue[2] > blue[1], "Right half is not blue: {:?}", blue);
+
+    let tags = exif_tags(&output);
+    assert!(tags.contains(&exif::Tag::Make), "Camera make not carried over: {:?}", tags);
+    assert!(!tags.contains(&exif::Tag::StripOffsets));
+
+    // Exposure compensation brightens the whole image
+    let brighter = image::open(develop(1.0)).unwrap().to_rgb8();
+    let sum = |img: &image::RgbImage| img.pixels().flat_map(|p| p.0).map(u64::from).sum::<u64>();
+    assert!(sum(&brighter) > sum(&img));
+}
+
+struct TestLayer {
+    name: &'static str,
+    rect: (i32, i32, u32, u32),
+    rgba: [u8; 4],
+    blend: &'static [u8; 4],
+    hidden: bool,
+}
+
+/// Writes an 8-bit RGB Photoshop file with RLE-compressed layers.
+fn write_psd(path: &std::path::Path, width: u32, height: u32, layers: &[TestLayer]) {
+    let mut records = Vec::new();
+    let mut channel_data = Vec::new();
+    for layer in layers {
+        let (left, top, w, h) = layer.rect;
+        records.extend_from_slice(&top.to_be_bytes());
+        records.extend_from_slice(&left.to_be_bytes());
+        records.extend_from_slice(&(top + h as i32).to_be_bytes());
+        records.extend_from_slice(&(left + w as i32).to_be_bytes());
+        records.extend_from_slice(&4u16.to_be_bytes());
+        for (id, value) in [(-1i16, layer.rgba[3]), (0, layer.rgba[0]), (1, layer.rgba[1]), (2, layer.rgba[2])] {
+            // PackBits: one literal run per row
+            let mut channel = 1u16.to_be_bytes().to_vec();
+            for _ in 0..h {
+                channel.extend_from_slice(&(1 + w as u16).to_be_bytes());
+            }
+            for _ in 0..h {
+                channel.push(w as u8 - 1);
+                channel.extend(std::iter::repeat_n(value, w as usize));
+            }
+            records.extend_from_slice(&id.to_be_bytes());
+            records.extend_from_slice(&(channel.len() as u32).to_be_bytes());
+            channel_data.extend(channel);
+        }
+        records.extend_from_slice(b"8BIM");
+        records.extend_from_slice(layer.blend);
+        records.extend_from_slice(&[255, 0, if layer.hidden { 2 } else { 0 }, 0]);
+
+        let mut extra = vec![0; 8];
+        let mut name = vec![layer.name.len() as u8];
+        name.extend_from_slice(layer.name.as_bytes());
+        name.resize(name.len().div_ceil(4) * 4, 0);
+        extra.extend(name);
+        records.extend_from_slice(&(extra.len() as u32).to_be_bytes());
+        records.extend(extra);
+    }
+
+    let mut info = (layers.len() as i16).to_be_bytes().to_vec();
+    info.extend(records);
+    info.extend(channel_data);
+    if info.len() % 2 == 1 {
+        info.push(0);
+    }
+
+    let mut psd = b"8BPS".to_vec();
+    psd.extend_from_slice(&1u16.to_be_bytes());
+    psd.extend_from_slice(&[0; 6]);
+    psd.extend_from_slice(&3u16.to_be_bytes());
+    psd.extend_from_slice(&height.to_be_bytes());
+    psd.extend_from_slice(&width.to_be_bytes());
+    psd.extend_from_slice(&8u16.to_be_bytes());
+    psd.extend_from_slice(&3u16.to_be_bytes());
+    psd.extend_from_slice(&0u32.to_be_bytes());
+    psd.extend_from_slice(&0u32.to_be_bytes());
+    psd.extend_from_slice(&(info.len() as u32 + 8).to_be_bytes());
+    psd.extend_from_slice(&(info.len() as u32).to_be_bytes());
+    psd.extend(info);
+    psd.extend_from_slice(&0u32.to_be_bytes());
+    // Raw composite, unused when the file has layers
+    psd.extend_from_slice(&0u16.to_be_bytes());
+    psd.extend(std::iter::repeat_n(0, (width * height * 3) as usize));
+
+    fs::write(path, psd).unwrap();
+}
+
+fn psd_layers() -> Vec<TestLayer> {
+    vec![
+        TestLayer { name: "Background", rect: (0, 0, 4, 4), rgba: [255, 0, 0, 255], blend: b"norm", hidden: false },
+        TestLayer { name: "Shade", rect: (0, 0, 2, 4), rgba: [128, 128, 128, 255], blend: b"mul ", hidden: false },
+        TestLayer { name: "Hidden", rect: (0, 0, 4, 4), rgba: [0, 255, 0, 255], blend: b"norm", hidden: true },
+    ]
+}
+
+fn convert_layered(input: &std::path::Path, layers: fileflip_lib::LayerOptions) -> fileflip_lib::ConversionResult {
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "png".to_string(),
+        overwrite_existing: true,
+        layers,
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Layered conversion failed: {:?}", result.error);
+    result
+}
+
+#[test]
+fn test_psd_flattens_visible_layers() {
+    let test_dir = setup_test_dir("psd_flatten");
+    let input = test_dir.join("design.psd");
+    write_psd(&input, 4, 4, &psd_layers());
+
+    let result = convert_layered(&input, Default::default());
+    assert_eq!(result.output_paths.len(), 1);
+
+    let img = image::open(result.output_path.unwrap()).unwrap().to_rgba8();
+    assert_eq!(img.dimensions(), (4, 4));
+    // Multiply at 50% grey halves the red; the hidden green layer is ignored
+    assert_eq!(img.get_pixel(0, 0).0, [128, 0, 0, 255]);
+    assert_eq!(img.get_pixel(3, 3).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn test_psd_exports_layers() {
+    let test_dir = setup_test_dir("psd_layers");
+    let input = test_dir.join("design.psd");
+    write_psd(&input, 4, 4, &psd_layers());
+
+    let result = convert_layered(
+        &input,
+        fileflip_lib::LayerOptions {
+            export_layers: true,
+            include_hidden: false,
+        },
+    );
+    let layers = &result.output_paths[1..];
+    assert_eq!(layers.len(), 2, "Hidden layer should be skipped: {:?}", layers);
+    assert!(layers[0].ends_with("design_layer01_Background.png"));
+    assert!(layers[1].ends_with("design_layer02_Shade.png"));
+
+    // Layers keep their place on a transparent canvas of the document's size
+    let shade = image::open(&layers[1]).unwrap().to_rgba8();
+    assert_eq!(shade.dimensions(), (4, 4));
+    assert_eq!(shade.get_pixel(0, 0).0, [128, 128, 128, 255]);
+    assert_eq!(shade.get_pixel(3, 0)[3], 0);
+
+    let result = convert_layered(
+        &input,
+        fileflip_lib::LayerOptions {
+            export_layers: true,
+            include_hidden: true,
+        },
+    );
+    assert_eq!(result.output_paths.len(), 4);
+}
+
+/// XCF run-length encoding using literal runs only.
+fn xcf_rle(pixels: &[u8], bpp: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for channel in 0..bpp {
+        let stream: Vec<u8> = pixels.iter().skip(channel).step_by(bpp).copied().collect();
+        for run in stream.chunks(127) {
+            out.push((256 - run.len()) as u8);
+            out.extend_from_slice(run);
+        }
+    }
+    out
+}
+
+struct XcfTestLayer {
+    name: &'static str,
+    offset: (i32, i32),
+    size: (u32, u32),
+    rgba: [u8; 4],
+    mode: u32,
+    visible: bool,
+    group: bool,
+    path: &'static [u32],
+}
+
+/// Writes a version 0 RGB GIMP file with RLE tiles, layers top to bottom.
+fn write_xcf(path: &std::path::Path, width: u32, height: u32, layers: &[XcfTestLayer]) {
+    let u32s = |values: &[u32]| values.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>();
+
+    let mut header = b"gimp xcf file\0".to_vec();
+    header.extend(u32s(&[width, height, 0]));
+    header.extend(u32s(&[17, 1]));
+    header.push(1);
+    header.extend(u32s(&[0, 0]));
+    let pointers_at = header.len();
+    let mut offset = pointers_at + (layers.len() + 2) * 4;
+
+    let mut blocks = Vec::new();
+    let mut pointers = Vec::new();
+    for layer in layers {
+        pointers.push(offset as u32);
+        let (w, h) = layer.size;
+        let mut block = u32s(&[w, h, 1]);
+        block.extend(u32s(&[layer.name.len() as u32 + 1]));
+        block.extend_from_slice(layer.name.as_bytes());
+        block.push(0);
+        block.extend(u32s(&[8, 4, layer.visible as u32, 7, 4, layer.mode, 15, 8]));
+        block.extend_from_slice(&layer.offset.0.to_be_bytes());
+        block.extend_from_slice(&layer.offset.1.to_be_bytes());
+        if layer.group {
+            block.extend(u32s(&[29, 0]));
+        }
+        if !layer.path.is_empty() {
+            block.extend(u32s(&[30, layer.path.len() as u32 * 4]));
+            block.extend(u32s(layer.path));
+        }
+        block.extend(u32s(&[0, 0]));
+
+        // Hierarchy, one level and a single tile follow the layer header
+        let hierarchy = offset + block.len() + 8;
+        let level = hierarchy + 20;
+        let tile = level + 16;
+        block.extend(u32s(&[hierarchy as u32, 0]));
+        block.extend(u32s(&[w, h, 4, level as u32, 0]));
+        block.extend(u32s(&[w, h, tile as u32, 0]));
+        let pixels: Vec<u8> = (0..w * h).flat_map(|_| layer.rgba).collect();
+        block.extend(xcf_rle(&pixels, 4));
+
+        offset += block.len();
+        blocks.push(block);
+    }
+
+    let mut xcf = header;
+    xcf.extend(u32s(&pointers));
+    xcf.extend(u32s(&[0, 0]));
+    assert_eq!(xcf.len(), pointers_at + (layers.len() + 2) * 4);
+    for block in blocks {
+        xcf.extend(block);
+    }
+    fs::write(path, xcf).unwrap();
+}
+
+#[test]
+fn test_xcf_flattens_layers_and_groups() {
+    let test_dir = setup_test_dir("xcf_flatten");
+    let input = test_dir.join("drawing.xcf");
+    let layer = |name, offset, size, rgba, mode, visible, group, path| XcfTestLayer {
+        name,
+        offset,
+        size,
+        rgba,
+        mode,
+        visible,
+        group,
+        path,
+    };
+    write_xcf(
+        &input,
+        4,
+        4,
+        &[
+            layer("Hidden group", (0, 0), (4, 4), [0, 0, 0, 0], 28, false, true, &[]),
+            layer("Inside", (0, 0), (4, 4), [0, 255, 0, 255], 28, true, false, &[0, 0]),
+            layer("Glow", (2, 0), (2, 4), [0, 0, 255, 255], 31, true, false, &[]),
+            layer("Base", (0, 0), (4, 4), [255, 0, 0, 255], 28, true, false, &[]),
+        ],
+    );
+
+    let result = convert_layered(&input, Default::default());
+    let img = image::open(result.output_path.unwrap()).unwrap().to_rgba8();
+    assert_eq!(img.dimensions(), (4, 4));
+    // Screen blue over red; the layer inside the hidden group doesn't show
+    assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(img.get_pixel(3, 3).0, [255, 0, 255, 255]);
+}
+
+#[test]
+fn test_layered_rejects_corrupt_sizes() {
+    let test_dir = setup_test_dir("layered_corrupt");
+    let u32s = |values: &[u32]| values.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>();
+
+    // An XCF tile hierarchy claiming zero bytes per pixel
+    let xcf = test_dir.join("zero_bpp.xcf");
+    write_xcf(
+        &xcf,
+        3,
+        2,
+        &[XcfTestLayer {
+            name: "Base",
+            offset: (0, 0),
+            size: (3, 2),
+            rgba: [255, 0, 0, 255],
+            mode: 28,
+            visible: true,
+            group: false,
+            path: &[],
+        }],
+    );
+    let mut data = fs::read(&xcf).unwrap();
+    let hierarchy = data.windows(12).position(|w| w == u32s(&[3, 2, 4])).unwrap();
+    data[hierarchy + 8..hierarchy + 12].copy_from_slice(&u32s(&[0]));
+    fs::write(&xcf, data).unwrap();
+
+    // A PSB whose layer section length overflows the file offset
+    let psb = test_dir.join("huge.psd");
+    let mut data = b"8BPS".to_vec();
+    data.extend(2u16.to_be_bytes());
+    data.extend([0; 6]);
+    data.extend(3u16.to_be_bytes());
+    data.extend(u32s(&[1, 1]));
+    data.extend(8u16.to_be_bytes());
+    data.extend(3u16.to_be_bytes());
+    data.extend(u32s(&[0, 0]));
+    data.extend(u64::MAX.to_be_bytes());
+    fs::write(&psb, data).unwrap();
+
+    for input in [xcf, psb] {
+        let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &conversion_options("png"), None);
+        assert!(!result.success);
+        assert!(result.error.unwrap().starts_with("Failed to decode image"));
+    }
+}
+
+#[test]
+fn test_video_to_animation_support() {
+    let supported = |from: &str, to: &str| fileflip_lib::is_conversion_supported(from.to_string(), to.to_string());
+    let ffmpeg = fileflip_lib::check_ffmpeg_available();
+    assert_eq!(supported("mp4", "gif"), ffmpeg);
+    assert_eq!(supported("MOV", "webp"), ffmpeg);
+    // Only animated formats can hold a clip
+    assert!(!supported("mp4", "png"));
+}
+
+#[test]
+fn test_video_to_gif() {
+    let test_dir = setup_test_dir("video_to_gif");
+    let input = test_dir.join("clip.mp4");
+    // FFmpeg makes the test clip as well as converting it
+    let made = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-f", "lavfi", "-i", "testsrc=duration=2:size=64x48:rate=25", "-c:v", "mpeg4"])
+        .arg(&input)
+        .status()
+        .is_ok_and(|status| status.success());
+    if !made || !fileflip_lib::check_ffmpeg_available() {
+        eprintln!("Skipping video to GIF test: FFmpeg not installed");
+        return;
+    }
+
+    let options = fileflip_lib::ConversionOptions {
+        output_format: "gif".to_string(),
+        animation: fileflip_lib::AnimationOptions {
+            fps: Some(5.0),
+            start: Some(0.5),
+            duration: Some(1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = fileflip_lib::convert_file_with_options(&input.to_string_lossy(), &options, None);
+    assert!(result.success, "Video to GIF failed: {:?}", result.error);
+
+    let file = fs::File::open(result.output_path.unwrap()).unwrap();
+    let decoder = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(file)).unwrap();
+    let frames = image::AnimationDecoder::into_frames(decoder).collect_frames().unwrap();
+    // One second at 5 fps
+    assert!((4..=6).contains(&frames.len()), "{} frames", frames.len());
+    assert_eq!(frames[0].buffer().dimensions(), (64, 48));
+}
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
 import { Header } from './components/Header';
 import { DropZone } from './components/DropZone';
@@ -9,7 +10,8 @@ import { SettingsPanel } from './components/SettingsPanel';
 import { EmptyState } from './components/EmptyState';
 import { ToastContainer, useToasts } from './components/Toast';
 import { useFiles } from './hooks/useFiles';
-import { Settings, SupportedFormat, getCategory, ToolAvailability, MediaCategory } from './types';
+import { useInputExtensions } from './hooks/useInputExtensions';
+import { Settings, SupportedFormat, getCategory, ToolAvailability, MediaCategory, ConversionProgress } from './types';
 import './index.css';
 
 const DEFAULT_SETTINGS: Settings = {
@@ -21,9 +23,11 @@ const DEFAULT_SETTINGS: Settings = {
   audioBitrate: '192',
   videoCodec: 'h264',
   videoResolution: 'original',
+  pdfImageDpi: 150,
 };
 
 function App() {
+  const inputExtensions = useInputExtensions();
   const {
     files,
     addFiles,
@@ -39,7 +43,7 @@ function App() {
     errorCount,
     audioCount,
     videoCount,
-  } = useFiles();
+  } = useFiles(inputExtensions);
 
   const [settings, setSettings] = useState<Settings>(DEFAULT_SETTINGS);
   const [showSettings, setShowSettings] = useState(false);
@@ -47,6 +51,7 @@ function App() {
   const [globalFormat, setGlobalFormat] = useState<SupportedFormat | null>(null);
   const [toolsAvailable, setToolsAvailable] = useState<ToolAvailability | null>(null);
   const cancelRequestedRef = useRef(false);
+  const activeJobRef = useRef<string | null>(null);
   const { toasts, dismissToast, showSuccess, showError, showWarning } = useToasts();
 
   // Determine the active category based on files
@@ -66,6 +71,18 @@ function App() {
     });
   }, []);
 
+  // Follow progress events for long-running (FFmpeg) conversions
+  useEffect(() => {
+    const unlisten = listen<ConversionProgress>('conversion-progress', (event) => {
+      const { job_id, percent } = event.payload;
+      // Keep the bar short of 100% until the result comes back
+      updateFileProgress(job_id, Math.min(Math.max(30, percent), 90));
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [updateFileProgress]);
+
   // Load settings from localStorage on mount
   useEffect(() => {
     const saved = localStorage.getItem('fileflip-settings');
@@ -119,6 +136,9 @@ function App() {
           setShowSettings(false);
         } else if (isConverting) {
           cancelRequestedRef.current = true;
+          if (activeJobRef.current) {
+            invoke('cancel_conversion', { jobId: activeJobRef.current }).catch(() => {});
+          }
         }
       }
     };
@@ -181,6 +201,7 @@ function App() {
           : undefined;
 
         // Call the Rust backend for conversion
+        activeJobRef.current = file.id;
         const result = await invoke<{ success: boolean; output_path?: string; error?: string }>('cmd_convert_file', {
           inputPath: file.path,
           outputFormat: file.targetFormat,
@@ -189,7 +210,10 @@ function App() {
           preserveMetadata: settings.preserveMetadata,
           overwriteExisting: settings.overwriteExisting,
           bitrate,
+          pdfOptimize: { image_dpi: settings.pdfImageDpi },
+          jobId: file.id,
         });
+        activeJobRef.current = null;
 
         updateFileProgress(file.id, 90);
 
@@ -252,6 +276,10 @@ function App() {
 
   const handleCancel = useCallback(() => {
     cancelRequestedRef.current = true;
+    // Stop the file currently being converted, not just the ones after it
+    if (activeJobRef.current) {
+      invoke('cancel_conversion', { jobId: activeJobRef.current }).catch(() => {});
+    }
   }, []);
 
   const handleGlobalFormatChange = useCallback((format: SupportedFormat) => {
@@ -271,7 +299,7 @@ function App() {
         <Header onSettingsClick={() => setShowSettings(true)} />
 
         {/* Drop Zone */}
-        <DropZone onFilesAdded={addFiles} disabled={isConverting} />
+        <DropZone onFilesAdded={addFiles} inputExtensions={inputExtensions} disabled={isConverting} />
 
         {/* File List */}
         {hasFiles ? (
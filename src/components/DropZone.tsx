@@ -4,10 +4,12 @@ import { IMAGE_EXTENSIONS, DOCUMENT_EXTENSIONS, AUDIO_EXTENSIONS, VIDEO_EXTENSIO
 
 interface DropZoneProps {
   onFilesAdded: (paths: string[]) => void;
+  // Inputs the backend can read in this build (null while loading)
+  inputExtensions: string[] | null;
   disabled?: boolean;
 }
 
-export function DropZone({ onFilesAdded, disabled }: DropZoneProps) {
+export function DropZone({ onFilesAdded, inputExtensions, disabled }: DropZoneProps) {
   const [isDragging, setIsDragging] = useState(false);
 
   const handleDragOver = useCallback((e: DragEvent) => {
@@ -45,29 +47,32 @@ export function DropZone({ onFilesAdded, disabled }: DropZoneProps) {
   const handleClick = useCallback(async () => {
     if (disabled) return;
 
+    const readable = (extensions: string[]) =>
+      inputExtensions ? extensions.filter((ext) => inputExtensions.includes(ext)) : extensions;
+
     try {
       const selected = await open({
         multiple: true,
         filters: [
           {
             name: 'All Supported Files',
-            extensions: ALL_EXTENSIONS,
+            extensions: readable(ALL_EXTENSIONS),
           },
           {
             name: 'Images',
-            extensions: IMAGE_EXTENSIONS,
+            extensions: readable(IMAGE_EXTENSIONS),
           },
           {
             name: 'Documents',
-            extensions: DOCUMENT_EXTENSIONS,
+            extensions: readable(DOCUMENT_EXTENSIONS),
           },
           {
             name: 'Audio',
-            extensions: AUDIO_EXTENSIONS,
+            extensions: readable(AUDIO_EXTENSIONS),
           },
           {
             name: 'Video',
-            extensions: VIDEO_EXTENSIONS,
+            extensions: readable(VIDEO_EXTENSIONS),
           },
         ],
       });
@@ -79,7 +84,7 @@ export function DropZone({ onFilesAdded, disabled }: DropZoneProps) {
     } catch (error) {
       console.error('Failed to open file dialog:', error);
     }
-  }, [onFilesAdded, disabled]);
+  }, [onFilesAdded, inputExtensions, disabled]);
 
   return (
     <div
@@ -133,6 +133,26 @@ export function SettingsPanel({ settings, onSettingsChange, onClose, onSelectOut
             </select>
           </div>
 
+          {/* PDF Image Resolution */}
+          <div>
+            <label className="block text-sm font-bold text-navy mb-2">
+              PDF IMAGE RESOLUTION
+            </label>
+            <select
+              value={settings.pdfImageDpi}
+              onChange={(e) => onSettingsChange({ ...settings, pdfImageDpi: parseInt(e.target.value) })}
+              className="select-brutal w-full"
+            >
+              <option value="72">72 DPI (Smallest)</option>
+              <option value="150">150 DPI (Standard)</option>
+              <option value="300">300 DPI (Print)</option>
+              <option value="0">Keep original</option>
+            </select>
+            <p className="text-xs text-text-muted mt-1 font-mono">
+              For PDF to PDF: sharper images are downsampled to this
+            </p>
+          </div>
+
           {/* Checkboxes */}
           <div className="space-y-3">
             <label className="flex items-center gap-3 cursor-pointer group">
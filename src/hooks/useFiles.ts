@@ -9,7 +9,9 @@ import {
   getAvailableFormats
 } from '../types';
 
-export function useFiles() {
+// `inputExtensions` is the backend's list of readable inputs; until it has
+// loaded, the static extension table is used instead
+export function useFiles(inputExtensions: string[] | null) {
   const [files, setFiles] = useState<FileItem[]>([]);
 
   const addFiles = useCallback((paths: string[]) => {
@@ -32,7 +34,9 @@ export function useFiles() {
           targetFormat: defaultFormat || undefined,
         };
       })
-      .filter((file) => isSupported(file.extension));
+      .filter((file) =>
+        inputExtensions ? inputExtensions.includes(file.extension) : isSupported(file.extension)
+      );
 
     setFiles((prev) => {
       const existingPaths = new Set(prev.map((f) => f.path));
@@ -41,7 +45,7 @@ export function useFiles() {
     });
 
     return newFiles.length;
-  }, []);
+  }, [inputExtensions]);
 
   const removeFile = useCallback((id: string) => {
     setFiles((prev) => prev.filter((f) => f.id !== id));
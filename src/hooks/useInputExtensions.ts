@@ -0,0 +1,24 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { ALL_EXTENSIONS } from '../types';
+
+// Input extensions this build can actually read. Some decoders are optional
+// cargo features, so the backend decides: an extension it offers no targets
+// for is left out. `null` until the backend has answered.
+export function useInputExtensions(): string[] | null {
+  const [extensions, setExtensions] = useState<string[] | null>(null);
+
+  useEffect(() => {
+    Promise.all(
+      ALL_EXTENSIONS.map((extension) =>
+        invoke<string[]>('get_supported_formats', { fromFormat: extension })
+          .then((formats) => (formats.length > 0 ? extension : null))
+          .catch(() => null)
+      )
+    ).then((supported) => {
+      setExtensions(supported.filter((extension): extension is string => extension !== null));
+    });
+  }, []);
+
+  return extensions;
+}
@@ -5,6 +5,7 @@ export type MediaCategory = 'image' | 'document' | 'audio' | 'video';
 export type SupportedFormat =
   // Image formats
   | 'jpg' | 'png' | 'webp' | 'gif' | 'bmp' | 'tiff' | 'ico' | 'avif'
+  | 'ppm' | 'pgm' | 'pbm' | 'pam' | 'tga' | 'qoi' | 'hdr' | 'exr' | 'icns'
   // Document formats
   | 'pdf' | 'txt' | 'md' | 'html' | 'rtf' | 'docx' | 'doc' | 'odt' | 'epub'
   // Audio formats
@@ -41,6 +42,8 @@ export interface Settings {
   // Video settings
   videoCodec: 'h264' | 'h265' | 'vp9';
   videoResolution: 'original' | '720p' | '1080p' | '4k';
+  // PDF to PDF: images shown above this are downsampled, 0 keeps them
+  pdfImageDpi: number;
 }
 
 export interface ConversionOptions {
@@ -53,14 +56,122 @@ export interface ConversionOptions {
   resolution?: string;
 }
 
+// Image edits sent as `transform` to cmd_convert_file (mirrors ImageTransform in Rust)
+export interface ImageTransform {
+  auto_orient?: boolean;
+  crop?: { x: number; y: number; width: number; height: number };
+  rotate?: 0 | 90 | 180 | 270;
+  flip_horizontal?: boolean;
+  flip_vertical?: boolean;
+  width?: number;
+  height?: number;
+  resize_mode?: 'fit' | 'fill' | 'pad' | 'stretch';
+  pad_color?: [number, number, number, number];
+  max_width?: number;
+  max_height?: number;
+  filter?: 'nearest' | 'triangle' | 'catmull_rom' | 'gaussian' | 'lanczos3';
+}
+
+// Camera RAW development sent as `raw` to cmd_convert_file (mirrors RawOptions in Rust)
+export interface RawOptions {
+  mode?: 'develop' | 'preview';
+  exposure?: number;
+}
+
+// Layer export for PSD/XCF sent as `layers` to cmd_convert_file (mirrors LayerOptions in Rust)
+export interface LayerOptions {
+  export_layers?: boolean;
+  include_hidden?: boolean;
+}
+
+// Animated images and video-to-GIF clips sent as `animation` to cmd_convert_file
+// (mirrors AnimationOptions in Rust)
+export interface AnimationOptions {
+  extract_frames?: boolean;
+  fps?: number;
+  start?: number;
+  duration?: number;
+}
+
+// Icon sizes sent as `icon` to cmd_convert_file (mirrors IconOptions in Rust).
+// Empty uses 16-256 for ICO and 16-1024 for ICNS.
+export interface IconOptions {
+  sizes?: number[];
+}
+
+// SVG rasterisation sent as `svg` to cmd_convert_file (mirrors SvgOptions in Rust).
+// Backgrounds are '#rgb', '#rrggbb' or '#rrggbbaa'.
+export interface SvgOptions {
+  width?: number;
+  height?: number;
+  dpi?: number;
+  background?: string;
+  font_dirs?: string[];
+  resources_dir?: string;
+}
+
+// Page layout for images written to PDF, sent as `pdf_page` to cmd_convert_file
+// and inside the options of cmd_images_to_pdf (mirrors PdfPageOptions in Rust)
+export interface PdfPageOptions {
+  size?: 'fit' | 'a4' | 'letter';
+  orientation?: 'auto' | 'portrait' | 'landscape';
+  margin_mm?: number;
+  fit?: 'fit' | 'fill' | 'stretch' | 'original';
+}
+
+// Pages written when converting a PDF to images, sent as `pdf_export` to
+// cmd_convert_file (mirrors PdfExportOptions in Rust)
+export interface PdfExportOptions {
+  pages?: string; // 'all', or 1-based pages and ranges like '1-3,7' or '5-'
+  zip?: boolean;
+}
+
+// PDF to PDF optimisation, sent as `pdf_optimize` to cmd_convert_file
+// (mirrors PdfOptimizeOptions in Rust)
+export interface PdfOptimizeOptions {
+  image_dpi?: number; // default 150; 0 keeps every image's resolution
+}
+
+// Password protection for PDF output, sent as `pdf_encryption` to
+// cmd_convert_file and inside the options of cmd_images_to_pdf (mirrors
+// PdfEncryptionOptions in Rust). Nothing is encrypted without a password;
+// the allow_* permissions default to true.
+export interface PdfEncryptionOptions {
+  user_password?: string; // needed to open the document
+  owner_password?: string; // lifts the restrictions; random if unset
+  allow_print?: boolean;
+  allow_modify?: boolean;
+  allow_copy?: boolean;
+  allow_annotate?: boolean;
+  allow_fill_forms?: boolean;
+  allow_assemble?: boolean; // inserting, deleting and rotating pages
+}
+
+// Output location for cmd_merge_pdfs, cmd_split_pdf, cmd_extract_pdf_pages,
+// cmd_delete_pdf_pages, cmd_reorder_pdf_pages, cmd_rotate_pdf_pages and
+// cmd_decrypt_pdf
+// (mirrors PdfToolOptions in Rust)
+export interface PdfToolOptions {
+  output_dir?: string;
+  overwrite_existing?: boolean;
+}
+
 export interface ConversionResult {
   success: boolean;
   outputPath?: string;
+  outputPaths?: string[];
   error?: string;
   originalSize?: number;
   newSize?: number;
 }
 
+export interface ConversionProgress {
+  job_id: string;
+  percent: number;
+  eta_seconds?: number;
+  speed?: number;
+}
+
 export interface ToolAvailability {
   ffmpeg: boolean;
   libreoffice: boolean;
@@ -72,7 +183,7 @@ export const SUPPORTED_EXTENSIONS: Record<string, { formats: SupportedFormat[];
   // Image formats
   heic: { formats: ['jpg', 'png', 'webp', 'avif', 'gif', 'bmp', 'tiff', 'pdf'], category: 'image' },
   heif: { formats: ['jpg', 'png', 'webp', 'avif', 'gif', 'bmp', 'tiff', 'pdf'], category: 'image' },
-  png: { formats: ['jpg', 'webp', 'bmp', 'gif', 'tiff', 'ico', 'avif', 'pdf'], category: 'image' },
+  png: { formats: ['jpg', 'webp', 'bmp', 'gif', 'tiff', 'ico', 'icns', 'avif', 'pdf'], category: 'image' },
   jpg: { formats: ['png', 'webp', 'bmp', 'gif', 'tiff', 'ico', 'avif', 'pdf'], category: 'image' },
   jpeg: { formats: ['png', 'webp', 'bmp', 'gif', 'tiff', 'ico', 'avif', 'pdf'], category: 'image' },
   jfif: { formats: ['png', 'webp', 'bmp', 'gif', 'tiff', 'ico', 'avif', 'pdf', 'jpg'], category: 'image' },
@@ -81,15 +192,30 @@ export const SUPPORTED_EXTENSIONS: Record<string, { formats: SupportedFormat[];
   tiff: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'avif', 'pdf'], category: 'image' },
   tif: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'avif', 'pdf'], category: 'image' },
   gif: { formats: ['jpg', 'png', 'webp', 'bmp', 'tiff', 'pdf'], category: 'image' },
-  svg: { formats: ['png', 'jpg', 'webp', 'pdf', 'gif', 'bmp', 'tiff', 'avif'], category: 'image' },
-  ico: { formats: ['png', 'jpg', 'webp', 'bmp', 'gif'], category: 'image' },
+  apng: { formats: ['gif', 'webp', 'png', 'jpg', 'bmp', 'tiff', 'pdf'], category: 'image' },
+  svg: { formats: ['png', 'jpg', 'webp', 'pdf', 'gif', 'bmp', 'tiff', 'avif', 'ico', 'icns'], category: 'image' },
+  ico: { formats: ['png', 'jpg', 'webp', 'bmp', 'gif', 'icns'], category: 'image' },
+  icns: { formats: ['png', 'jpg', 'webp', 'bmp', 'ico'], category: 'image' },
   avif: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'pdf'], category: 'image' },
-  ppm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff'], category: 'image' },
-  pgm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff'], category: 'image' },
-  pbm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff'], category: 'image' },
+  ppm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'pgm', 'pbm', 'tga', 'qoi', 'pdf'], category: 'image' },
+  raw: { formats: ['jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  cr2: { formats: ['jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  nef: { formats: ['jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  arw: { formats: ['jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  dng: { formats: ['jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  psd: { formats: ['png', 'jpg', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  xcf: { formats: ['png', 'jpg', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  pgm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'ppm', 'pbm', 'tga', 'qoi', 'pdf'], category: 'image' },
+  pbm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'ppm', 'pgm', 'tga', 'qoi', 'pdf'], category: 'image' },
+  pam: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'ppm', 'tga', 'qoi', 'pdf'], category: 'image' },
+  pnm: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'ppm', 'tga', 'qoi', 'pdf'], category: 'image' },
+  tga: { formats: ['jpg', 'png', 'webp', 'bmp', 'gif', 'tiff', 'qoi', 'ppm', 'pdf'], category: 'image' },
+  qoi: { formats: ['png', 'jpg', 'webp', 'bmp', 'gif', 'tiff', 'tga', 'ppm', 'pdf'], category: 'image' },
+  hdr: { formats: ['exr', 'jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
+  exr: { formats: ['hdr', 'jpg', 'png', 'webp', 'tiff', 'avif', 'pdf'], category: 'image' },
 
   // Document formats
-  pdf: { formats: ['jpg', 'png', 'txt', 'md', 'html'], category: 'document' },
+  pdf: { formats: ['jpg', 'png', 'txt', 'md', 'html', 'rtf', 'pdf'], category: 'document' },
   txt: { formats: ['pdf', 'md', 'html', 'rtf'], category: 'document' },
   md: { formats: ['pdf', 'txt', 'html', 'rtf'], category: 'document' },
   markdown: { formats: ['pdf', 'txt', 'html', 'rtf'], category: 'document' },
@@ -119,30 +245,31 @@ export const SUPPORTED_EXTENSIONS: Record<string, { formats: SupportedFormat[];
   ac3: { formats: ['mp3', 'wav', 'flac', 'ogg', 'aac', 'm4a'], category: 'audio' },
 
   // Video formats
-  mp4: { formats: ['webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  webm: { formats: ['mp4', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  mkv: { formats: ['mp4', 'webm', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  avi: { formats: ['mp4', 'webm', 'mkv', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  mov: { formats: ['mp4', 'webm', 'mkv', 'avi', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  flv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'wmv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  wmv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', '3gp', 'mts', 'ts', 'ogv'], category: 'video' },
-  '3gp': { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'mts', 'ts', 'ogv'], category: 'video' },
-  mts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'ts', 'ogv'], category: 'video' },
-  m2ts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'ts', 'ogv'], category: 'video' },
-  ts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ogv'], category: 'video' },
-  vob: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv'], category: 'video' },
-  ogv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts'], category: 'video' },
-  m4v: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv'], category: 'video' },
-  mpg: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv'], category: 'video' },
-  mpeg: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv'], category: 'video' },
-  divx: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov'], category: 'video' },
-  asf: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'wmv'], category: 'video' },
+  mp4: { formats: ['webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  webm: { formats: ['mp4', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  mkv: { formats: ['mp4', 'webm', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  avi: { formats: ['mp4', 'webm', 'mkv', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  mov: { formats: ['mp4', 'webm', 'mkv', 'avi', 'flv', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  flv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'wmv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  wmv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', '3gp', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  '3gp': { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'mts', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  mts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  m2ts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'ts', 'ogv', 'gif', 'webp'], category: 'video' },
+  ts: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ogv', 'gif', 'webp'], category: 'video' },
+  vob: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'gif', 'webp'], category: 'video' },
+  ogv: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', '3gp', 'mts', 'ts', 'gif', 'webp'], category: 'video' },
+  m4v: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'gif', 'webp'], category: 'video' },
+  mpg: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'gif', 'webp'], category: 'video' },
+  mpeg: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'flv', 'wmv', 'gif', 'webp'], category: 'video' },
+  divx: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'gif', 'webp'], category: 'video' },
+  asf: { formats: ['mp4', 'webm', 'mkv', 'avi', 'mov', 'wmv', 'gif', 'webp'], category: 'video' },
 };
 
 // Extension lists by category
 export const IMAGE_EXTENSIONS = [
   'heic', 'heif', 'png', 'jpg', 'jpeg', 'jfif', 'webp', 'bmp', 'tiff', 'tif',
-  'gif', 'svg', 'ico', 'avif', 'ppm', 'pgm', 'pbm'
+  'gif', 'svg', 'ico', 'avif', 'ppm', 'pgm', 'pbm', 'raw', 'cr2', 'nef', 'arw', 'dng',
+  'psd', 'xcf', 'pam', 'pnm', 'tga', 'qoi', 'hdr', 'exr', 'icns', 'apng'
 ];
 export const DOCUMENT_EXTENSIONS = ['pdf', 'txt', 'md', 'markdown', 'html', 'htm', 'rtf', 'docx', 'doc', 'odt', 'epub'];
 export const AUDIO_EXTENSIONS = ['mp3', 'wav', 'flac', 'ogg', 'aac', 'm4a', 'opus', 'wma', 'aiff', 'aif', 'ape', 'wv', 'ac3'];
@@ -158,8 +285,17 @@ export const FORMAT_INFO: Record<SupportedFormat, { name: string; description: s
   gif: { name: 'GIF', description: 'Supports animation, limited colors', lossy: true, category: 'image' },
   bmp: { name: 'Bitmap', description: 'Uncompressed, large files', lossy: false, category: 'image' },
   tiff: { name: 'TIFF', description: 'High quality, large files', lossy: false, category: 'image' },
-  ico: { name: 'ICO', description: 'Icon format for Windows', lossy: false, category: 'image' },
+  ico: { name: 'ICO', description: 'Windows icon, 16 to 256 px in one file', lossy: false, category: 'image' },
+  icns: { name: 'ICNS', description: 'macOS app icon, 16 to 1024 px in one file', lossy: false, category: 'image' },
   avif: { name: 'AVIF', description: 'Next-gen format, excellent compression', lossy: true, category: 'image' },
+  ppm: { name: 'PPM', description: 'Netpbm colour image, uncompressed', lossy: false, category: 'image' },
+  pgm: { name: 'PGM', description: 'Netpbm grayscale image, uncompressed', lossy: false, category: 'image' },
+  pbm: { name: 'PBM', description: 'Netpbm black-and-white bitmap', lossy: false, category: 'image' },
+  pam: { name: 'PAM', description: 'Netpbm with transparency and 16-bit depth', lossy: false, category: 'image' },
+  tga: { name: 'TGA', description: 'Targa, used by games and 3D tools', lossy: false, category: 'image' },
+  qoi: { name: 'QOI', description: 'Fast lossless compression', lossy: false, category: 'image' },
+  hdr: { name: 'Radiance HDR', description: 'High dynamic range, no transparency', lossy: false, category: 'image' },
+  exr: { name: 'OpenEXR', description: 'High dynamic range for VFX and 3D', lossy: false, category: 'image' },
 
   // Document formats
   pdf: { name: 'PDF', description: 'Document format, printable', lossy: false, category: 'document' },